@@ -1,12 +1,28 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
-use pyo3::exceptions::PyTypeError;
+use pyo3::{exceptions::PyTypeError, Python};
 use querent_synapse::{
-	callbacks::{interface::EventHandler, EventType},
+	callbacks::{
+		interface::EventHandler, EventFilter, EventJournal, EventRecvError, EventReplayer,
+		EventType, GraphTriple, OverflowPolicy, ReplayPace, VectorEmbedding,
+	},
 	comm::ChannelHandler,
-	config::{config::WorkflowConfig, Config},
+	config::{
+		config::{ResourceConfig, WorkflowConfig},
+		Config,
+	},
 	cross::{CLRepr, StringType},
-	querent::workflow::{Workflow, WorkflowManager},
+	querent::{
+		py_runtime,
+		query::{QueryEngine, QueryEngineManager},
+		workflow::{
+			OverlapPolicy, ReloadMode, RetryPolicy, RunFilter, RunJournal, Runnable,
+			RunnableManager, Schedule, Severity, Workflow, WorkflowBuilder, WorkflowManager,
+			WorkflowStatus,
+		},
+		ConfigInjection, LabelSelector, PyCallOptions, PythonEnvironment, Querent, TaskPriority,
+		WorkerPool,
+	},
 };
 
 #[pyo3_asyncio::tokio::main]
@@ -37,10 +53,14 @@ async fn workflow_manager_basic_tests() -> pyo3::PyResult<()> {
 		arguments: args,
 		code: None,
 		config: None,
+		..Default::default()
 	};
 	assert!(workflow_manager.add_workflow(test_flow).is_ok());
 	match workflow_manager.start_workflows().await {
-		Ok(_) => assert!(true),
+		Ok(report) => {
+			assert!(!report.is_empty());
+			assert!(report.is_success(), "Failed workflows: {:?}", report.failed());
+		},
 		Err(e) => panic!("Error starting workflows: {}", e),
 	}
 	Ok(())
@@ -65,6 +85,7 @@ async fn workflow_manager_multiple_workflows() -> pyo3::PyResult<()> {
 		arguments: args1,
 		code: None,
 		config: None,
+		..Default::default()
 	};
 	assert!(workflow_manager.add_workflow(test_flow1).is_ok());
 
@@ -78,12 +99,16 @@ async fn workflow_manager_multiple_workflows() -> pyo3::PyResult<()> {
 		arguments: args2,
 		code: None,
 		config: None,
+		..Default::default()
 	};
 	assert!(workflow_manager.add_workflow(test_flow2).is_ok());
 
 	// Start workflows
 	match workflow_manager.start_workflows().await {
-		Ok(_) => assert!(true),
+		Ok(report) => {
+			assert!(!report.is_empty());
+			assert!(report.is_success(), "Failed workflows: {:?}", report.failed());
+		},
 		Err(e) => panic!("Error starting workflows: {}", e),
 	}
 	Ok(())
@@ -116,10 +141,14 @@ async fn workflow_manager_python_tests() -> pyo3::PyResult<()> {
 		arguments: args,
 		code: Some(_CODE.to_string()),
 		config: None,
+		..Default::default()
 	};
 	assert!(workflow_manager.add_workflow(test_flow).is_ok());
 	match workflow_manager.start_workflows().await {
-		Ok(_) => assert!(true),
+		Ok(report) => {
+			assert!(!report.is_empty());
+			assert!(report.is_success(), "Failed workflows: {:?}", report.failed());
+		},
 		Err(e) => panic!("Error starting workflows: {}", e),
 	}
 	Ok(())
@@ -153,13 +182,15 @@ async fn workflow_manager_python_test_with_result() -> pyo3::PyResult<()> {
 		arguments: args,
 		code: Some(CODE_WITH_RESULT.to_string()),
 		config: None,
+		..Default::default()
 	};
 	assert!(workflow_manager.add_workflow(test_flow).is_ok());
 
 	// Start workflows
 	match workflow_manager.start_workflows().await {
-		Ok(_) => {
-			assert!(true);
+		Ok(report) => {
+			assert!(!report.is_empty());
+			assert!(report.is_success(), "Failed workflows: {:?}", report.failed());
 		},
 		Err(e) => panic!("Error starting workflows: {}", e),
 	}
@@ -215,6 +246,7 @@ async fn workflow_manager_python_tests_with_config() -> pyo3::PyResult<()> {
 		code: Some(CODE_CONFIG.to_string()),
 		arguments: vec![CLRepr::String("Querent".to_string(), StringType::Normal)],
 		config: Some(config),
+		..Default::default()
 	};
 
 	// Create a WorkflowManager and add the Workflow
@@ -223,7 +255,10 @@ async fn workflow_manager_python_tests_with_config() -> pyo3::PyResult<()> {
 
 	// Start the workflows
 	match workflow_manager.start_workflows().await {
-		Ok(_) => assert!(true),
+		Ok(report) => {
+			assert!(!report.is_empty());
+			assert!(report.is_success(), "Failed workflows: {:?}", report.failed());
+		},
 		Err(e) => panic!("Error starting workflows: {}", e),
 	}
 
@@ -278,6 +313,7 @@ async fn workflow_manager_python_tests_with_config2() -> pyo3::PyResult<()> {
 		code: Some(CODE_CONFIG_2.to_string()),
 		arguments: vec![CLRepr::String("Querent".to_string(), StringType::Normal)],
 		config: Some(config),
+		..Default::default()
 	};
 
 	// Create a WorkflowManager and add the Workflow
@@ -286,7 +322,10 @@ async fn workflow_manager_python_tests_with_config2() -> pyo3::PyResult<()> {
 
 	// Start the workflows
 	match workflow_manager.start_workflows().await {
-		Ok(_) => assert!(true),
+		Ok(report) => {
+			assert!(!report.is_empty());
+			assert!(report.is_success(), "Failed workflows: {:?}", report.failed());
+		},
 		Err(e) => panic!("Error starting workflows: {}", e),
 	}
 
@@ -341,6 +380,7 @@ async fn workflow_manager_python_tests_with_config_channel() -> pyo3::PyResult<(
 		code: Some(CODE_CONFIG_CHANNEL.to_string()),
 		arguments: vec![CLRepr::String("Querent".to_string(), StringType::Normal)],
 		config: Some(config),
+		..Default::default()
 	};
 
 	// Create a WorkflowManager and add the Workflow
@@ -349,7 +389,10 @@ async fn workflow_manager_python_tests_with_config_channel() -> pyo3::PyResult<(
 
 	// Start the workflows
 	match workflow_manager.start_workflows().await {
-		Ok(_) => assert!(true),
+		Ok(report) => {
+			assert!(!report.is_empty());
+			assert!(report.is_success(), "Failed workflows: {:?}", report.failed());
+		},
 		Err(e) => panic!("Error starting workflows: {}", e),
 	}
 
@@ -414,6 +457,7 @@ async fn workflow_manager_python_tests_with_config_events() -> pyo3::PyResult<()
 		code: Some(CODE_CONFIG_EVENT_HANDLER.to_string()),
 		arguments: vec![CLRepr::String("Querent".to_string(), StringType::Normal)],
 		config: Some(config),
+		..Default::default()
 	};
 
 	// Create a WorkflowManager and add the Workflow
@@ -422,7 +466,10 @@ async fn workflow_manager_python_tests_with_config_events() -> pyo3::PyResult<()
 
 	// Start the workflows
 	match workflow_manager.start_workflows().await {
-		Ok(_) => assert!(true),
+		Ok(report) => {
+			assert!(!report.is_empty());
+			assert!(report.is_success(), "Failed workflows: {:?}", report.failed());
+		},
 		Err(e) => panic!("Error starting workflows: {}", e),
 	}
 
@@ -486,6 +533,7 @@ async fn workflow_manager_python_tests_with_config_events_without_image_id() ->
 		code: Some(CODE_CONFIG_EVENT_HANDLER_WITHOUT_IMAGE_ID.to_string()),
 		arguments: vec![CLRepr::String("Querent".to_string(), StringType::Normal)],
 		config: Some(config),
+		..Default::default()
 	};
 
 	// Create a WorkflowManager and add the Workflow
@@ -494,7 +542,10 @@ async fn workflow_manager_python_tests_with_config_events_without_image_id() ->
 
 	// Start the workflows
 	match workflow_manager.start_workflows().await {
-		Ok(_) => assert!(true),
+		Ok(report) => {
+			assert!(!report.is_empty());
+			assert!(report.is_success(), "Failed workflows: {:?}", report.failed());
+		},
 		Err(e) => panic!("Error starting workflows: {}", e),
 	}
 
@@ -543,6 +594,7 @@ async fn workflow_manager_python_tests_with_config_events_mpsc() -> pyo3::PyResu
 		code: Some(CODE_CONFIG_EVENT_HANDLER.to_string()),
 		arguments: vec![CLRepr::String("Querent".to_string(), StringType::Normal)],
 		config: Some(config),
+		..Default::default()
 	};
 
 	// Create a WorkflowManager and add the Workflow
@@ -610,6 +662,7 @@ async fn workflow_manager_python_tests_with_config_events_mpsc_separate_receiver
 		code: Some(CODE_CONFIG_EVENT_HANDLER.to_string()),
 		arguments: vec![CLRepr::String("Querent".to_string(), StringType::Normal)],
 		config: Some(config),
+		..Default::default()
 	};
 
 	// Create a WorkflowManager and add the Workflow
@@ -697,6 +750,7 @@ async fn workflow_manager_python_tests_with_config_channel_break() -> pyo3::PyRe
 		code: Some(CODE_CONFIG_CHANNEL_LOOP.to_string()),
 		arguments: vec![CLRepr::String("Querent".to_string(), StringType::Normal)],
 		config: Some(config),
+		..Default::default()
 	};
 
 	// Create a WorkflowManager and add the Workflow
@@ -705,9 +759,1671 @@ async fn workflow_manager_python_tests_with_config_channel_break() -> pyo3::PyRe
 
 	// Start the workflows
 	match workflow_manager.start_workflows().await {
-		Ok(_) => assert!(true),
+		Ok(report) => {
+			assert!(!report.is_empty());
+			assert!(report.is_success(), "Failed workflows: {:?}", report.failed());
+		},
 		Err(e) => panic!("Error starting workflows: {}", e),
 	}
 
 	Ok(())
 }
+
+const CODE_SLEEP_AND_RETURN: &str = r#"
+import asyncio
+
+async def sleep_and_return(value):
+    await asyncio.sleep(1)
+    return value
+"#;
+
+const CODE_FAILING: &str = r#"
+async def fail():
+    raise ValueError("boom")
+"#;
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_runs_workflows_concurrently_with_report() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	for (id, value) in [("concurrent_1", 1), ("concurrent_2", 2)] {
+		let workflow = WorkflowBuilder::new(id)
+			.attr(Some("sleep_and_return".to_string()))
+			.code(Some(CODE_SLEEP_AND_RETURN.to_string()))
+			.arguments(vec![CLRepr::Int(value)])
+			.build();
+		assert!(workflow_manager.add_workflow(workflow).is_ok());
+	}
+	let failing = WorkflowBuilder::new("concurrent_failing")
+		.attr(Some("fail".to_string()))
+		.code(Some(CODE_FAILING.to_string()))
+		.build();
+	assert!(workflow_manager.add_workflow(failing).is_ok());
+
+	let started = std::time::Instant::now();
+	let report = workflow_manager.start_workflows().await.expect("Failed to start workflows");
+	assert!(started.elapsed() < std::time::Duration::from_millis(1900));
+
+	assert_eq!(report.len(), 3);
+	assert!(!report.is_success());
+	assert_eq!(report.failed(), vec!["concurrent_failing"]);
+	let first = report.get("concurrent_1").expect("missing report");
+	assert!(matches!(first.output(), Some(CLRepr::Int(1))));
+	assert!(first.duration >= std::time::Duration::from_secs(1));
+	assert!(matches!(report.get("concurrent_2").and_then(|r| r.output()), Some(CLRepr::Int(2))));
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_tracks_workflow_status() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	let ok = WorkflowBuilder::new("status_ok")
+		.attr(Some("sleep_and_return".to_string()))
+		.code(Some(CODE_SLEEP_AND_RETURN.to_string()))
+		.arguments(vec![CLRepr::Int(1)])
+		.build();
+	let failing = WorkflowBuilder::new("status_failing")
+		.attr(Some("fail".to_string()))
+		.code(Some(CODE_FAILING.to_string()))
+		.build();
+	assert!(workflow_manager.add_workflow(ok).is_ok());
+	assert!(workflow_manager.add_workflow(failing).is_ok());
+	assert_eq!(workflow_manager.status("status_ok").unwrap().status, WorkflowStatus::Pending);
+
+	let mut events = workflow_manager.subscribe_status();
+	workflow_manager.start_workflows().await.expect("Failed to start workflows");
+
+	let ok_state = workflow_manager.status("status_ok").unwrap();
+	assert_eq!(ok_state.status, WorkflowStatus::Completed);
+	let statuses: Vec<_> = ok_state.history.iter().map(|t| t.status).collect();
+	assert_eq!(
+		statuses,
+		vec![WorkflowStatus::Pending, WorkflowStatus::Running, WorkflowStatus::Completed]
+	);
+	assert!(ok_state.finished_at().unwrap() >= ok_state.started_at().unwrap());
+
+	let failing_state = workflow_manager.status("status_failing").unwrap();
+	assert_eq!(failing_state.status, WorkflowStatus::Failed);
+	assert!(failing_state.history.last().unwrap().message.as_ref().unwrap().contains("boom"));
+	assert_eq!(workflow_manager.list_status().len(), 2);
+
+	let mut ok_transitions = Vec::new();
+	while let Ok(event) = events.try_recv() {
+		if event.workflow_id == "status_ok" {
+			ok_transitions.push((event.from, event.to));
+		}
+	}
+	assert_eq!(
+		ok_transitions,
+		vec![
+			(WorkflowStatus::Pending, WorkflowStatus::Running),
+			(WorkflowStatus::Running, WorkflowStatus::Completed)
+		]
+	);
+	Ok(())
+}
+
+const CODE_CANCELLABLE: &str = r#"
+import asyncio
+import builtins
+
+async def wait_forever():
+    try:
+        await asyncio.sleep(30)
+    except asyncio.CancelledError:
+        builtins.querent_cancelled = True
+        raise
+"#;
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_cancels_running_workflow() -> pyo3::PyResult<()> {
+	let workflow_manager =
+		Arc::new(WorkflowManager::new().expect("Failed to create WorkflowManager"));
+	let workflow = WorkflowBuilder::new("cancellable")
+		.attr(Some("wait_forever".to_string()))
+		.code(Some(CODE_CANCELLABLE.to_string()))
+		.build();
+	assert!(workflow_manager.add_workflow(workflow).is_ok());
+	assert!(workflow_manager.cancel("cancellable").is_err());
+
+	let manager = workflow_manager.clone();
+	let run = tokio::spawn(async move { manager.start_workflows().await });
+	tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+	assert_eq!(workflow_manager.status("cancellable").unwrap().status, WorkflowStatus::Running);
+	workflow_manager.cancel("cancellable").expect("Failed to cancel workflow");
+
+	let report = run.await.expect("Join failed").expect("Failed to start workflows");
+	let error = report.get("cancellable").and_then(|r| r.error()).expect("Expected an error");
+	assert!(error.is_cancelled());
+	assert_eq!(workflow_manager.status("cancellable").unwrap().status, WorkflowStatus::Cancelled);
+
+	let mut seen_in_python = false;
+	for _ in 0..20 {
+		seen_in_python = Python::with_gil(|py| {
+			py.eval("getattr(__import__('builtins'), 'querent_cancelled', False)", None, None)
+				.and_then(|v| v.extract::<bool>())
+				.unwrap_or(false)
+		});
+		if seen_in_python {
+			break;
+		}
+		tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+	}
+	assert!(seen_in_python);
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_cancels_workflows_waiting_for_dependencies() -> pyo3::PyResult<()> {
+	let workflow_manager =
+		Arc::new(WorkflowManager::new().expect("Failed to create WorkflowManager"));
+	let upstream = WorkflowBuilder::new("cancel_upstream")
+		.attr(Some("wait_forever".to_string()))
+		.code(Some(CODE_CANCELLABLE.to_string()))
+		.build();
+	let downstream = stage("cancel_downstream", "collect", vec![CLRepr::Int(1)])
+		.depends_on("cancel_upstream")
+		.build();
+	assert!(workflow_manager.add_workflow(upstream).is_ok());
+	assert!(workflow_manager.add_workflow(downstream).is_ok());
+
+	let manager = workflow_manager.clone();
+	let run = tokio::spawn(async move { manager.start_workflows().await });
+	tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+	workflow_manager
+		.cancel("cancel_downstream")
+		.expect("Failed to cancel waiting workflow");
+	let mut status = WorkflowStatus::Pending;
+	for _ in 0..20 {
+		status = workflow_manager.status("cancel_downstream").unwrap().status;
+		if status == WorkflowStatus::Cancelled {
+			break;
+		}
+		tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+	}
+	assert_eq!(status, WorkflowStatus::Cancelled);
+	assert_eq!(workflow_manager.status("cancel_upstream").unwrap().status, WorkflowStatus::Running);
+
+	workflow_manager.cancel("cancel_upstream").expect("Failed to cancel workflow");
+	let report = run.await.expect("Join failed").expect("Failed to start workflows");
+	let downstream_report = report.get("cancel_downstream").unwrap();
+	assert_eq!(downstream_report.status, WorkflowStatus::Cancelled);
+	assert_eq!(downstream_report.attempts, 0);
+	assert!(downstream_report.error().unwrap().is_cancelled());
+	assert_eq!(report.get("cancel_upstream").unwrap().status, WorkflowStatus::Cancelled);
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_enforces_timeouts_and_deadlines() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	let with_timeout = WorkflowBuilder::new("with_timeout")
+		.attr(Some("wait_forever".to_string()))
+		.code(Some(CODE_CANCELLABLE.to_string()))
+		.timeout(std::time::Duration::from_millis(300))
+		.build();
+	let past_deadline = WorkflowBuilder::new("past_deadline")
+		.attr(Some("wait_forever".to_string()))
+		.code(Some(CODE_CANCELLABLE.to_string()))
+		.deadline(std::time::SystemTime::now())
+		.build();
+	assert!(workflow_manager.add_workflow(with_timeout).is_ok());
+	assert!(workflow_manager.add_workflow(past_deadline).is_ok());
+
+	let started = std::time::Instant::now();
+	let report = workflow_manager.start_workflows().await.expect("Failed to start workflows");
+	assert!(started.elapsed() < std::time::Duration::from_secs(5));
+	for id in ["with_timeout", "past_deadline"] {
+		let error = report.get(id).and_then(|r| r.error()).expect("Expected an error");
+		assert!(error.is_timeout(), "unexpected error for {}: {}", id, error);
+		assert_eq!(workflow_manager.status(id).unwrap().status, WorkflowStatus::Failed);
+	}
+	Ok(())
+}
+
+const CODE_FLAKY: &str = r#"
+import builtins
+
+async def flaky(failures):
+    builtins.querent_flaky_calls = getattr(builtins, 'querent_flaky_calls', 0) + 1
+    if builtins.querent_flaky_calls <= failures:
+        raise ConnectionRefusedError("try again")
+    return builtins.querent_flaky_calls
+"#;
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_retries_failed_workflows() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	let policy = RetryPolicy::new(3)
+		.backoff(std::time::Duration::from_millis(10), std::time::Duration::from_millis(50))
+		.retry_on("ConnectionError");
+	let flaky = WorkflowBuilder::new("flaky")
+		.attr(Some("flaky".to_string()))
+		.code(Some(CODE_FLAKY.to_string()))
+		.arguments(vec![CLRepr::Int(2)])
+		.retry(policy.clone())
+		.build();
+	let not_retried = WorkflowBuilder::new("not_retried")
+		.attr(Some("fail".to_string()))
+		.code(Some(CODE_FAILING.to_string()))
+		.retry(policy)
+		.build();
+	assert!(workflow_manager.add_workflow(flaky).is_ok());
+	assert!(workflow_manager.add_workflow(not_retried).is_ok());
+
+	let report = workflow_manager.start_workflows().await.expect("Failed to start workflows");
+	let flaky_report = report.get("flaky").unwrap();
+	assert_eq!(flaky_report.attempts, 3);
+	assert!(matches!(flaky_report.output(), Some(CLRepr::Int(3))));
+	let history: Vec<_> = workflow_manager
+		.status("flaky")
+		.unwrap()
+		.history
+		.iter()
+		.map(|t| (t.status, t.attempt))
+		.collect();
+	assert_eq!(
+		history,
+		vec![
+			(WorkflowStatus::Pending, 0),
+			(WorkflowStatus::Running, 1),
+			(WorkflowStatus::Retrying, 1),
+			(WorkflowStatus::Running, 2),
+			(WorkflowStatus::Retrying, 2),
+			(WorkflowStatus::Running, 3),
+			(WorkflowStatus::Completed, 3),
+		]
+	);
+
+	let not_retried_report = report.get("not_retried").unwrap();
+	assert_eq!(not_retried_report.attempts, 1);
+	assert_eq!(not_retried_report.error().unwrap().python_exception_type(), Some("ValueError"));
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_does_not_retry_past_deadline() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	let policy = RetryPolicy::new(5)
+		.backoff(std::time::Duration::from_secs(2), std::time::Duration::from_secs(2))
+		.jitter(0.0);
+	let deadline = std::time::SystemTime::now() + std::time::Duration::from_secs(1);
+	let failing = WorkflowBuilder::new("failing_near_deadline")
+		.attr(Some("fail".to_string()))
+		.code(Some(CODE_FAILING.to_string()))
+		.retry(policy)
+		.deadline(deadline)
+		.build();
+	assert!(workflow_manager.add_workflow(failing).is_ok());
+
+	let started = std::time::Instant::now();
+	let report = workflow_manager.start_workflows().await.expect("Failed to start workflows");
+	assert!(started.elapsed() < std::time::Duration::from_secs(2));
+	let failing_report = report.get("failing_near_deadline").unwrap();
+	assert_eq!(failing_report.attempts, 1);
+	assert_eq!(failing_report.error().unwrap().python_exception_type(), Some("ValueError"));
+	assert_eq!(
+		workflow_manager.status("failing_near_deadline").unwrap().status,
+		WorkflowStatus::Failed
+	);
+	Ok(())
+}
+
+const CODE_STAGES: &str = r#"
+async def collect(value):
+    return value + 1
+
+async def extract(value):
+    return value * 10
+
+async def index(scale, value):
+    return value + scale
+"#;
+
+fn stage(id: &str, attr: &str, arguments: Vec<CLRepr>) -> WorkflowBuilder {
+	WorkflowBuilder::new(id)
+		.attr(Some(attr.to_string()))
+		.code(Some(CODE_STAGES.to_string()))
+		.arguments(arguments)
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_runs_dependency_dag() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	let index = stage("dag_index", "index", vec![CLRepr::Int(5)])
+		.depends_on_result("dag_extract")
+		.build();
+	let extract = stage("dag_extract", "extract", vec![]).depends_on_result("dag_collect").build();
+	let collect = stage("dag_collect", "collect", vec![CLRepr::Int(1)]).build();
+	// Registered before its dependency, which is fine as long as it exists when started.
+	assert!(workflow_manager.add_workflow(index).is_ok());
+	assert!(workflow_manager.add_workflow(extract).is_ok());
+	assert!(workflow_manager.add_workflow(collect).is_ok());
+	let cyclic = stage("dag_cyclic", "collect", vec![CLRepr::Int(1)]).depends_on("dag_index");
+	let collect_again = WorkflowBuilder::from_workflow(cyclic.build()).depends_on("dag_cyclic");
+	assert!(workflow_manager.add_workflow(collect_again.build()).is_err());
+
+	let failing = WorkflowBuilder::new("dag_failing")
+		.attr(Some("fail".to_string()))
+		.code(Some(CODE_FAILING.to_string()))
+		.build();
+	let downstream = stage("dag_downstream", "collect", vec![CLRepr::Int(1)])
+		.depends_on("dag_failing")
+		.build();
+	let unknown = stage("dag_unknown", "collect", vec![CLRepr::Int(1)])
+		.depends_on("dag_missing")
+		.build();
+	assert!(workflow_manager.add_workflow(failing).is_ok());
+	assert!(workflow_manager.add_workflow(downstream).is_ok());
+	assert!(workflow_manager.add_workflow(unknown).is_ok());
+
+	let report = workflow_manager.start_workflows().await.expect("Failed to start workflows");
+	assert_eq!(report.len(), 6);
+	assert!(matches!(report.get("dag_collect").and_then(|r| r.output()), Some(CLRepr::Int(2))));
+	assert!(matches!(report.get("dag_extract").and_then(|r| r.output()), Some(CLRepr::Int(20))));
+	assert!(matches!(report.get("dag_index").and_then(|r| r.output()), Some(CLRepr::Int(25))));
+	assert_eq!(report.skipped(), vec!["dag_downstream"]);
+	assert_eq!(workflow_manager.status("dag_downstream").unwrap().status, WorkflowStatus::Skipped);
+	let mut failed = report.failed();
+	failed.sort();
+	assert_eq!(failed, vec!["dag_failing", "dag_unknown"]);
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_stores_workflow_results() -> pyo3::PyResult<()> {
+	let workflow_manager =
+		Arc::new(WorkflowManager::new().expect("Failed to create WorkflowManager"));
+	let workflow = WorkflowBuilder::new("result_store")
+		.attr(Some("sleep_and_return".to_string()))
+		.code(Some(CODE_SLEEP_AND_RETURN.to_string()))
+		.arguments(vec![CLRepr::Int(42)])
+		.build();
+	assert!(workflow_manager.add_workflow(workflow).is_ok());
+	assert!(workflow_manager.result("result_store").is_none());
+	assert!(workflow_manager.await_result("unknown").await.is_err());
+	// Registered but never started, so there is nothing to wait for.
+	let never_ran = tokio::time::timeout(
+		std::time::Duration::from_secs(1),
+		workflow_manager.await_result("result_store"),
+	)
+	.await
+	.expect("Waited for a workflow that never ran");
+	assert!(never_ran.is_err());
+
+	let run = tokio::spawn({
+		let workflow_manager = workflow_manager.clone();
+		async move { workflow_manager.start_workflows().await }
+	});
+	while !workflow_manager.is_running("result_store") {
+		tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+	}
+	let awaited = workflow_manager
+		.await_result("result_store")
+		.await
+		.expect("Failed to await result");
+	assert!(matches!(awaited.output, Some(CLRepr::Int(42))));
+	run.await.unwrap().expect("Failed to start workflows");
+
+	let stored = workflow_manager.result("result_store").expect("missing result");
+	assert_eq!(stored.status, WorkflowStatus::Completed);
+	assert_eq!(stored.attempts, 1);
+	assert!(stored.error.is_none());
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn query_engine_manager_reports_results() -> pyo3::PyResult<()> {
+	let query_engine_manager =
+		QueryEngineManager::new().expect("Failed to create QueryEngineManager");
+	let engine = QueryEngine {
+		name: "query".to_string(),
+		id: "query".to_string(),
+		import: "".to_string(),
+		attr: "add_numbers".to_string(),
+		code: Some(CODE_WITH_RESULT.to_string()),
+		arguments: vec![CLRepr::Int(1), CLRepr::Int(2)],
+		kwargs: HashMap::new(),
+		config: None,
+		config_injection: ConfigInjection::Positional,
+	};
+	assert!(query_engine_manager.add_workflow(engine).is_ok());
+	let report = query_engine_manager.start_workflows().await.expect("Failed to start engines");
+	assert!(matches!(report.get("query").and_then(|r| r.output()), Some(CLRepr::Int(3))));
+	assert_eq!(query_engine_manager.status("query").unwrap().status, WorkflowStatus::Completed);
+	Ok(())
+}
+
+/// A runnable kind defined outside of the crate.
+#[derive(Clone)]
+struct Evaluator {
+	id: String,
+	samples: Vec<CLRepr>,
+}
+
+impl Runnable for Evaluator {
+	fn id(&self) -> &str {
+		&self.id
+	}
+
+	fn name(&self) -> &str {
+		"evaluator"
+	}
+
+	fn import(&self) -> &str {
+		""
+	}
+
+	fn attr(&self) -> &str {
+		"sleep_and_return"
+	}
+
+	fn code(&self) -> Option<&str> {
+		Some(CODE_SLEEP_AND_RETURN)
+	}
+
+	fn arguments(&self) -> &[CLRepr] {
+		&self.samples
+	}
+
+	fn push_argument(&mut self, argument: CLRepr) {
+		self.samples.push(argument);
+	}
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn runnable_manager_runs_custom_runnables() -> pyo3::PyResult<()> {
+	let manager: RunnableManager<Evaluator> =
+		RunnableManager::new().expect("Failed to create RunnableManager");
+	let evaluator = Evaluator { id: "evaluator".to_string(), samples: vec![CLRepr::Int(7)] };
+	assert!(manager.add_workflow(evaluator).is_ok());
+	manager.start_workflows().await.expect("Failed to start evaluators");
+	let result = manager.result("evaluator").expect("missing result");
+	assert!(matches!(result.output, Some(CLRepr::Int(7))));
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn querent_runs_scheduled_workflows() -> pyo3::PyResult<()> {
+	let querent = Querent::new().expect("Failed to create Querent");
+	let every = std::time::Duration::from_millis(300);
+	let skipping = WorkflowBuilder::new("scheduled_skip")
+		.attr(Some("sleep_and_return".to_string()))
+		.code(Some(CODE_SLEEP_AND_RETURN.to_string()))
+		.arguments(vec![CLRepr::Int(1)])
+		.schedule(Schedule::every(every).unwrap())
+		.build();
+	let parallel = WorkflowBuilder::from_workflow(skipping.clone())
+		.name("scheduled_parallel")
+		.schedule(Schedule::every(every).unwrap().overlap(OverlapPolicy::Parallel))
+		.build();
+	let parallel = Workflow { id: "scheduled_parallel".to_string(), ..parallel };
+	let nightly = WorkflowBuilder::from_workflow(skipping.clone()).build();
+	let nightly = Workflow { id: "scheduled_nightly".to_string(), schedule: None, ..nightly };
+	assert!(querent.add_workflow(skipping).is_ok());
+	assert!(querent.add_workflow(parallel).is_ok());
+	assert!(querent.add_workflow(nightly).is_ok());
+	assert!(querent.schedule_workflow("unknown", Schedule::every(every).unwrap()).is_err());
+	assert!(Schedule::cron("0 25 * * *").is_err());
+	assert!(Schedule::every(std::time::Duration::ZERO).is_err());
+	querent
+		.schedule_workflow("scheduled_nightly", Schedule::cron("0 2 * * *").unwrap())
+		.expect("Failed to schedule workflow");
+
+	let now = std::time::SystemTime::now();
+	let next = querent.next_run("scheduled_nightly").expect("missing next run");
+	assert!(next > now && next <= now + std::time::Duration::from_secs(24 * 3600));
+	assert_eq!(querent.list_schedules().len(), 3);
+
+	tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+	let schedules: HashMap<_, _> = querent
+		.list_schedules()
+		.into_iter()
+		.map(|s| (s.workflow_id.clone(), s))
+		.collect();
+	let skipped = &schedules["scheduled_skip"];
+	assert_eq!(skipped.runs_started, 1);
+	assert!(skipped.runs_skipped >= 2, "{:?}", skipped);
+	let parallel = &schedules["scheduled_parallel"];
+	assert!(parallel.runs_started >= 2, "{:?}", parallel);
+	assert_eq!(parallel.runs_skipped, 0);
+	assert!(querent.unschedule_workflow("scheduled_skip"));
+	assert!(querent.unschedule_workflow("scheduled_parallel"));
+	assert!(querent.unschedule_workflow("scheduled_nightly"));
+	assert!(querent.next_run("scheduled_skip").is_none());
+
+	let skip_report = querent.await_workflow_result("scheduled_skip").await.unwrap();
+	assert!(matches!(skip_report.output, Some(CLRepr::Int(1))));
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn querent_records_run_history() -> pyo3::PyResult<()> {
+	let path =
+		std::env::temp_dir().join(format!("querent-run-history-{}.jsonl", std::process::id()));
+	let _ = std::fs::remove_file(&path);
+	let querent = Querent::new().expect("Failed to create Querent");
+	assert!(querent.run_history(&RunFilter::new()).is_err());
+	querent.enable_run_journal(&path).expect("Failed to enable run journal");
+
+	// Recorded times are truncated to the millisecond.
+	let started = std::time::SystemTime::now() - std::time::Duration::from_millis(1);
+	let succeeding = WorkflowBuilder::new("history_ok")
+		.attr(Some("sleep_and_return".to_string()))
+		.code(Some(CODE_SLEEP_AND_RETURN.to_string()))
+		.arguments(vec![CLRepr::Int(7)])
+		.build();
+	let failing = WorkflowBuilder::new("history_failing")
+		.attr(Some("fail".to_string()))
+		.code(Some(CODE_FAILING.to_string()))
+		.build();
+	assert!(querent.add_workflow(succeeding).is_ok());
+	assert!(querent.add_workflow(failing).is_ok());
+	querent.start_workflows().await.expect("Failed to start workflows");
+	querent.start_workflow("history_ok").await.expect("Failed to start workflow");
+
+	let runs = querent.run_history(&RunFilter::new().workflow("history_ok")).unwrap();
+	assert_eq!(runs.len(), 2);
+	assert_ne!(runs[0].run_id, runs[1].run_id);
+	for run in &runs {
+		assert_eq!(run.status, WorkflowStatus::Completed);
+		assert_eq!(run.arguments, vec![serde_json::json!(7)]);
+		assert!(run.started_at >= started && run.finished_at.unwrap() >= run.started_at);
+	}
+	let failed = querent.run_history(&RunFilter::new().workflow("history_failing")).unwrap();
+	assert_eq!(failed.len(), 1);
+	assert_eq!(failed[0].status, WorkflowStatus::Failed);
+	assert!(failed[0].error.as_deref().unwrap().contains("boom"));
+	let later = RunFilter::new().since(std::time::SystemTime::now());
+	assert!(querent.run_history(&later).unwrap().is_empty());
+
+	// Another instance reads the history back from the file.
+	let reopened = RunJournal::open(&path).expect("Failed to reopen run journal");
+	assert_eq!(reopened.query(&RunFilter::new()).unwrap().len(), 3);
+	std::fs::remove_file(&path).unwrap();
+	Ok(())
+}
+
+const CODE_COUNTER: &str = r#"
+runs = 0
+
+async def count():
+    global runs
+    runs += 1
+    return runs
+"#;
+
+async fn run_output(querent: &Querent, workflow_id: &str) -> Option<CLRepr> {
+	let report = querent.start_workflow(workflow_id).await.expect("Failed to start workflow");
+	report.output().cloned()
+}
+
+fn write_module(path: &std::path::Path, value: i64, modified_secs: u64) {
+	std::fs::write(path, format!("async def version():\n    return {}\n", value)).unwrap();
+	let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(modified_secs);
+	std::fs::File::options()
+		.write(true)
+		.open(path)
+		.unwrap()
+		.set_modified(modified)
+		.unwrap();
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn querent_reloads_workflow_modules() -> pyo3::PyResult<()> {
+	let querent = Querent::new().expect("Failed to create Querent");
+	let counter = WorkflowBuilder::new("reload_code")
+		.attr(Some("count".to_string()))
+		.code(Some(CODE_COUNTER.to_string()))
+		.build();
+	assert!(querent.add_workflow(counter.clone()).is_ok());
+	// The compiled module is kept between runs, along with its globals.
+	assert!(matches!(run_output(&querent, "reload_code").await, Some(CLRepr::Int(1))));
+	assert!(matches!(run_output(&querent, "reload_code").await, Some(CLRepr::Int(2))));
+	let changed = Workflow {
+		code: Some("async def count():\n    return 100\n".to_string()),
+		..counter.clone()
+	};
+	assert!(querent.update_workflow(changed).is_ok());
+	assert!(matches!(run_output(&querent, "reload_code").await, Some(CLRepr::Int(100))));
+	assert!(querent.update_workflow(counter).is_ok());
+	querent.set_reload_mode(ReloadMode::Always);
+	assert!(matches!(run_output(&querent, "reload_code").await, Some(CLRepr::Int(1))));
+	assert!(matches!(run_output(&querent, "reload_code").await, Some(CLRepr::Int(1))));
+
+	let dir = std::env::temp_dir().join(format!("querent-reload-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	let module_name = format!("querent_reload_{}", std::process::id());
+	let module_path = dir.join(format!("{}.py", module_name));
+	write_module(&module_path, 1, 1_000);
+	Python::with_gil(|py| -> pyo3::PyResult<()> {
+		py.import("sys")?
+			.getattr("path")?
+			.call_method1("insert", (0, dir.to_str().unwrap()))?;
+		Ok(())
+	})?;
+	let imported = WorkflowBuilder::new("reload_import")
+		.import(Some(module_name))
+		.attr(Some("version".to_string()))
+		.build();
+	assert!(querent.add_workflow(imported).is_ok());
+	querent.set_reload_mode(ReloadMode::OnChange);
+	assert!(matches!(run_output(&querent, "reload_import").await, Some(CLRepr::Int(1))));
+	write_module(&module_path, 2, 2_000);
+	assert!(matches!(run_output(&querent, "reload_import").await, Some(CLRepr::Int(2))));
+
+	// A package is reloaded when one of its submodules changes.
+	let package_name = format!("querent_reload_pkg_{}", std::process::id());
+	std::fs::create_dir_all(dir.join(&package_name)).unwrap();
+	std::fs::write(dir.join(&package_name).join("__init__.py"), "from .impl import version\n")
+		.unwrap();
+	let impl_path = dir.join(&package_name).join("impl.py");
+	write_module(&impl_path, 1, 1_000);
+	let package = WorkflowBuilder::new("reload_package")
+		.import(Some(package_name))
+		.attr(Some("version".to_string()))
+		.build();
+	assert!(querent.add_workflow(package).is_ok());
+	assert!(matches!(run_output(&querent, "reload_package").await, Some(CLRepr::Int(1))));
+	write_module(&impl_path, 2, 2_000);
+	assert!(matches!(run_output(&querent, "reload_package").await, Some(CLRepr::Int(2))));
+
+	querent.set_reload_mode(ReloadMode::Never);
+	write_module(&module_path, 3, 3_000);
+	assert!(matches!(run_output(&querent, "reload_import").await, Some(CLRepr::Int(2))));
+	querent.reload_workflow("reload_import").expect("Failed to reload workflow");
+	assert!(matches!(run_output(&querent, "reload_import").await, Some(CLRepr::Int(3))));
+	assert!(querent.reload_workflow("unknown").is_err());
+	std::fs::remove_dir_all(&dir).unwrap();
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn querent_runs_workflows_from_files() -> pyo3::PyResult<()> {
+	let dir = std::env::temp_dir().join(format!("querent-files-{}", std::process::id()));
+	let _ = std::fs::remove_dir_all(&dir);
+	let lib = dir.join("lib");
+	let package = dir.join("querent_files_pkg");
+	std::fs::create_dir_all(&lib).unwrap();
+	std::fs::create_dir_all(&package).unwrap();
+	std::fs::write(lib.join("querent_files_helpers.py"), "def double(x):\n    return 2 * x\n")
+		.unwrap();
+	std::fs::write(
+		dir.join("tasks.py"),
+		"from querent_files_helpers import double\n\nasync def run(x):\n    return double(x)\n",
+	)
+	.unwrap();
+	std::fs::write(package.join("__init__.py"), "from .impl import run\n").unwrap();
+	std::fs::write(package.join("impl.py"), "async def run(x):\n    return 3 * x\n").unwrap();
+
+	let querent = Querent::new().expect("Failed to create Querent");
+	let missing_venv = PythonEnvironment::new().virtualenv(dir.join("missing-venv"));
+	let error = querent.set_python_environment(&missing_venv).unwrap_err();
+	assert!(error.message.contains("is not a virtualenv"), "{}", error);
+	let missing_dir = PythonEnvironment::new().sys_path(dir.join("missing"));
+	assert!(querent.set_python_environment(&missing_dir).is_err());
+	querent
+		.set_python_environment(&PythonEnvironment::new().sys_path(&lib))
+		.expect("Failed to set Python environment");
+
+	let file = WorkflowBuilder::new("from_file")
+		.path(dir.join("tasks.py"))
+		.attr(Some("run".to_string()))
+		.arguments(vec![CLRepr::Int(5)])
+		.build();
+	let package_workflow = WorkflowBuilder::new("from_package")
+		.path(&package)
+		.attr(Some("run".to_string()))
+		.arguments(vec![CLRepr::Int(5)])
+		.build();
+	let missing = WorkflowBuilder::new("from_missing_file")
+		.path(dir.join("missing.py"))
+		.attr(Some("run".to_string()))
+		.build();
+	assert!(querent.add_workflow(file).is_ok());
+	assert!(querent.add_workflow(package_workflow).is_ok());
+	assert!(querent.add_workflow(missing).is_ok());
+	// The environment is applied again when the modules are loaded.
+	Python::with_gil(|py| -> pyo3::PyResult<()> {
+		py.import("sys")?
+			.getattr("path")?
+			.call_method1("remove", (lib.to_string_lossy(),))?;
+		Ok(())
+	})?;
+
+	let report = querent.start_workflows().await.expect("Failed to start workflows");
+	assert!(matches!(report.get("from_file").unwrap().output(), Some(CLRepr::Int(10))));
+	assert!(matches!(report.get("from_package").unwrap().output(), Some(CLRepr::Int(15))));
+	let error = report.get("from_missing_file").unwrap().error().unwrap();
+	assert!(error.message.contains("does not exist"), "{}", error);
+	std::fs::remove_dir_all(&dir).unwrap();
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn querent_isolates_modules_loaded_from_files() -> pyo3::PyResult<()> {
+	let dir = std::env::temp_dir().join(format!("querent-isolated-{}", std::process::id()));
+	let _ = std::fs::remove_dir_all(&dir);
+	for (name, value) in [("first", 1), ("second", 2)] {
+		std::fs::create_dir_all(dir.join(name)).unwrap();
+		std::fs::write(
+			dir.join(name).join("main.py"),
+			format!("async def run():\n    return {}\n", value),
+		)
+		.unwrap();
+	}
+	std::fs::write(dir.join("json.py"), "async def run():\n    return 3\n").unwrap();
+	let stdlib_json =
+		Python::with_gil(|py| -> pyo3::PyResult<pyo3::PyObject> { Ok(py.import("json")?.into()) })?;
+
+	let querent = Querent::new().expect("Failed to create Querent");
+	for (id, path) in [
+		("isolated_first", dir.join("first").join("main.py")),
+		("isolated_second", dir.join("second").join("main.py")),
+		("isolated_json", dir.join("json.py")),
+	] {
+		let workflow = WorkflowBuilder::new(id).path(path).attr(Some("run".to_string())).build();
+		assert!(querent.add_workflow(workflow).is_ok());
+	}
+	let report = querent.start_workflows().await.expect("Failed to start workflows");
+	assert!(matches!(report.get("isolated_first").unwrap().output(), Some(CLRepr::Int(1))));
+	assert!(matches!(report.get("isolated_second").unwrap().output(), Some(CLRepr::Int(2))));
+	assert!(matches!(report.get("isolated_json").unwrap().output(), Some(CLRepr::Int(3))));
+	Python::with_gil(|py| -> pyo3::PyResult<()> {
+		let modules = py.import("sys")?.getattr("modules")?;
+		assert!(modules.get_item("json")?.is(stdlib_json.as_ref(py)));
+		assert!(modules.get_item("main").is_err());
+		assert!(modules.get_item("querent_workflows.isolated__json").is_ok());
+		Ok(())
+	})?;
+	std::fs::remove_dir_all(&dir).unwrap();
+	Ok(())
+}
+
+const CODE_SYNC: &str = r#"
+import asyncio
+import builtins
+import time
+
+def blocking(value):
+    time.sleep(1)
+    return value * 2
+
+def returns_coroutine(value):
+    async def later():
+        await asyncio.sleep(0.1)
+        return value + 1
+    return later()
+
+def busy():
+    builtins.querent_busy_ticks = 0
+    while True:
+        builtins.querent_busy_ticks += 1
+        time.sleep(0.02)
+"#;
+
+fn sync_workflow(id: &str, attr: &str, arguments: Vec<CLRepr>) -> Workflow {
+	WorkflowBuilder::new(id)
+		.attr(Some(attr.to_string()))
+		.code(Some(CODE_SYNC.to_string()))
+		.arguments(arguments)
+		.build()
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_runs_sync_entry_points() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	for (id, value) in [("sync_a", 1), ("sync_b", 2), ("sync_c", 3)] {
+		let workflow = sync_workflow(id, "blocking", vec![CLRepr::Int(value)]);
+		assert!(workflow_manager.add_workflow(workflow).is_ok());
+	}
+	let coroutine = sync_workflow("sync_coroutine", "returns_coroutine", vec![CLRepr::Int(41)]);
+	assert!(workflow_manager.add_workflow(coroutine).is_ok());
+	let async_workflow = WorkflowBuilder::new("async_alongside")
+		.attr(Some("sleep_and_return".to_string()))
+		.code(Some(CODE_SLEEP_AND_RETURN.to_string()))
+		.arguments(vec![CLRepr::Int(0)])
+		.build();
+	assert!(workflow_manager.add_workflow(async_workflow).is_ok());
+
+	let started = std::time::Instant::now();
+	let report = workflow_manager.start_workflows().await.expect("Failed to start workflows");
+	// The blocking calls run on worker threads, next to each other and to the event loop.
+	assert!(started.elapsed() < std::time::Duration::from_millis(1900), "{:?}", started.elapsed());
+	assert!(report.failed().is_empty(), "{:?}", report.failed());
+	assert!(matches!(report.get("sync_c").unwrap().output(), Some(CLRepr::Int(6))));
+	assert!(matches!(report.get("sync_coroutine").unwrap().output(), Some(CLRepr::Int(42))));
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_interrupts_sync_entry_points() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	let busy = WorkflowBuilder::from_workflow(sync_workflow("sync_busy", "busy", vec![]))
+		.timeout(std::time::Duration::from_millis(300))
+		.build();
+	assert!(workflow_manager.add_workflow(busy).is_ok());
+	let report = workflow_manager.start_workflow("sync_busy").await.unwrap();
+	assert!(report.error().unwrap().is_timeout(), "{:?}", report.error());
+
+	let ticks = || {
+		Python::with_gil(|py| -> pyo3::PyResult<i64> {
+			py.import("builtins")?.getattr("querent_busy_ticks")?.extract()
+		})
+	};
+	tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+	let stopped_at = ticks()?;
+	tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+	assert_eq!(ticks()?, stopped_at);
+	Ok(())
+}
+
+const CODE_STREAMS: &str = r#"
+import asyncio
+import builtins
+
+async def count(n):
+    for i in range(n):
+        yield i
+
+async def tracked():
+    builtins.querent_stream_pulled = 0
+    builtins.querent_stream_closed = False
+    try:
+        while True:
+            builtins.querent_stream_pulled += 1
+            yield builtins.querent_stream_pulled
+            await asyncio.sleep(0)
+    finally:
+        builtins.querent_stream_closed = True
+
+async def failing():
+    yield 1
+    raise ValueError("broken stream")
+
+async def not_a_generator():
+    return 1
+"#;
+
+fn stream_function(attr: &str) -> pyo3::Py<pyo3::types::PyFunction> {
+	Python::with_gil(|py| {
+		let module = pyo3::types::PyModule::from_code(py, CODE_STREAMS, "streams.py", "streams")
+			.expect("Failed to compile streams");
+		module.getattr(attr).unwrap().extract().unwrap()
+	})
+}
+
+fn stream_state<T: for<'a> pyo3::FromPyObject<'a>>(name: &str) -> T {
+	Python::with_gil(|py| py.import("builtins").unwrap().getattr(name).unwrap().extract().unwrap())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn py_runtime_streams_async_generators() -> pyo3::PyResult<()> {
+	use futures::StreamExt;
+	let runtime = py_runtime().expect("Failed to get PyRuntime");
+
+	let stream = runtime
+		.call_stream(
+			stream_function("count"),
+			vec![CLRepr::Int(5)],
+			None,
+			None,
+			PyCallOptions::default(),
+		)
+		.await
+		.expect("Failed to start stream");
+	let values: Vec<_> = stream.map(|item| item.unwrap()).collect().await;
+	assert_eq!(values.len(), 5);
+	assert!(matches!(values[4], CLRepr::Int(4)));
+
+	let options = PyCallOptions { stream_buffer: Some(2), ..Default::default() };
+	let mut stream = runtime
+		.call_stream(stream_function("tracked"), vec![], None, None, options)
+		.await
+		.expect("Failed to start stream");
+	assert!(matches!(stream.next().await, Some(Ok(CLRepr::Int(1)))));
+	tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+	// One value taken, two buffered and one waiting for room.
+	assert!(stream_state::<i64>("querent_stream_pulled") <= 4);
+	drop(stream);
+	tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+	assert!(stream_state::<bool>("querent_stream_closed"));
+
+	let cancel = tokio_util::sync::CancellationToken::new();
+	let options = PyCallOptions {
+		cancel: Some(cancel.clone()),
+		stream_buffer: Some(1),
+		..Default::default()
+	};
+	let mut stream = runtime
+		.call_stream(stream_function("tracked"), vec![], None, None, options)
+		.await
+		.expect("Failed to start stream");
+	assert!(matches!(stream.next().await, Some(Ok(CLRepr::Int(1)))));
+	cancel.cancel();
+	while stream.next().await.is_some() {}
+	assert!(stream_state::<bool>("querent_stream_closed"));
+
+	let mut stream = runtime
+		.call_stream(stream_function("failing"), vec![], None, None, PyCallOptions::default())
+		.await
+		.expect("Failed to start stream");
+	assert!(matches!(stream.next().await, Some(Ok(CLRepr::Int(1)))));
+	let error = stream.next().await.unwrap().unwrap_err();
+	assert_eq!(error.python_exception_type(), Some("ValueError"));
+	assert!(stream.next().await.is_none());
+
+	let mut stream = runtime
+		.call_stream(
+			stream_function("not_a_generator"),
+			vec![],
+			None,
+			None,
+			PyCallOptions::default(),
+		)
+		.await
+		.expect("Failed to start stream");
+	let error = stream.next().await.unwrap().unwrap_err();
+	assert_eq!(error.python_exception_type(), Some("TypeError"));
+	Ok(())
+}
+
+const CODE_KWARGS: &str = r#"
+async def keyword_config(value, *, scale, settings=None):
+    return value * scale + (1 if settings is not None else 0)
+
+async def without_config(value, scale=1):
+    return value * scale
+"#;
+
+fn kwargs_workflow(id: &str, attr: &str, injection: ConfigInjection) -> Workflow {
+	let config = Config {
+		version: 1.0,
+		querent_id: id.to_string(),
+		querent_name: "Test Querent kwargs".to_string(),
+		workflow: WorkflowConfig {
+			name: id.to_string(),
+			id: id.to_string(),
+			config: HashMap::new(),
+			channel: None,
+			inner_channel: None,
+			inner_event_handler: None,
+			event_handler: None,
+			inner_tokens_feader: None,
+			tokens_feader: None,
+		},
+		collectors: vec![],
+		engines: vec![],
+		resource: None,
+	};
+	WorkflowBuilder::new(id)
+		.attr(Some(attr.to_string()))
+		.code(Some(CODE_KWARGS.to_string()))
+		.arguments(vec![CLRepr::Int(5)])
+		.kwarg("scale", CLRepr::Int(10))
+		.config(config)
+		.config_injection(injection)
+		.build()
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_passes_keyword_arguments() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	let workflows = [
+		kwargs_workflow(
+			"kwargs_keyword",
+			"keyword_config",
+			ConfigInjection::Keyword("settings".to_string()),
+		),
+		kwargs_workflow("kwargs_omitted", "without_config", ConfigInjection::Omitted),
+		kwargs_workflow(
+			"kwargs_conflict",
+			"keyword_config",
+			ConfigInjection::Keyword("scale".to_string()),
+		),
+	];
+	for workflow in workflows {
+		assert!(workflow_manager.add_workflow(workflow).is_ok());
+	}
+	let report = workflow_manager.start_workflows().await.expect("Failed to start workflows");
+	assert!(matches!(report.get("kwargs_keyword").and_then(|r| r.output()), Some(CLRepr::Int(51))));
+	assert!(matches!(report.get("kwargs_omitted").and_then(|r| r.output()), Some(CLRepr::Int(50))));
+	let error = report
+		.get("kwargs_conflict")
+		.and_then(|r| r.error())
+		.expect("Expected an error");
+	assert!(error.message.contains("reserved for the configuration"), "{}", error);
+	Ok(())
+}
+
+const CODE_VALIDATION: &str = r#"
+async def start(config, path, *, depth):
+    return path
+
+async def stream(config):
+    yield 1
+
+def blocking(config, **options):
+    return 1
+
+not_callable = 42
+"#;
+
+fn validated_workflow(id: &str, attr: &str, arguments: Vec<CLRepr>) -> Workflow {
+	WorkflowBuilder::from_workflow(kwargs_workflow(id, attr, ConfigInjection::Positional))
+		.code(Some(CODE_VALIDATION.to_string()))
+		.arguments(arguments)
+		.kwargs(HashMap::from([("depth".to_string(), CLRepr::Int(2))]))
+		.build()
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_validates_workflows_without_running_them() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	let path = vec![CLRepr::String("data/".to_string(), StringType::Normal)];
+	let workflows = [
+		validated_workflow("valid", "start", path.clone()),
+		validated_workflow("misspelled", "strat", path.clone()),
+		validated_workflow("arity", "start", vec![]),
+		validated_workflow("generator", "stream", vec![]),
+		validated_workflow("sync", "blocking", vec![]),
+		validated_workflow("attribute", "not_callable", vec![]),
+		WorkflowBuilder::new("missing_module")
+			.import(Some("querent_no_such_module".to_string()))
+			.build(),
+	];
+	for workflow in workflows {
+		assert!(workflow_manager.add_workflow(workflow).is_ok());
+	}
+
+	let diagnostics = workflow_manager.validate();
+	let find = |id: &str| diagnostics.iter().filter(|d| d.workflow_id == id).collect::<Vec<_>>();
+	assert!(find("valid").is_empty(), "{:?}", find("valid"));
+	assert!(find("misspelled")[0].message.contains("did you mean start?"), "{:?}", diagnostics);
+	assert!(find("arity")[0].is_error());
+	assert!(find("arity")[0].message.contains("missing a required argument: 'path'"));
+	assert!(find("generator").iter().any(|d| d.message.contains("async generator")));
+	let sync = find("sync");
+	assert_eq!(sync.len(), 1);
+	assert_eq!(sync[0].severity, Severity::Warning);
+	assert!(find("attribute")[0].message.contains("is not a function but a int"));
+	assert!(find("missing_module")[0].message.contains("Unable to import module"));
+	for id in ["valid", "sync"] {
+		assert_eq!(workflow_manager.status(id).map(|s| s.status), Some(WorkflowStatus::Pending));
+	}
+	Ok(())
+}
+
+const CODE_LIMITED: &str = r#"
+import asyncio
+
+async def limited(config, value):
+    await asyncio.sleep(0.6)
+    return value
+"#;
+
+fn limited_workflow(id: &str, value: i64) -> Workflow {
+	let mut config = kwargs_workflow(id, "limited", ConfigInjection::Positional)
+		.config
+		.expect("Expected a config");
+	config.querent_id = "limited_querent".to_string();
+	config.resource = Some(ResourceConfig {
+		id: "limited_resource".to_string(),
+		max_workers_allowed: Some(4),
+		max_workers_per_collector: None,
+		max_workers_per_engine: None,
+		max_workers_per_querent: Some(1),
+	});
+	WorkflowBuilder::new(id)
+		.attr(Some("limited".to_string()))
+		.code(Some(CODE_LIMITED.to_string()))
+		.arguments(vec![CLRepr::Int(value)])
+		.config(config)
+		.build()
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_enforces_worker_limits() -> pyo3::PyResult<()> {
+	let workflow_manager =
+		Arc::new(WorkflowManager::new().expect("Failed to create WorkflowManager"));
+	for (id, value) in [("limited_a", 1), ("limited_b", 2), ("limited_c", 3)] {
+		assert!(workflow_manager.add_workflow(limited_workflow(id, value)).is_ok());
+	}
+
+	let started = std::time::Instant::now();
+	let manager = workflow_manager.clone();
+	let run = tokio::spawn(async move { manager.start_workflows().await });
+	// Waits for every run to either take the worker or queue for it.
+	let mut statuses: Vec<WorkflowStatus> = Vec::new();
+	for _ in 0..40 {
+		tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+		statuses = workflow_manager
+			.list_status()
+			.into_iter()
+			.filter(|state| state.workflow_id.starts_with("limited_"))
+			.map(|state| state.status)
+			.collect();
+		if statuses.iter().filter(|s| **s == WorkflowStatus::Queued).count() == 2 {
+			break;
+		}
+	}
+	assert_eq!(statuses.iter().filter(|s| **s == WorkflowStatus::Running).count(), 1);
+	assert_eq!(statuses.iter().filter(|s| **s == WorkflowStatus::Queued).count(), 2);
+	let usage = workflow_manager.worker_usage();
+	let querent = usage
+		.iter()
+		.find(|u| u.pool == WorkerPool::Querent("limited_querent".to_string()))
+		.expect("Expected the querent pool");
+	assert_eq!((querent.limit, querent.active, querent.queued), (1, 1, 2));
+
+	let report = run.await.unwrap().expect("Failed to start workflows");
+	assert!(started.elapsed() >= std::time::Duration::from_millis(1800));
+	for (id, value) in [("limited_a", 1), ("limited_b", 2), ("limited_c", 3)] {
+		assert!(
+			matches!(report.get(id).and_then(|r| r.output()), Some(CLRepr::Int(v)) if *v == value)
+		);
+	}
+	let history = workflow_manager.status("limited_c").unwrap().history;
+	assert!(history.iter().any(|t| t.status == WorkflowStatus::Queued));
+	assert!(workflow_manager.worker_usage().iter().all(|u| u.active == 0 && u.queued == 0));
+	Ok(())
+}
+
+const CODE_PRIORITIES: &str = r#"
+started = []
+
+async def record(name):
+    started.append(name)
+    return name
+"#;
+
+#[pyo3_asyncio::tokio::test]
+async fn py_runtime_starts_high_priority_calls_first() -> pyo3::PyResult<()> {
+	let runtime = py_runtime().expect("Failed to get PyRuntime");
+	let (module, record) = Python::with_gil(|py| {
+		let module =
+			pyo3::types::PyModule::from_code(py, CODE_PRIORITIES, "priorities.py", "priorities")
+				.expect("Failed to compile priorities");
+		let record: pyo3::Py<pyo3::types::PyFunction> =
+			module.getattr("record").unwrap().extract().unwrap();
+		(pyo3::Py::<pyo3::types::PyModule>::from(module), record)
+	});
+
+	// Holding the GIL keeps the runtime from starting calls, so they pile up in its queue.
+	// Calls are made from plain threads, since a tokio worker may be stuck waiting for the GIL.
+	let queued = tokio::task::spawn_blocking(move || {
+		let gil = std::thread::spawn(|| {
+			Python::with_gil(|_| std::thread::sleep(std::time::Duration::from_millis(400)))
+		});
+		std::thread::sleep(std::time::Duration::from_millis(50));
+		let call = |name: &str, priority: TaskPriority| {
+			let record = record.clone();
+			let name = CLRepr::String(name.to_string(), StringType::Normal);
+			let options = PyCallOptions { priority, ..Default::default() };
+			std::thread::spawn(move || {
+				futures::executor::block_on(runtime.call_async_with_options(
+					record,
+					vec![name],
+					None,
+					None,
+					options,
+				))
+			})
+		};
+		let mut calls = Vec::new();
+		for index in 0..5 {
+			calls.push(call(&format!("bulk-{}", index), TaskPriority::Low));
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+		calls.push(call("query", TaskPriority::High));
+		std::thread::sleep(std::time::Duration::from_millis(50));
+		let queued = runtime.queued_calls();
+		gil.join().unwrap();
+		for call in calls {
+			call.join().unwrap().expect("Call failed");
+		}
+		queued
+	})
+	.await
+	.unwrap();
+	assert!(queued >= 4, "{} calls queued", queued);
+
+	let started: Vec<String> =
+		Python::with_gil(|py| module.as_ref(py).getattr("started").unwrap().extract().unwrap());
+	assert_eq!(started.len(), 6);
+	// The runtime may have taken one bulk call before the GIL was held.
+	let query = started.iter().position(|name| name == "query").unwrap();
+	assert!(query <= 1, "{:?}", started);
+	Ok(())
+}
+
+const CODE_LABELS: &str = r#"
+import asyncio
+
+async def echo(value):
+    return value
+
+async def add(value, upstream):
+    return value + upstream
+
+async def wait():
+    await asyncio.sleep(5)
+"#;
+
+fn labelled_workflow(id: &str, attr: &str, value: Option<i64>) -> WorkflowBuilder {
+	WorkflowBuilder::new(id)
+		.attr(Some(attr.to_string()))
+		.code(Some(CODE_LABELS.to_string()))
+		.arguments(value.map(CLRepr::Int).into_iter().collect())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn workflow_manager_selects_workflows_by_label() -> pyo3::PyResult<()> {
+	let workflow_manager =
+		Arc::new(WorkflowManager::new().expect("Failed to create WorkflowManager"));
+	let workflows = [
+		labelled_workflow("seed", "echo", Some(10)).label("env", "dev").build(),
+		labelled_workflow("prod_sum", "add", Some(1))
+			.label("env", "prod")
+			.tag("nightly")
+			.depends_on_result("seed")
+			.build(),
+		labelled_workflow("staging_echo", "echo", Some(2))
+			.label("env", "staging")
+			.build(),
+		labelled_workflow("unlabelled", "echo", Some(3)).build(),
+	];
+	for workflow in workflows {
+		assert!(workflow_manager.add_workflow(workflow).is_ok());
+	}
+
+	let deployed: LabelSelector = "env in (prod, staging)".parse().expect("Invalid selector");
+	let ids: Vec<String> = workflow_manager
+		.workflows_matching(&deployed)
+		.iter()
+		.map(|w| w.id.clone())
+		.collect();
+	assert_eq!(ids, vec!["prod_sum".to_string(), "staging_echo".to_string()]);
+
+	// The upstream workflow is not selected and never ran, so the dependent one is skipped.
+	let report = workflow_manager.start_workflows_matching(&deployed).await.unwrap();
+	assert_eq!(report.len(), 2);
+	assert_eq!(report.get("prod_sum").unwrap().status, WorkflowStatus::Skipped);
+	assert!(matches!(report.get("staging_echo").and_then(|r| r.output()), Some(CLRepr::Int(2))));
+
+	// Once it completed, its latest result is passed on.
+	workflow_manager.start_workflow("seed").await.unwrap();
+	let nightly: LabelSelector = "nightly".parse().expect("Invalid selector");
+	let report = workflow_manager.start_workflows_matching(&nightly).await.unwrap();
+	assert_eq!(report.len(), 1);
+	assert!(matches!(report.get("prod_sum").and_then(|r| r.output()), Some(CLRepr::Int(11))));
+
+	let waiting = labelled_workflow("waiting", "wait", None).label("env", "prod").build();
+	assert!(workflow_manager.add_workflow(waiting).is_ok());
+	let manager = workflow_manager.clone();
+	let run = tokio::spawn(async move { manager.start_workflow("waiting").await });
+	tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+	let prod = LabelSelector::new().equals("env", "prod");
+	assert_eq!(workflow_manager.cancel_matching(&prod), vec!["waiting".to_string()]);
+	assert_eq!(run.await.unwrap().unwrap().status, WorkflowStatus::Cancelled);
+	Ok(())
+}
+
+const CODE_EVENT_CALLBACKS: &str = r#"
+async def send_events(config, payload):
+    handler = config['workflow']['event_handler']
+    for event_type in ["Graph", "Vector"]:
+        handler.handle_event(event_type, {
+            "event_type": event_type,
+            "timestamp": 1.5,
+            "payload": payload,
+            "file": "callbacks.txt",
+            "doc_source": "test_doc_source",
+        })
+"#;
+
+fn event_workflow(id: &str, payload: &str) -> Workflow {
+	let config = Config {
+		version: 1.0,
+		querent_id: id.to_string(),
+		querent_name: "Test Querent callbacks".to_string(),
+		workflow: WorkflowConfig {
+			name: id.to_string(),
+			id: id.to_string(),
+			config: HashMap::new(),
+			channel: None,
+			inner_channel: None,
+			inner_event_handler: Some(EventHandler::new(None)),
+			event_handler: None,
+			inner_tokens_feader: None,
+			tokens_feader: None,
+		},
+		collectors: vec![],
+		engines: vec![],
+		resource: None,
+	};
+	WorkflowBuilder::new(id)
+		.attr(Some("send_events".to_string()))
+		.code(Some(CODE_EVENT_CALLBACKS.to_string()))
+		.arguments(vec![CLRepr::String(payload.to_string(), StringType::Normal)])
+		.config(config)
+		.build()
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn querent_calls_registered_event_callbacks() -> pyo3::PyResult<()> {
+	let querent = Querent::new().expect("Failed to create Querent");
+	let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+	let graph_sender = sender.clone();
+	let graph = querent.register_callback(EventType::Graph, move |event| {
+		graph_sender
+			.send(format!("graph {} from {}", event.payload, event.file))
+			.unwrap();
+	});
+	querent.register_async_callback(EventType::Vector, move |event| {
+		let sender = sender.clone();
+		async move {
+			sender.send(format!("vector {}", event.payload)).unwrap();
+		}
+	});
+
+	querent.add_workflow(event_workflow("callbacks", "first")).unwrap();
+	querent.start_workflow("callbacks").await.unwrap();
+	assert_eq!(received.recv().await.unwrap(), "graph first from callbacks.txt");
+	assert_eq!(received.recv().await.unwrap(), "vector first");
+
+	assert_eq!(graph.event_type(), &EventType::Graph);
+	assert!(graph.unsubscribe());
+	querent.update_workflow(event_workflow("callbacks", "second")).unwrap();
+	querent.start_workflow("callbacks").await.unwrap();
+	assert_eq!(received.recv().await.unwrap(), "vector second");
+	let late = tokio::time::timeout(std::time::Duration::from_millis(100), received.recv()).await;
+	assert!(late.is_err());
+	Ok(())
+}
+
+const CODE_PIPELINE_EVENTS: &str = r#"
+async def flood(config, count):
+    handler = config['workflow']['event_handler']
+    for index in range(count):
+        event_type = "Graph" if index == count - 1 else "Vector"
+        handler.handle_event(event_type, {
+            "event_type": event_type,
+            "timestamp": 1.5,
+            "payload": str(index),
+            "file": "pipeline.txt",
+            "doc_source": "test_doc_source",
+        })
+"#;
+
+#[pyo3_asyncio::tokio::test]
+async fn querent_delivers_events_of_loaded_pipelines_to_callbacks() -> pyo3::PyResult<()> {
+	let dir = std::env::temp_dir().join(format!("querent-pipeline-events-{}", std::process::id()));
+	let _ = std::fs::remove_dir_all(&dir);
+	std::fs::create_dir_all(&dir).unwrap();
+	let path = dir.join("pipeline.json");
+	let pipeline = serde_json::json!({
+		"config": { "querent_id": "pipeline_events" },
+		"workflows": [{
+			"id": "pipeline_events",
+			"attr": "flood",
+			"code": CODE_PIPELINE_EVENTS,
+			"arguments": [1500],
+		}],
+	});
+	let invalid_path = dir.join("invalid.json");
+	let invalid = serde_json::json!({
+		"workflows": [{ "id": "negative", "attr": "flood", "code": "", "timeout_secs": -1 }],
+	});
+	std::fs::write(&invalid_path, invalid.to_string()).unwrap();
+	let error = Querent::load_from_file(&invalid_path)
+		.err()
+		.expect("Expected an invalid pipeline");
+	let prefix = format!("{}: workflows[0] (negative): timeout_secs", invalid_path.display());
+	assert!(error.message.starts_with(&prefix), "{}", error);
+	std::fs::write(&path, pipeline.to_string()).unwrap();
+
+	let querent = Querent::load_from_file(&path).expect("Failed to load pipeline");
+	let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+	querent.register_callback(EventType::Graph, move |event| {
+		sender.send(event.payload.clone()).unwrap();
+	});
+	// Nobody receives from the pipeline channel yet, which must not fail the workflow.
+	let report = querent.start_workflow("pipeline_events").await.unwrap();
+	assert!(report.is_success(), "{:?}", report.error());
+	assert_eq!(received.recv().await.unwrap(), "1499");
+
+	let mut channels = querent.take_channels().expect("Expected pipeline channels");
+	let mut last = None;
+	while let Ok(Some((_, event))) =
+		tokio::time::timeout(std::time::Duration::from_millis(200), channels.events.recv()).await
+	{
+		last = Some(event.payload);
+	}
+	assert_eq!(last.as_deref(), Some("1499"));
+	std::fs::remove_dir_all(&dir).unwrap();
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn querent_fans_events_out_to_every_receiver() -> pyo3::PyResult<()> {
+	let querent = Querent::new().expect("Failed to create Querent");
+	let (sender, mut counted) = tokio::sync::mpsc::unbounded_channel();
+	querent.register_callback(EventType::Vector, move |event| {
+		sender.send(event.payload.clone()).unwrap();
+	});
+	let mut persisted = querent.subscribe_events(16);
+	let mut streamed = querent.subscribe_events(1);
+
+	querent.add_workflow(event_workflow("fan_out", "shared")).unwrap();
+	querent.start_workflow("fan_out").await.unwrap();
+
+	for expected in [EventType::Graph, EventType::Vector] {
+		let (event_type, event) = persisted.recv().await.unwrap();
+		assert_eq!(event_type, expected);
+		assert_eq!(event.payload, "shared");
+	}
+	// The small buffer kept the latest event only, without holding back the others.
+	assert_eq!(streamed.lagged_total(), 1);
+	assert_eq!(streamed.recv().await.unwrap_err(), EventRecvError::Lagged(1));
+	assert_eq!(streamed.recv().await.unwrap().0, EventType::Vector);
+	assert_eq!(counted.recv().await.unwrap(), "shared");
+
+	// A receiver dropped while workflows run no longer gets events, new ones do.
+	drop(streamed);
+	let mut late = querent.subscribe_events(16);
+	querent.start_workflow("fan_out").await.unwrap();
+	assert_eq!(late.recv().await.unwrap().0, EventType::Graph);
+	assert_eq!(persisted.recv().await.unwrap().0, EventType::Graph);
+	Ok(())
+}
+
+const CODE_EVENT_OVERFLOW: &str = r#"
+async def send_events(config, count):
+    outcomes = []
+    for index in range(count):
+        try:
+            config['workflow']['event_handler'].handle_event("Graph", {
+                "event_type": "Graph",
+                "timestamp": float(index),
+                "payload": str(index),
+                "file": "overflow.txt",
+                "doc_source": "test_doc_source",
+            })
+            outcomes.append("ok")
+        except Exception as e:
+            outcomes.append(type(e).__name__)
+    return ",".join(outcomes)
+"#;
+
+fn overflow_workflow(id: &str, event_handler: EventHandler) -> Workflow {
+	let config = Config {
+		version: 1.0,
+		querent_id: id.to_string(),
+		querent_name: "Test Querent overflow".to_string(),
+		workflow: WorkflowConfig {
+			name: id.to_string(),
+			id: id.to_string(),
+			config: HashMap::new(),
+			channel: None,
+			inner_channel: None,
+			inner_event_handler: Some(event_handler),
+			event_handler: None,
+			inner_tokens_feader: None,
+			tokens_feader: None,
+		},
+		collectors: vec![],
+		engines: vec![],
+		resource: None,
+	};
+	WorkflowBuilder::new(id)
+		.attr(Some("send_events".to_string()))
+		.code(Some(CODE_EVENT_OVERFLOW.to_string()))
+		.arguments(vec![CLRepr::Int(3)])
+		.config(config)
+		.build()
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn event_handler_applies_overflow_policies() -> pyo3::PyResult<()> {
+	let workflow_manager = WorkflowManager::new().expect("Failed to create WorkflowManager");
+	let outcome = |output: Option<&CLRepr>| match output {
+		Some(CLRepr::String(outcomes, _)) => outcomes.clone(),
+		other => panic!("Unexpected result: {:?}", other),
+	};
+
+	let (sender, mut rejecting) = tokio::sync::mpsc::channel(1);
+	let handler = EventHandler::new(Some(sender));
+	workflow_manager
+		.add_workflow(overflow_workflow("drop_newest", handler.clone()))
+		.unwrap();
+	let report = workflow_manager.start_workflow("drop_newest").await.unwrap();
+	assert_eq!(outcome(report.output()), "ok,BufferError,BufferError");
+	assert_eq!(handler.overflow_stats().rejected, 2);
+	assert_eq!(rejecting.recv().await.unwrap().1.payload, "0");
+
+	let (sender, _blocking) = tokio::sync::mpsc::channel(1);
+	let handler = EventHandler::new(Some(sender))
+		.with_overflow(OverflowPolicy::Block { timeout: std::time::Duration::from_millis(50) });
+	workflow_manager.add_workflow(overflow_workflow("block", handler)).unwrap();
+	let report = workflow_manager.start_workflow("block").await.unwrap();
+	assert_eq!(outcome(report.output()), "ok,TimeoutError,TimeoutError");
+
+	let (sender, mut buffered) = tokio::sync::mpsc::channel(1);
+	let handler =
+		EventHandler::new(Some(sender)).with_overflow(OverflowPolicy::DropOldest { capacity: 1 });
+	workflow_manager
+		.add_workflow(overflow_workflow("drop_oldest", handler.clone()))
+		.unwrap();
+	let report = workflow_manager.start_workflow("drop_oldest").await.unwrap();
+	assert_eq!(outcome(report.output()), "ok,ok,ok");
+	assert_eq!(handler.overflow_stats().evicted, 1);
+	assert_eq!(buffered.recv().await.unwrap().1.payload, "0");
+	assert_eq!(buffered.recv().await.unwrap().1.payload, "2");
+	Ok(())
+}
+
+#[pyo3_asyncio::tokio::test]
+async fn querent_records_and_replays_events() -> pyo3::PyResult<()> {
+	let path =
+		std::env::temp_dir().join(format!("querent-events-replay-{}.avro", std::process::id()));
+	let _ = std::fs::remove_file(&path);
+
+	let querent = Querent::new().expect("Failed to create Querent");
+	querent.record_events(&path).unwrap();
+	let mut recorded = querent.subscribe_events(16);
+	querent.add_workflow(event_workflow("recorded", "extracted")).unwrap();
+	querent.start_workflow("recorded").await.unwrap();
+	recorded.recv().await.unwrap();
+	recorded.recv().await.unwrap();
+	// Gives the recorder a moment to write the events it was handed.
+	tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+	let journal = EventJournal::open(&path).unwrap();
+	let entries = journal.query(&EventFilter::new()).unwrap();
+	assert_eq!(entries.len(), 2);
+	assert_eq!(entries[0].event.payload, "extracted");
+	assert_eq!(entries[1].event_type, EventType::Vector);
+
+	// A graph loader runs again from the journal, without the workflow.
+	let loader = Querent::new().expect("Failed to create Querent");
+	let (sender, mut loaded) = tokio::sync::mpsc::unbounded_channel();
+	loader.register_callback(EventType::Graph, move |event| {
+		sender.send(event.file.clone()).unwrap();
+	});
+	let replayer = EventReplayer::new(&path)
+		.filter(EventFilter::new().event_type(EventType::Graph).file("callbacks.txt"))
+		.pace(ReplayPace::Accelerated(10.0));
+	assert_eq!(loader.replay_events(&replayer).await.unwrap(), 1);
+	assert_eq!(loaded.recv().await.unwrap(), "callbacks.txt");
+	std::fs::remove_file(&path).unwrap();
+	Ok(())
+}
+
+const CODE_STRUCTURED_EVENTS: &str = r#"
+async def send_structured(config):
+    handler = config['workflow']['event_handler']
+    handler.handle_event("Graph", {
+        "event_type": "Graph",
+        "timestamp": 1.0,
+        "payload": [{
+            "subject": "Marie Curie",
+            "subject_type": "person",
+            "predicate": "discovered",
+            "object": "polonium",
+            "object_type": "element",
+            "context": "Marie Curie discovered polonium in 1898.",
+        }],
+        "file": "curie.txt",
+        "doc_source": "test_doc_source",
+    })
+    handler.handle_event("Vector", {
+        "event_type": "Vector",
+        "timestamp": 2.0,
+        "payload": {"id": "curie-0", "embeddings": [0.25, 0.5], "metadata": {"page": 1}},
+        "file": "curie.txt",
+        "doc_source": "test_doc_source",
+    })
+"#;
+
+#[pyo3_asyncio::tokio::test]
+async fn querent_reads_structured_payloads_from_python() -> pyo3::PyResult<()> {
+	let querent = Querent::new().expect("Failed to create Querent");
+	let (graph_sender, mut triples) = tokio::sync::mpsc::unbounded_channel();
+	querent.register_callback(EventType::Graph, move |event| {
+		assert!(event.structured_payload().is_some());
+		graph_sender
+			.send(event.graph_triples().expect("Invalid graph payload"))
+			.unwrap();
+	});
+	let (vector_sender, mut embeddings) = tokio::sync::mpsc::unbounded_channel();
+	querent.register_callback(EventType::Vector, move |event| {
+		vector_sender
+			.send(event.vector_embeddings().expect("Invalid vector payload"))
+			.unwrap();
+	});
+	let mut workflow = event_workflow("structured", "unused");
+	workflow.attr = "send_structured".to_string();
+	workflow.code = Some(CODE_STRUCTURED_EVENTS.to_string());
+	workflow.arguments = vec![];
+	querent.add_workflow(workflow).unwrap();
+	querent.start_workflow("structured").await.unwrap();
+
+	let expected = GraphTriple {
+		subject: "Marie Curie".to_string(),
+		subject_type: Some("person".to_string()),
+		predicate: "discovered".to_string(),
+		predicate_type: None,
+		object: "polonium".to_string(),
+		object_type: Some("element".to_string()),
+		context: Some("Marie Curie discovered polonium in 1898.".to_string()),
+		metadata: Default::default(),
+	};
+	assert_eq!(triples.recv().await.unwrap(), vec![expected]);
+	let embedding = VectorEmbedding {
+		id: "curie-0".to_string(),
+		vector: vec![0.25, 0.5],
+		metadata: [("page".to_string(), serde_json::json!(1))].into(),
+	};
+	assert_eq!(embeddings.recv().await.unwrap(), vec![embedding]);
+	Ok(())
+}
@@ -0,0 +1,264 @@
+use crate::callbacks::{EventCallbackInterface, EventState, EventType};
+use std::{
+	collections::VecDeque,
+	fmt,
+	sync::{Arc, Mutex, Weak},
+};
+use tokio::sync::Notify;
+
+/// Error returned by `EventReceiver::recv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventRecvError {
+	/// The buffer of the receiver was full, so its oldest events were dropped. Receiving
+	/// again continues with the oldest event still buffered.
+	Lagged(u64),
+	/// Every handle of the bus is gone and every buffered event was received.
+	Closed,
+}
+
+impl fmt::Display for EventRecvError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			EventRecvError::Lagged(count) =>
+				write!(f, "Receiver lagged behind by {} events", count),
+			EventRecvError::Closed => write!(f, "Event bus closed"),
+		}
+	}
+}
+
+impl std::error::Error for EventRecvError {}
+
+struct SlotState {
+	events: VecDeque<(EventType, EventState)>,
+	/// Events dropped since the receiver was last told about it.
+	lagged: u64,
+	/// Events dropped since the receiver subscribed.
+	lagged_total: u64,
+	closed: bool,
+}
+
+/// Buffer of a single receiver.
+struct Slot {
+	capacity: usize,
+	state: Mutex<SlotState>,
+	available: Notify,
+}
+
+impl Slot {
+	fn push(&self, event: (EventType, EventState)) {
+		{
+			let mut state = self.state.lock().unwrap();
+			if state.events.len() == self.capacity {
+				state.events.pop_front();
+				state.lagged += 1;
+				state.lagged_total += 1;
+			}
+			state.events.push_back(event);
+		}
+		self.available.notify_one();
+	}
+
+	fn close(&self) {
+		self.state.lock().unwrap().closed = true;
+		self.available.notify_one();
+	}
+}
+
+struct BusInner {
+	/// Receivers remove themselves by being dropped, and are pruned on the next publish.
+	slots: Mutex<Vec<Weak<Slot>>>,
+}
+
+impl Drop for BusInner {
+	fn drop(&mut self) {
+		for slot in self.slots.get_mut().unwrap().drain(..) {
+			if let Some(slot) = slot.upgrade() {
+				slot.close();
+			}
+		}
+	}
+}
+
+/// Broadcasts the events of workflows to any number of receivers.
+///
+/// Every receiver has a buffer of its own, so a slow receiver only loses its own oldest events
+/// and never holds back the workflows or the other receivers. Receivers may subscribe and be
+/// dropped at any time; a receiver only sees the events published after it subscribed.
+#[derive(Clone)]
+pub struct EventBus {
+	inner: Arc<BusInner>,
+}
+
+impl Default for EventBus {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl fmt::Debug for EventBus {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("EventBus").field("receivers", &self.receiver_count()).finish()
+	}
+}
+
+impl EventBus {
+	/// Creates a bus without receivers.
+	pub fn new() -> Self {
+		Self { inner: Arc::new(BusInner { slots: Mutex::new(Vec::new()) }) }
+	}
+
+	/// Adds a receiver buffering up to `capacity` events, at least one.
+	pub fn subscribe(&self, capacity: usize) -> EventReceiver {
+		let slot = Arc::new(Slot {
+			capacity: capacity.max(1),
+			state: Mutex::new(SlotState {
+				events: VecDeque::new(),
+				lagged: 0,
+				lagged_total: 0,
+				closed: false,
+			}),
+			available: Notify::new(),
+		});
+		self.inner.slots.lock().unwrap().push(Arc::downgrade(&slot));
+		EventReceiver { slot }
+	}
+
+	/// Hands a copy of the event to every receiver, without waiting.
+	pub fn publish(&self, event_type: EventType, event: EventState) {
+		let slots: Vec<Arc<Slot>> = {
+			let mut slots = self.inner.slots.lock().unwrap();
+			slots.retain(|slot| slot.strong_count() > 0);
+			slots.iter().filter_map(Weak::upgrade).collect()
+		};
+		for slot in slots {
+			slot.push((event_type.clone(), event.clone()));
+		}
+	}
+
+	/// Number of receivers.
+	pub fn receiver_count(&self) -> usize {
+		self.inner
+			.slots
+			.lock()
+			.unwrap()
+			.iter()
+			.filter(|slot| slot.strong_count() > 0)
+			.count()
+	}
+}
+
+impl EventCallbackInterface for EventBus {
+	fn handle_event(&mut self, event_type: EventType, event_data: EventState) {
+		self.publish(event_type, event_data);
+	}
+}
+
+/// Receiver of the events published on an `EventBus`. Dropping it unsubscribes it.
+pub struct EventReceiver {
+	slot: Arc<Slot>,
+}
+
+impl EventReceiver {
+	/// Waits for the next event. Reports once how many events were dropped when the buffer
+	/// overflowed, before going on with the events still buffered.
+	pub async fn recv(&mut self) -> Result<(EventType, EventState), EventRecvError> {
+		loop {
+			{
+				let mut state = self.slot.state.lock().unwrap();
+				if state.lagged > 0 {
+					let lagged = state.lagged;
+					state.lagged = 0;
+					return Err(EventRecvError::Lagged(lagged));
+				}
+				if let Some(event) = state.events.pop_front() {
+					return Ok(event);
+				}
+				if state.closed {
+					return Err(EventRecvError::Closed);
+				}
+			}
+			// `notify_one` stores a wakeup when the receiver is not waiting yet, so an event
+			// published since the check above is not missed.
+			self.slot.available.notified().await;
+		}
+	}
+
+	/// Number of events buffered and not received yet.
+	pub fn len(&self) -> usize {
+		self.slot.state.lock().unwrap().events.len()
+	}
+
+	/// Returns true if no event is waiting to be received.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Number of events the receiver can buffer.
+	pub fn capacity(&self) -> usize {
+		self.slot.capacity
+	}
+
+	/// Number of events dropped from the buffer since the receiver subscribed.
+	pub fn lagged_total(&self) -> u64 {
+		self.slot.state.lock().unwrap().lagged_total
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn event(payload: &str) -> EventState {
+		EventState {
+			event_type: EventType::Graph,
+			timestamp: 0.0,
+			payload: payload.to_string(),
+			structured_payload: None,
+			file: "file".to_string(),
+			doc_source: "source".to_string(),
+			image_id: None,
+		}
+	}
+
+	async fn payload(receiver: &mut EventReceiver) -> Result<String, EventRecvError> {
+		receiver.recv().await.map(|(_, event)| event.payload)
+	}
+
+	#[tokio::test]
+	async fn event_bus_should_buffer_per_receiver() {
+		let bus = EventBus::new();
+		let mut fast = bus.subscribe(8);
+		let mut slow = bus.subscribe(2);
+		for payload in ["a", "b", "c"] {
+			bus.publish(EventType::Graph, event(payload));
+		}
+		assert_eq!(payload(&mut fast).await.unwrap(), "a");
+
+		assert_eq!(slow.lagged_total(), 1);
+		assert_eq!(payload(&mut slow).await, Err(EventRecvError::Lagged(1)));
+		assert_eq!(payload(&mut slow).await.unwrap(), "b");
+		assert_eq!(payload(&mut slow).await.unwrap(), "c");
+		assert_eq!(fast.len(), 2);
+	}
+
+	#[tokio::test]
+	async fn event_bus_should_let_receivers_come_and_go() {
+		let bus = EventBus::new();
+		let first = bus.subscribe(4);
+		bus.publish(EventType::Graph, event("before"));
+		let mut second = bus.subscribe(4);
+		assert_eq!(bus.receiver_count(), 2);
+		drop(first);
+		assert_eq!(bus.receiver_count(), 1);
+
+		let waiting = tokio::spawn(async move {
+			let received = payload(&mut second).await;
+			(received, payload(&mut second).await)
+		});
+		bus.publish(EventType::Graph, event("after"));
+		drop(bus);
+		let (received, closed) = waiting.await.unwrap();
+		assert_eq!(received.unwrap(), "after");
+		assert_eq!(closed, Err(EventRecvError::Closed));
+	}
+}
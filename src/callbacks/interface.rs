@@ -1,7 +1,12 @@
 // Import necessary items from the callbacks module
-use crate::callbacks::types::event::{EventState, EventType};
+use crate::callbacks::{
+	bus::EventBus,
+	overflow::{EventOutlet, EventRejected, OverflowPolicy, OverflowStats},
+	types::event::{EventState, EventType},
+};
 // Import necessary items from the pyo3 crate
 use pyo3::prelude::*;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 // Define the base interface for event callbacks
@@ -13,13 +18,65 @@ pub trait EventCallbackInterface {
 #[derive(Clone, Debug)]
 #[pyclass]
 pub struct EventHandler {
-	event_sender: Option<mpsc::Sender<(EventType, EventState)>>,
+	event_outlet: Option<Arc<EventOutlet>>,
+	event_bus: Option<EventBus>,
 }
 
 impl EventHandler {
-	// Constructor for EventHandler
+	// Constructor for EventHandler, rejecting events while the channel is full
 	pub fn new(event_sender: Option<mpsc::Sender<(EventType, EventState)>>) -> Self {
-		EventHandler { event_sender }
+		let event_outlet =
+			event_sender.map(|sender| EventOutlet::new(sender, OverflowPolicy::default()));
+		EventHandler { event_outlet, event_bus: None }
+	}
+
+	// Constructor for an EventHandler publishing to every receiver of the bus
+	pub fn with_bus(event_bus: EventBus) -> Self {
+		EventHandler { event_outlet: None, event_bus: Some(event_bus) }
+	}
+
+	// Sets what happens to events while the channel is full. Receivers of a bus have
+	// their own buffers, dropping their oldest events, so the policy does not apply to them.
+	pub fn with_overflow(mut self, policy: OverflowPolicy) -> Self {
+		if let Some(outlet) = self.event_outlet.take() {
+			self.event_outlet = Some(EventOutlet::new(outlet.sender(), policy));
+		}
+		self
+	}
+
+	// Returns the overflow policy of the channel, if events are sent to one
+	pub fn overflow_policy(&self) -> Option<&OverflowPolicy> {
+		self.event_outlet.as_ref().map(|outlet| outlet.policy())
+	}
+
+	// Returns how many events were sent, rejected, evicted or spilled so far
+	pub fn overflow_stats(&self) -> OverflowStats {
+		self.event_outlet.as_ref().map(|outlet| outlet.stats()).unwrap_or_default()
+	}
+
+	// Returns true if events are sent somewhere rather than printed
+	pub fn is_connected(&self) -> bool {
+		self.event_outlet.is_some() || self.event_bus.is_some()
+	}
+
+	// Handles an event, telling the caller when it was not accepted
+	pub fn try_handle_event(
+		&self,
+		event_type: EventType,
+		event_data: EventState,
+	) -> Result<(), EventRejected> {
+		// If the event bus is not None, publish the event to its receivers
+		if let Some(event_bus) = &self.event_bus {
+			event_bus.publish(event_type, event_data);
+		// If the event outlet is not None, send the event
+		} else if let Some(event_outlet) = &self.event_outlet {
+			event_outlet.deliver((event_type, event_data))?;
+		} else {
+			println!("Event sender is None");
+			println!("Event type: {:?}", event_type);
+			println!("Event data: {:?}", event_data);
+		}
+		Ok(())
 	}
 }
 
@@ -40,10 +97,18 @@ impl PyEventCallbackInterface {
 		PyEventCallbackInterface { event_handler }
 	}
 
-	// Python method to handle events
-	fn handle_event(&mut self, event_type: EventType, event_data: EventState) {
+	// Python method to handle events, raising an exception when the event is not accepted.
+	// The GIL is released while waiting for room in the channel.
+	fn handle_event(
+		&self,
+		py: Python<'_>,
+		event_type: EventType,
+		event_data: EventState,
+	) -> PyResult<()> {
 		// Delegate the event handling to the internal event handler
-		self.event_handler.handle_event(event_type, event_data);
+		let event_handler = &self.event_handler;
+		py.allow_threads(|| event_handler.try_handle_event(event_type, event_data))?;
+		Ok(())
 	}
 }
 
@@ -51,16 +116,8 @@ impl PyEventCallbackInterface {
 impl EventCallbackInterface for EventHandler {
 	// Implementation of the handle_event method for EventHandler
 	fn handle_event(&mut self, event_type: EventType, event_data: EventState) {
-		// If the event sender is not None, send the event
-		if let Some(event_sender) = &self.event_sender {
-			// Send the event
-			event_sender.try_send((event_type, event_data)).unwrap_or_else(|e| {
-				println!("Error sending event: {:?}", e);
-			});
-		} else {
-			println!("Event sender is None");
-			println!("Event type: {:?}", event_type);
-			println!("Event data: {:?}", event_data);
+		if let Err(e) = self.try_handle_event(event_type, event_data) {
+			log::warn!("Event dropped: {}", e);
 		}
 	}
 }
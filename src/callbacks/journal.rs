@@ -0,0 +1,498 @@
+use crate::{
+	callbacks::{
+		bus::{EventReceiver, EventRecvError},
+		EventCallbackInterface, EventState, EventType,
+	},
+	querent::QuerentError,
+};
+use apache_avro::{
+	types::{Record, Value},
+	Codec, Reader, Schema, Writer,
+};
+use std::{
+	collections::HashSet,
+	fs::{File, OpenOptions},
+	io::{Read, Seek, SeekFrom},
+	path::{Path, PathBuf},
+	sync::Mutex,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Number of events buffered for a journal recording the events of a bus.
+pub const EVENT_JOURNAL_CAPACITY: usize = 4096;
+
+/// Avro schema of the journal records.
+const EVENT_JOURNAL_SCHEMA: &str = r#"
+{
+	"type": "record",
+	"name": "JournalEvent",
+	"namespace": "querent.events",
+	"fields": [
+		{"name": "recorded_at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+		{
+			"name": "event_type",
+			"type": {
+				"type": "enum",
+				"name": "EventType",
+				"symbols": ["Graph", "Vector", "QueryResult", "Success", "Failure"]
+			}
+		},
+		{"name": "timestamp", "type": "double"},
+		{"name": "payload", "type": "string"},
+		{"name": "file", "type": "string"},
+		{"name": "doc_source", "type": "string"},
+		{"name": "image_id", "type": ["null", "string"], "default": null}
+	]
+}
+"#;
+
+const EVENT_TYPES: [EventType; 5] = [
+	EventType::Graph,
+	EventType::Vector,
+	EventType::QueryResult,
+	EventType::Success,
+	EventType::Failure,
+];
+
+/// An event, as recorded in the event journal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+	/// When the event was appended to the journal.
+	pub recorded_at: SystemTime,
+	/// Type the event was handled as.
+	pub event_type: EventType,
+	/// The event itself.
+	pub event: EventState,
+}
+
+impl JournalEntry {
+	fn to_value(&self, schema: &Schema) -> Result<Value, String> {
+		let mut record = Record::new(schema).ok_or("journal schema is not a record")?;
+		let symbol = EVENT_TYPES.iter().position(|t| *t == self.event_type).unwrap_or(0);
+		record.put("recorded_at", Value::TimestampMillis(to_millis(self.recorded_at)));
+		record.put("event_type", Value::Enum(symbol as u32, format!("{:?}", self.event_type)));
+		record.put("timestamp", self.event.timestamp);
+		record.put("payload", self.event.payload.as_str());
+		record.put("file", self.event.file.as_str());
+		record.put("doc_source", self.event.doc_source.as_str());
+		record.put(
+			"image_id",
+			match &self.event.image_id {
+				Some(image_id) => Value::Union(1, Box::new(Value::String(image_id.clone()))),
+				None => Value::Union(0, Box::new(Value::Null)),
+			},
+		);
+		Ok(record.into())
+	}
+
+	fn from_value(value: Value) -> Result<Self, String> {
+		let Value::Record(fields) = value else {
+			return Err(format!("expected a record, got {:?}", value));
+		};
+		let (mut recorded_at, mut event_type, mut timestamp) = (None, None, None);
+		let (mut payload, mut file, mut doc_source, mut image_id) = (None, None, None, None);
+		for (name, value) in fields {
+			match (name.as_str(), value) {
+				("recorded_at", Value::TimestampMillis(millis) | Value::Long(millis)) =>
+					recorded_at = Some(from_millis(millis)),
+				("event_type", Value::Enum(index, _)) =>
+					event_type = EVENT_TYPES.get(index as usize).cloned(),
+				("timestamp", Value::Double(value)) => timestamp = Some(value),
+				("payload", Value::String(value)) => payload = Some(value),
+				("file", Value::String(value)) => file = Some(value),
+				("doc_source", Value::String(value)) => doc_source = Some(value),
+				("image_id", Value::Union(_, value)) =>
+					image_id = match *value {
+						Value::String(value) => Some(Some(value)),
+						_ => Some(None),
+					},
+				(name, value) => return Err(format!("unexpected field {}: {:?}", name, value)),
+			}
+		}
+		let missing = |field: &str| format!("missing field {}", field);
+		let event_type = event_type.ok_or_else(|| missing("event_type"))?;
+		Ok(JournalEntry {
+			recorded_at: recorded_at.ok_or_else(|| missing("recorded_at"))?,
+			event: EventState {
+				event_type: event_type.clone(),
+				timestamp: timestamp.ok_or_else(|| missing("timestamp"))?,
+				payload: payload.ok_or_else(|| missing("payload"))?,
+				structured_payload: None,
+				file: file.ok_or_else(|| missing("file"))?,
+				doc_source: doc_source.ok_or_else(|| missing("doc_source"))?,
+				image_id: image_id.unwrap_or_default(),
+			},
+			event_type,
+		})
+	}
+}
+
+fn to_millis(time: SystemTime) -> i64 {
+	time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+fn from_millis(millis: i64) -> SystemTime {
+	UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+fn journal_schema() -> Schema {
+	Schema::parse_str(EVENT_JOURNAL_SCHEMA).expect("Invalid event journal schema")
+}
+
+struct JournalFile {
+	file: File,
+	/// Sync marker of the file, known once its header is written.
+	marker: Option<[u8; 16]>,
+}
+
+/// Append-only record of workflow events, stored as a snappy-compressed Avro file.
+///
+/// Every event is written and flushed as an Avro block of its own, so the journal can be
+/// read while it is being written and an interrupted write only loses the last event.
+pub struct EventJournal {
+	path: PathBuf,
+	schema: Schema,
+	file: Mutex<JournalFile>,
+}
+
+impl EventJournal {
+	/// Opens the journal at the given path, creating the file if it does not exist.
+	pub fn open(path: impl AsRef<Path>) -> Result<Self, QuerentError> {
+		let path = path.as_ref().to_path_buf();
+		let error = |e: &dyn std::fmt::Display| {
+			QuerentError::internal(format!(
+				"Unable to open event journal {}: {}",
+				path.display(),
+				e
+			))
+		};
+		let mut file = OpenOptions::new()
+			.create(true)
+			.read(true)
+			.append(true)
+			.open(&path)
+			.map_err(|e| error(&e))?;
+		let length = file.metadata().map_err(|e| error(&e))?.len();
+		let marker = if length == 0 {
+			None
+		} else {
+			// Validates the header, then reuses the marker ending the file.
+			Reader::new(&file).map_err(|e| error(&e))?;
+			Some(read_marker(&mut file).map_err(|e| error(&e))?)
+		};
+		Ok(EventJournal {
+			path,
+			schema: journal_schema(),
+			file: Mutex::new(JournalFile { file, marker }),
+		})
+	}
+
+	/// Path of the journal file.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Appends an event, recorded as of now.
+	pub fn append(&self, event_type: EventType, event: &EventState) -> Result<(), QuerentError> {
+		let entry =
+			JournalEntry { recorded_at: SystemTime::now(), event_type, event: event.clone() };
+		let error = |e: &dyn std::fmt::Display| {
+			QuerentError::internal(format!(
+				"Unable to write event journal {}: {}",
+				self.path.display(),
+				e
+			))
+		};
+		let value = entry.to_value(&self.schema).map_err(|e| error(&e))?;
+		let mut journal = self.file.lock().unwrap();
+		let JournalFile { file, marker } = &mut *journal;
+		let mut writer = match marker {
+			Some(marker) =>
+				Writer::append_to_with_codec(&self.schema, &mut *file, Codec::Snappy, *marker),
+			None => Writer::with_codec(&self.schema, &mut *file, Codec::Snappy),
+		};
+		writer.append(value).map_err(|e| error(&e))?;
+		writer.flush().map_err(|e| error(&e))?;
+		drop(writer);
+		if marker.is_none() {
+			*marker = Some(read_marker(file).map_err(|e| error(&e))?);
+		}
+		Ok(())
+	}
+
+	/// Appends the events of a bus receiver until the bus closes.
+	pub async fn record(&self, mut receiver: EventReceiver) {
+		loop {
+			match receiver.recv().await {
+				Ok((event_type, event)) =>
+					if let Err(e) = self.append(event_type, &event) {
+						log::error!("{}", e);
+					},
+				Err(EventRecvError::Lagged(count)) => log::warn!(
+					"Event journal {} fell behind, {} events were not recorded",
+					self.path.display(),
+					count
+				),
+				Err(EventRecvError::Closed) => break,
+			}
+		}
+	}
+
+	/// Returns the events selected by the filter, in the order they were recorded.
+	pub fn query(&self, filter: &EventFilter) -> Result<Vec<JournalEntry>, QuerentError> {
+		read_journal(&self.path, filter)
+	}
+}
+
+/// Reads the 16 bytes ending the file, which are the sync marker of an Avro file.
+fn read_marker(file: &mut File) -> std::io::Result<[u8; 16]> {
+	let mut marker = [0u8; 16];
+	file.seek(SeekFrom::End(-16))?;
+	file.read_exact(&mut marker)?;
+	Ok(marker)
+}
+
+fn read_journal(path: &Path, filter: &EventFilter) -> Result<Vec<JournalEntry>, QuerentError> {
+	let error = |e: &dyn std::fmt::Display| {
+		QuerentError::internal(format!("Unable to read event journal {}: {}", path.display(), e))
+	};
+	let file = File::open(path).map_err(|e| error(&e))?;
+	if file.metadata().map_err(|e| error(&e))?.len() == 0 {
+		return Ok(Vec::new());
+	}
+	let reader = Reader::new(file).map_err(|e| error(&e))?;
+	let mut entries = Vec::new();
+	for value in reader {
+		let entry = match value.map_err(|e| e.to_string()).and_then(JournalEntry::from_value) {
+			Ok(entry) => entry,
+			Err(e) => {
+				// Typically a block cut short by a crash, the rest of the file is unreadable.
+				log::warn!(
+					"Stopping at an unreadable block of event journal {}: {}",
+					path.display(),
+					e
+				);
+				break;
+			},
+		};
+		if filter.matches(&entry) {
+			entries.push(entry);
+		}
+	}
+	Ok(entries)
+}
+
+/// Selects events from the journal.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+	event_types: Option<HashSet<EventType>>,
+	file: Option<String>,
+	since: Option<SystemTime>,
+	until: Option<SystemTime>,
+}
+
+impl EventFilter {
+	/// Selects every event.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Only selects events of the given type, on top of the types selected before.
+	pub fn event_type(mut self, event_type: EventType) -> Self {
+		self.event_types.get_or_insert_with(HashSet::new).insert(event_type);
+		self
+	}
+
+	/// Only selects events about the given file.
+	pub fn file(mut self, file: &str) -> Self {
+		self.file = Some(file.to_string());
+		self
+	}
+
+	/// Only selects events recorded at or after `since`.
+	pub fn since(mut self, since: SystemTime) -> Self {
+		self.since = Some(since);
+		self
+	}
+
+	/// Only selects events recorded before `until`.
+	pub fn until(mut self, until: SystemTime) -> Self {
+		self.until = Some(until);
+		self
+	}
+
+	fn matches(&self, entry: &JournalEntry) -> bool {
+		self.event_types
+			.as_ref()
+			.map_or(true, |types| types.contains(&entry.event_type)) &&
+			self.file.as_ref().map_or(true, |file| *file == entry.event.file) &&
+			self.since.map_or(true, |since| entry.recorded_at >= since) &&
+			self.until.map_or(true, |until| entry.recorded_at < until)
+	}
+}
+
+/// How fast a replay goes through the journal.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplayPace {
+	/// One event after the other, without waiting.
+	#[default]
+	Unpaced,
+	/// With the delays the events were recorded with.
+	Original,
+	/// With the delays the events were recorded with, divided by the factor.
+	Accelerated(f64),
+}
+
+impl ReplayPace {
+	fn delay(self, gap: Duration) -> Duration {
+		match self {
+			ReplayPace::Unpaced => Duration::ZERO,
+			ReplayPace::Original => gap,
+			ReplayPace::Accelerated(factor) if factor > 0.0 => gap.div_f64(factor),
+			ReplayPace::Accelerated(_) => Duration::ZERO,
+		}
+	}
+}
+
+/// Feeds the events of a journal to an `EventHandler`, an `EventBus` or any other
+/// `EventCallbackInterface`, so that downstream consumers run again without the workflows
+/// that produced the events.
+#[derive(Debug, Clone)]
+pub struct EventReplayer {
+	path: PathBuf,
+	filter: EventFilter,
+	pace: ReplayPace,
+}
+
+impl EventReplayer {
+	/// Creates a replayer of every event of the journal at the given path, without waiting
+	/// between events.
+	pub fn new(path: impl AsRef<Path>) -> Self {
+		Self {
+			path: path.as_ref().to_path_buf(),
+			filter: EventFilter::new(),
+			pace: ReplayPace::default(),
+		}
+	}
+
+	/// Only replays the events selected by the filter.
+	pub fn filter(mut self, filter: EventFilter) -> Self {
+		self.filter = filter;
+		self
+	}
+
+	/// Sets how fast the events are replayed.
+	pub fn pace(mut self, pace: ReplayPace) -> Self {
+		self.pace = pace;
+		self
+	}
+
+	/// Replays the selected events into `target`, returning how many were replayed.
+	pub async fn replay<T: EventCallbackInterface>(
+		&self,
+		target: &mut T,
+	) -> Result<usize, QuerentError> {
+		let entries = read_journal(&self.path, &self.filter)?;
+		let mut previous: Option<SystemTime> = None;
+		for entry in &entries {
+			if let Some(previous) = previous {
+				let gap = entry.recorded_at.duration_since(previous).unwrap_or_default();
+				let delay = self.pace.delay(gap);
+				if !delay.is_zero() {
+					tokio::time::sleep(delay).await;
+				}
+			}
+			previous = Some(entry.recorded_at);
+			target.handle_event(entry.event_type.clone(), entry.event.clone());
+		}
+		Ok(entries.len())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::callbacks::EventBus;
+
+	fn event(event_type: EventType, file: &str, payload: &str) -> EventState {
+		EventState {
+			event_type,
+			timestamp: 1.5,
+			payload: payload.to_string(),
+			structured_payload: None,
+			file: file.to_string(),
+			doc_source: "source".to_string(),
+			image_id: (file == "image.png").then(|| "img-1".to_string()),
+		}
+	}
+
+	fn temp_journal(name: &str) -> PathBuf {
+		let path = std::env::temp_dir().join(format!(
+			"querent-event-journal-{}-{}.avro",
+			name,
+			std::process::id()
+		));
+		let _ = std::fs::remove_file(&path);
+		path
+	}
+
+	#[test]
+	fn event_journal_should_append_across_reopens_and_filter() {
+		let path = temp_journal("filter");
+		let journal = EventJournal::open(&path).unwrap();
+		journal
+			.append(EventType::Graph, &event(EventType::Graph, "a.txt", "g1"))
+			.unwrap();
+		journal
+			.append(EventType::Vector, &event(EventType::Vector, "image.png", "v1"))
+			.unwrap();
+		drop(journal);
+		let journal = EventJournal::open(&path).unwrap();
+		journal
+			.append(EventType::Graph, &event(EventType::Graph, "image.png", "g2"))
+			.unwrap();
+
+		let payloads = |filter: EventFilter| -> Vec<String> {
+			journal
+				.query(&filter)
+				.unwrap()
+				.into_iter()
+				.map(|entry| entry.event.payload)
+				.collect()
+		};
+		assert_eq!(payloads(EventFilter::new()), vec!["g1", "v1", "g2"]);
+		assert_eq!(payloads(EventFilter::new().event_type(EventType::Graph)), vec!["g1", "g2"]);
+		assert_eq!(payloads(EventFilter::new().file("image.png")), vec!["v1", "g2"]);
+		assert_eq!(
+			payloads(EventFilter::new().until(SystemTime::now() - Duration::from_secs(60))),
+			Vec::<String>::new()
+		);
+		let entries = journal.query(&EventFilter::new().file("image.png")).unwrap();
+		assert_eq!(entries[0].event, event(EventType::Vector, "image.png", "v1"));
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[tokio::test]
+	async fn event_replayer_should_keep_the_recorded_pace() {
+		let path = temp_journal("pace");
+		let journal = EventJournal::open(&path).unwrap();
+		journal
+			.append(EventType::Graph, &event(EventType::Graph, "a.txt", "first"))
+			.unwrap();
+		std::thread::sleep(Duration::from_millis(200));
+		journal
+			.append(EventType::Graph, &event(EventType::Graph, "a.txt", "second"))
+			.unwrap();
+
+		let mut bus = EventBus::new();
+		let mut receiver = bus.subscribe(4);
+		let started = std::time::Instant::now();
+		let replayer = EventReplayer::new(&path).pace(ReplayPace::Accelerated(2.0));
+		assert_eq!(replayer.replay(&mut bus).await.unwrap(), 2);
+		let elapsed = started.elapsed();
+		assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_millis(200));
+		assert_eq!(receiver.recv().await.unwrap().1.payload, "first");
+		assert_eq!(receiver.recv().await.unwrap().1.payload, "second");
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -14,3 +14,35 @@ pub use types::*;
 // behavior by responding to specific events during execution.
 pub mod interface;
 pub use interface::{EventCallbackInterface, PyEventCallbackInterface};
+
+// ! Event bus
+//
+// This module broadcasts the events of workflows to any number of
+// receivers, each with a buffer of its own, so that several consumers can
+// watch the same workflow.
+pub mod bus;
+pub use bus::{EventBus, EventReceiver, EventRecvError};
+
+// ! Overflow
+//
+// This module decides what happens to the events Python sends while the
+// event channel is full: rejecting them, blocking the caller, dropping the
+// oldest waiting events or spilling them to disk.
+pub mod overflow;
+pub use overflow::{EventRejected, OverflowPolicy, OverflowStats};
+
+// ! Journal
+//
+// This module records events in an append-only Avro file and replays them
+// into any event handler, so that downstream consumers can run again
+// without the workflows that produced the events.
+pub mod journal;
+pub use journal::{EventFilter, EventJournal, EventReplayer, JournalEntry, ReplayPace};
+
+// ! Subscriptions
+//
+// This module lets applications register closures or async handlers per
+// event type, dispatched on the tokio runtime, instead of reading the raw
+// event channels.
+pub mod subscriptions;
+pub use subscriptions::{EventSubscriptions, Subscription};
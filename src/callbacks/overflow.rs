@@ -0,0 +1,464 @@
+use crate::{
+	callbacks::{EventState, EventType},
+	tokio_runtime,
+};
+use pyo3::{
+	exceptions::{PyBufferError, PyConnectionError, PyOSError, PyTimeoutError},
+	PyErr,
+};
+use std::{
+	collections::VecDeque,
+	fs::{File, OpenOptions},
+	io::{BufRead, BufReader, Seek, SeekFrom, Write},
+	path::PathBuf,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+	time::Duration,
+};
+use tokio::{
+	runtime::{Handle, RuntimeFlavor},
+	sync::mpsc::{
+		self,
+		error::{SendTimeoutError, TrySendError},
+	},
+};
+
+type Event = (EventType, EventState);
+
+/// What an `EventHandler` does with an event when its channel is full.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+	/// Rejects the event, so the Python caller is told right away.
+	#[default]
+	DropNewest,
+	/// Makes the Python caller wait for room in the channel, for at most `timeout`, then
+	/// rejects the event.
+	Block { timeout: Duration },
+	/// Accepts the event and keeps up to `capacity` events waiting for room in memory,
+	/// dropping the oldest waiting event when there are more.
+	DropOldest { capacity: usize },
+	/// Accepts the event and appends the events waiting for room to a file, as JSON lines.
+	/// Events left in the file by a previous process are delivered before the ones spilled
+	/// next.
+	Spill { path: PathBuf },
+}
+
+/// Why an `EventHandler` did not accept an event.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum EventRejected {
+	#[error("Event channel is full")]
+	Full,
+	#[error("Event channel stayed full for {0:?}")]
+	TimedOut(Duration),
+	#[error("Event channel is closed")]
+	Closed,
+	#[error("Unable to spill event to disk: {0}")]
+	Spill(String),
+}
+
+impl From<EventRejected> for PyErr {
+	fn from(rejected: EventRejected) -> Self {
+		let message = rejected.to_string();
+		match rejected {
+			EventRejected::Full => PyBufferError::new_err(message),
+			EventRejected::TimedOut(_) => PyTimeoutError::new_err(message),
+			EventRejected::Closed => PyConnectionError::new_err(message),
+			EventRejected::Spill(_) => PyOSError::new_err(message),
+		}
+	}
+}
+
+/// Number of events an `EventHandler` handled, by outcome.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OverflowStats {
+	/// Events handed to the channel.
+	pub sent: u64,
+	/// Events not accepted, the caller was told.
+	pub rejected: u64,
+	/// Events accepted, then dropped to make room for newer ones.
+	pub evicted: u64,
+	/// Events written to the spill file.
+	pub spilled: u64,
+	/// Events accepted and still waiting for room in the channel.
+	pub pending: u64,
+}
+
+/// Sends events to a channel, applying the overflow policy when it is full. Shared by the
+/// clones of an `EventHandler`, so they queue behind the same backlog.
+pub(crate) struct EventOutlet {
+	sender: mpsc::Sender<Event>,
+	policy: OverflowPolicy,
+	backlog: Mutex<Backlog>,
+	sent: AtomicU64,
+	rejected: AtomicU64,
+	evicted: AtomicU64,
+	spilled: AtomicU64,
+}
+
+impl std::fmt::Debug for EventOutlet {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("EventOutlet").field("policy", &self.policy).finish()
+	}
+}
+
+#[derive(Default)]
+struct Backlog {
+	memory: VecDeque<Event>,
+	disk: Option<SpillFile>,
+	/// Whether a task is moving the backlog to the channel.
+	draining: bool,
+}
+
+impl Backlog {
+	fn len(&self) -> usize {
+		self.memory.len() + self.disk.as_ref().map_or(0, |disk| disk.len)
+	}
+
+	fn pop(&mut self) -> Option<Event> {
+		if let Some(event) = self.memory.pop_front() {
+			return Some(event);
+		}
+		let disk = self.disk.as_mut()?;
+		match disk.pop() {
+			Ok(event) => event,
+			Err(e) => {
+				log::error!("Unable to read spilled events from {}: {}", disk.path.display(), e);
+				None
+			},
+		}
+	}
+}
+
+/// Events waiting for room, appended to a file and read back from `offset`.
+struct SpillFile {
+	path: PathBuf,
+	file: File,
+	offset: u64,
+	len: usize,
+}
+
+impl SpillFile {
+	fn open(path: PathBuf) -> std::io::Result<Self> {
+		let file = OpenOptions::new().create(true).read(true).append(true).open(&path)?;
+		let len = BufReader::new(&file).lines().count();
+		Ok(Self { path, file, offset: 0, len })
+	}
+
+	fn push(&mut self, event: &Event) -> std::io::Result<()> {
+		let mut line = serde_json::to_vec(event)?;
+		line.push(b'\n');
+		self.file.write_all(&line)?;
+		self.len += 1;
+		Ok(())
+	}
+
+	fn pop(&mut self) -> std::io::Result<Option<Event>> {
+		while self.len > 0 {
+			let mut reader = BufReader::new(&self.file);
+			reader.seek(SeekFrom::Start(self.offset))?;
+			let mut line = String::new();
+			let read = reader.read_line(&mut line)?;
+			self.offset += read as u64;
+			self.len -= 1;
+			if self.len == 0 || read == 0 {
+				self.file.set_len(0)?;
+				self.offset = 0;
+				self.len = 0;
+			}
+			match serde_json::from_str(&line) {
+				Ok(event) => return Ok(Some(event)),
+				Err(e) => log::warn!("Skipping unreadable spilled event: {}", e),
+			}
+		}
+		Ok(None)
+	}
+}
+
+impl EventOutlet {
+	/// Creates an outlet for the channel. With `OverflowPolicy::Spill`, the events a previous
+	/// process left in the file start moving to the channel right away.
+	pub(crate) fn new(sender: mpsc::Sender<Event>, policy: OverflowPolicy) -> Arc<Self> {
+		let mut backlog = Backlog::default();
+		if let OverflowPolicy::Spill { path } = &policy {
+			// On failure, opening the file is tried again when an event has to be spilled.
+			match SpillFile::open(path.clone()) {
+				Ok(disk) => backlog.disk = Some(disk),
+				Err(e) => log::error!("Unable to open spill file {}: {}", path.display(), e),
+			}
+		}
+		let pending = backlog.len() > 0;
+		backlog.draining = pending;
+		let outlet = Arc::new(Self {
+			sender,
+			policy,
+			backlog: Mutex::new(backlog),
+			sent: AtomicU64::new(0),
+			rejected: AtomicU64::new(0),
+			evicted: AtomicU64::new(0),
+			spilled: AtomicU64::new(0),
+		});
+		if pending {
+			outlet.spawn_drain();
+		}
+		outlet
+	}
+
+	pub(crate) fn sender(&self) -> mpsc::Sender<Event> {
+		self.sender.clone()
+	}
+
+	pub(crate) fn policy(&self) -> &OverflowPolicy {
+		&self.policy
+	}
+
+	/// Sends the event or applies the overflow policy. Only `OverflowPolicy::Block` waits,
+	/// on the calling thread; a Tokio worker thread calling it hands its other tasks over to
+	/// another worker while it waits.
+	pub(crate) fn deliver(self: &Arc<Self>, event: Event) -> Result<(), EventRejected> {
+		let result = match &self.policy {
+			OverflowPolicy::DropNewest => self.sender.try_send(event).map_err(|e| match e {
+				TrySendError::Full(_) => EventRejected::Full,
+				TrySendError::Closed(_) => EventRejected::Closed,
+			}),
+			OverflowPolicy::Block { timeout } => self.send_blocking(event, *timeout),
+			OverflowPolicy::DropOldest { .. } | OverflowPolicy::Spill { .. } =>
+				return self.enqueue(event),
+		};
+		match &result {
+			Ok(()) => self.sent.fetch_add(1, Ordering::SeqCst),
+			Err(_) => self.rejected.fetch_add(1, Ordering::SeqCst),
+		};
+		result
+	}
+
+	fn send_blocking(&self, event: Event, timeout: Duration) -> Result<(), EventRejected> {
+		let event = match self.sender.try_send(event) {
+			Ok(()) => return Ok(()),
+			Err(TrySendError::Closed(_)) => return Err(EventRejected::Closed),
+			Err(TrySendError::Full(event)) => event,
+		};
+		let send = self.sender.send_timeout(event, timeout);
+		let sent = match Handle::try_current() {
+			Ok(handle) => match handle.runtime_flavor() {
+				RuntimeFlavor::MultiThread => tokio::task::block_in_place(|| handle.block_on(send)),
+				// Waiting would stall the only thread, including whoever receives the events.
+				_ => {
+					log::warn!("Unable to wait for room in the event channel on this runtime");
+					return Err(EventRejected::Full);
+				},
+			},
+			Err(_) => match tokio_runtime() {
+				Ok(runtime) => runtime.block_on(send),
+				Err(e) => {
+					log::error!("Unable to wait for room in the event channel: {}", e);
+					return Err(EventRejected::Full);
+				},
+			},
+		};
+		sent.map_err(|e| match e {
+			SendTimeoutError::Timeout(_) => EventRejected::TimedOut(timeout),
+			SendTimeoutError::Closed(_) => EventRejected::Closed,
+		})
+	}
+
+	fn enqueue(self: &Arc<Self>, event: Event) -> Result<(), EventRejected> {
+		let mut backlog = self.backlog.lock().unwrap();
+		// Events only skip the backlog when it is empty, so they are delivered in order.
+		let event = if backlog.len() == 0 {
+			match self.sender.try_send(event) {
+				Ok(()) => {
+					self.sent.fetch_add(1, Ordering::SeqCst);
+					return Ok(());
+				},
+				Err(TrySendError::Closed(_)) => {
+					self.rejected.fetch_add(1, Ordering::SeqCst);
+					return Err(EventRejected::Closed);
+				},
+				Err(TrySendError::Full(event)) => event,
+			}
+		} else {
+			event
+		};
+		match &self.policy {
+			OverflowPolicy::DropOldest { capacity } => {
+				if backlog.memory.len() >= (*capacity).max(1) {
+					backlog.memory.pop_front();
+					self.evicted.fetch_add(1, Ordering::SeqCst);
+				}
+				backlog.memory.push_back(event);
+			},
+			OverflowPolicy::Spill { path } => {
+				let spilled = match backlog.disk.as_mut() {
+					Some(disk) => disk.push(&event),
+					None => SpillFile::open(path.clone()).and_then(|mut disk| {
+						disk.push(&event)?;
+						backlog.disk = Some(disk);
+						Ok(())
+					}),
+				};
+				if let Err(e) = spilled {
+					self.rejected.fetch_add(1, Ordering::SeqCst);
+					return Err(EventRejected::Spill(e.to_string()));
+				}
+				self.spilled.fetch_add(1, Ordering::SeqCst);
+			},
+			_ => unreachable!("only buffering policies have a backlog"),
+		}
+		if !backlog.draining {
+			backlog.draining = true;
+			self.spawn_drain();
+		}
+		Ok(())
+	}
+
+	fn spawn_drain(self: &Arc<Self>) {
+		let outlet = self.clone();
+		let drain = async move { outlet.drain().await };
+		match tokio::runtime::Handle::try_current() {
+			Ok(handle) => {
+				handle.spawn(drain);
+			},
+			Err(_) => match tokio_runtime() {
+				Ok(runtime) => {
+					runtime.spawn(drain);
+				},
+				Err(e) => log::error!("Unable to drain the event backlog: {}", e),
+			},
+		}
+	}
+
+	/// Moves the backlog to the channel as room frees up, until it is empty.
+	async fn drain(self: Arc<Self>) {
+		loop {
+			let permit = match self.sender.reserve().await {
+				Ok(permit) => permit,
+				Err(_) => {
+					let mut backlog = self.backlog.lock().unwrap();
+					let lost = backlog.len();
+					backlog.memory.clear();
+					backlog.disk = None;
+					backlog.draining = false;
+					log::warn!("Event channel closed, {} pending events were dropped", lost);
+					return;
+				},
+			};
+			let mut backlog = self.backlog.lock().unwrap();
+			match backlog.pop() {
+				Some(event) => {
+					permit.send(event);
+					self.sent.fetch_add(1, Ordering::SeqCst);
+				},
+				None => {
+					backlog.draining = false;
+					return;
+				},
+			}
+		}
+	}
+
+	pub(crate) fn stats(&self) -> OverflowStats {
+		OverflowStats {
+			sent: self.sent.load(Ordering::SeqCst),
+			rejected: self.rejected.load(Ordering::SeqCst),
+			evicted: self.evicted.load(Ordering::SeqCst),
+			spilled: self.spilled.load(Ordering::SeqCst),
+			pending: self.backlog.lock().unwrap().len() as u64,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Instant;
+
+	fn event(payload: &str) -> Event {
+		let state = EventState {
+			event_type: EventType::Graph,
+			timestamp: 0.0,
+			payload: payload.to_string(),
+			structured_payload: None,
+			file: "file".to_string(),
+			doc_source: "source".to_string(),
+			image_id: None,
+		};
+		(EventType::Graph, state)
+	}
+
+	async fn payloads(receiver: &mut mpsc::Receiver<Event>, count: usize) -> Vec<String> {
+		let mut payloads = Vec::new();
+		for _ in 0..count {
+			payloads.push(receiver.recv().await.unwrap().1.payload);
+		}
+		payloads
+	}
+
+	// Waiting for room hands the worker thread over, which a single-threaded runtime cannot do.
+	#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+	async fn event_outlet_should_reject_when_full() {
+		let (sender, mut receiver) = mpsc::channel(1);
+		let outlet = EventOutlet::new(sender.clone(), OverflowPolicy::DropNewest);
+		outlet.deliver(event("a")).unwrap();
+		assert_eq!(outlet.deliver(event("b")), Err(EventRejected::Full));
+
+		let blocking =
+			EventOutlet::new(sender, OverflowPolicy::Block { timeout: Duration::from_millis(20) });
+		let started = Instant::now();
+		assert!(matches!(blocking.deliver(event("c")), Err(EventRejected::TimedOut(_))));
+		assert!(started.elapsed() >= Duration::from_millis(20));
+		assert_eq!(payloads(&mut receiver, 1).await, vec!["a"]);
+		blocking.deliver(event("d")).unwrap();
+		assert_eq!(outlet.stats().rejected, 1);
+		assert_eq!(blocking.stats().sent, 1);
+	}
+
+	#[tokio::test]
+	async fn event_outlet_should_drop_the_oldest_waiting_events() {
+		let (sender, mut receiver) = mpsc::channel(1);
+		let outlet = EventOutlet::new(sender, OverflowPolicy::DropOldest { capacity: 2 });
+		for payload in ["a", "b", "c", "d"] {
+			outlet.deliver(event(payload)).unwrap();
+		}
+		let stats = outlet.stats();
+		assert_eq!((stats.sent, stats.evicted, stats.pending), (1, 1, 2));
+		assert_eq!(payloads(&mut receiver, 3).await, vec!["a", "c", "d"]);
+	}
+
+	#[tokio::test]
+	async fn event_outlet_should_spill_to_disk_in_order() {
+		let path = std::env::temp_dir().join(format!("querent-spill-{}.jsonl", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		let (sender, mut receiver) = mpsc::channel(1);
+		let outlet = EventOutlet::new(sender, OverflowPolicy::Spill { path: path.clone() });
+		for payload in ["a", "b", "c"] {
+			outlet.deliver(event(payload)).unwrap();
+		}
+		assert_eq!(outlet.stats().spilled, 2);
+		assert_eq!(payloads(&mut receiver, 3).await, vec!["a", "b", "c"]);
+		tokio::task::yield_now().await;
+		assert_eq!(outlet.stats().pending, 0);
+		assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[tokio::test]
+	async fn event_outlet_should_deliver_events_left_in_the_spill_file() {
+		let path =
+			std::env::temp_dir().join(format!("querent-spill-left-{}.jsonl", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		let mut left = SpillFile::open(path.clone()).unwrap();
+		left.push(&event("a")).unwrap();
+		left.push(&event("b")).unwrap();
+		drop(left);
+
+		let (sender, mut receiver) = mpsc::channel(4);
+		let outlet = EventOutlet::new(sender, OverflowPolicy::Spill { path: path.clone() });
+		assert_eq!(payloads(&mut receiver, 2).await, vec!["a", "b"]);
+		outlet.deliver(event("c")).unwrap();
+		assert_eq!(payloads(&mut receiver, 1).await, vec!["c"]);
+		assert_eq!(outlet.stats().pending, 0);
+		std::fs::remove_file(&path).unwrap();
+	}
+}
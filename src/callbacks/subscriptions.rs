@@ -0,0 +1,227 @@
+use crate::{
+	callbacks::{
+		bus::{EventBus, EventReceiver, EventRecvError},
+		interface::EventHandler,
+		EventState, EventType,
+	},
+	querent::QuerentError,
+	tokio_runtime,
+};
+use futures::{future::BoxFuture, FutureExt};
+use std::{
+	collections::HashMap,
+	future::Future,
+	panic::{catch_unwind, AssertUnwindSafe},
+	sync::{Arc, Mutex, Weak},
+};
+
+/// Number of events buffered between Python and the dispatcher of the subscriptions.
+const SUBSCRIPTION_EVENTS_CAPACITY: usize = 1024;
+
+/// Callback registered for an event type.
+#[derive(Clone)]
+enum Callback {
+	/// Called on the dispatcher task, one event after the other.
+	Sync(Arc<dyn Fn(&EventState) + Send + Sync>),
+	/// Spawned on the runtime for every event, so a slow handler does not hold back the
+	/// others.
+	Async(Arc<dyn Fn(EventState) -> BoxFuture<'static, ()> + Send + Sync>),
+}
+
+#[derive(Default)]
+struct Registry {
+	next_id: u64,
+	callbacks: HashMap<EventType, Vec<(u64, Callback)>>,
+}
+
+impl Registry {
+	fn insert(&mut self, event_type: EventType, callback: Callback) -> u64 {
+		self.next_id += 1;
+		self.callbacks.entry(event_type).or_default().push((self.next_id, callback));
+		self.next_id
+	}
+}
+
+/// Handle of a registered callback.
+///
+/// Dropping the handle keeps the callback registered, `unsubscribe` removes it.
+#[derive(Debug, Clone)]
+pub struct Subscription {
+	id: u64,
+	event_type: EventType,
+	registry: Weak<Mutex<Registry>>,
+}
+
+impl Subscription {
+	/// Event type the callback is registered for.
+	pub fn event_type(&self) -> &EventType {
+		&self.event_type
+	}
+
+	/// Removes the callback, so it is not called for the events dispatched from now on.
+	/// Returns false if it was already removed.
+	pub fn unsubscribe(&self) -> bool {
+		let Some(registry) = self.registry.upgrade() else {
+			return false;
+		};
+		let mut registry = registry.lock().unwrap();
+		let Some(callbacks) = registry.callbacks.get_mut(&self.event_type) else {
+			return false;
+		};
+		let before = callbacks.len();
+		callbacks.retain(|(id, _)| *id != self.id);
+		before != callbacks.len()
+	}
+}
+
+/// Callbacks registered per event type, called with the events Python sends through the
+/// `EventHandler` of the registry.
+///
+/// The callbacks are a single receiver of an `EventBus`, which other receivers may watch too.
+pub struct EventSubscriptions {
+	registry: Arc<Mutex<Registry>>,
+	bus: EventBus,
+}
+
+impl EventSubscriptions {
+	/// Creates an empty registry and spawns its dispatcher on the current tokio runtime, or
+	/// on the runtime of the crate outside of one.
+	pub fn new() -> Result<Self, QuerentError> {
+		let registry = Arc::new(Mutex::new(Registry::default()));
+		let bus = EventBus::new();
+		let dispatcher =
+			dispatch(Arc::downgrade(&registry), bus.subscribe(SUBSCRIPTION_EVENTS_CAPACITY));
+		match tokio::runtime::Handle::try_current() {
+			Ok(handle) => handle.spawn(dispatcher),
+			Err(_) => tokio_runtime()?.spawn(dispatcher),
+		};
+		Ok(Self { registry, bus })
+	}
+
+	/// Calls `callback` with every event of the given type.
+	pub fn subscribe<F>(&self, event_type: EventType, callback: F) -> Subscription
+	where
+		F: Fn(&EventState) + Send + Sync + 'static,
+	{
+		self.insert(event_type, Callback::Sync(Arc::new(callback)))
+	}
+
+	/// Runs the future returned by `callback` for every event of the given type.
+	pub fn subscribe_async<F, Fut>(&self, event_type: EventType, callback: F) -> Subscription
+	where
+		F: Fn(EventState) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = ()> + Send + 'static,
+	{
+		let callback = move |event: EventState| callback(event).boxed();
+		self.insert(event_type, Callback::Async(Arc::new(callback)))
+	}
+
+	fn insert(&self, event_type: EventType, callback: Callback) -> Subscription {
+		let id = self.registry.lock().unwrap().insert(event_type.clone(), callback);
+		Subscription { id, event_type, registry: Arc::downgrade(&self.registry) }
+	}
+
+	/// Number of callbacks registered for the event type.
+	pub fn count(&self, event_type: &EventType) -> usize {
+		self.registry.lock().unwrap().callbacks.get(event_type).map_or(0, Vec::len)
+	}
+
+	/// Event handler feeding the registry, to put in the configuration of a workflow.
+	pub fn event_handler(&self) -> EventHandler {
+		EventHandler::with_bus(self.bus.clone())
+	}
+
+	/// Bus the events go through, to watch them with receivers of its own.
+	pub fn event_bus(&self) -> &EventBus {
+		&self.bus
+	}
+}
+
+/// Calls the callbacks of every event until the registry is dropped or every event handler
+/// is gone.
+async fn dispatch(registry: Weak<Mutex<Registry>>, mut events: EventReceiver) {
+	loop {
+		let (event_type, event) = match events.recv().await {
+			Ok(event) => event,
+			Err(EventRecvError::Lagged(count)) => {
+				log::warn!("Event callbacks fell behind, {} events were dropped", count);
+				continue;
+			},
+			Err(EventRecvError::Closed) => break,
+		};
+		let Some(registry) = registry.upgrade() else {
+			break;
+		};
+		// Taken out of the lock, so callbacks may subscribe and unsubscribe.
+		let callbacks: Vec<Callback> = registry
+			.lock()
+			.unwrap()
+			.callbacks
+			.get(&event_type)
+			.map(|callbacks| callbacks.iter().map(|(_, callback)| callback.clone()).collect())
+			.unwrap_or_default();
+		for callback in callbacks {
+			match callback {
+				Callback::Sync(callback) =>
+					if catch_unwind(AssertUnwindSafe(|| callback(&event))).is_err() {
+						log::error!("Callback for {:?} events panicked", event_type);
+					},
+				Callback::Async(callback) => {
+					tokio::spawn(callback(event.clone()));
+				},
+			}
+		}
+	}
+	log::debug!("Event subscriptions dispatcher stopped");
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::callbacks::EventCallbackInterface;
+	use std::time::Duration;
+	use tokio::sync::mpsc;
+
+	fn event(event_type: EventType, payload: &str) -> EventState {
+		EventState {
+			event_type,
+			timestamp: 0.0,
+			payload: payload.to_string(),
+			structured_payload: None,
+			file: "file".to_string(),
+			doc_source: "source".to_string(),
+			image_id: None,
+		}
+	}
+
+	#[tokio::test]
+	async fn event_subscriptions_should_dispatch_by_type_until_unsubscribed() {
+		let subscriptions = EventSubscriptions::new().unwrap();
+		let (sender, mut received) = mpsc::unbounded_channel();
+		let graph_sender = sender.clone();
+		let graph = subscriptions.subscribe(EventType::Graph, move |event| {
+			graph_sender.send(format!("graph {}", event.payload)).unwrap();
+		});
+		subscriptions.subscribe(EventType::Graph, |_| panic!("faulty callback"));
+		subscriptions.subscribe_async(EventType::Vector, move |event| {
+			let sender = sender.clone();
+			async move {
+				sender.send(format!("vector {}", event.payload)).unwrap();
+			}
+		});
+		assert_eq!(subscriptions.count(&EventType::Graph), 2);
+
+		let mut handler = subscriptions.event_handler();
+		handler.handle_event(EventType::Graph, event(EventType::Graph, "a"));
+		handler.handle_event(EventType::Vector, event(EventType::Vector, "b"));
+		assert_eq!(received.recv().await.unwrap(), "graph a");
+		assert_eq!(received.recv().await.unwrap(), "vector b");
+
+		assert!(graph.unsubscribe());
+		assert!(!graph.unsubscribe());
+		handler.handle_event(EventType::Graph, event(EventType::Graph, "c"));
+		handler.handle_event(EventType::Vector, event(EventType::Vector, "d"));
+		assert_eq!(received.recv().await.unwrap(), "vector d");
+		assert!(tokio::time::timeout(Duration::from_millis(20), received.recv()).await.is_err());
+	}
+}
@@ -1,9 +1,11 @@
+// Import necessary items from the crate
+use crate::cross::{CLRepr, CLReprPython};
 // Import necessary items from the pyo3 crate
-use pyo3::{exceptions::PyTypeError, prelude::*};
-use serde::Serialize;
+use pyo3::{exceptions::PyTypeError, prelude::*, types::PyString};
+use serde::{Deserialize, Serialize};
 
 // Define an enumeration for different event types
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum EventType {
 	Graph,
 	Vector,
@@ -33,23 +35,56 @@ impl<'a> FromPyObject<'a> for EventType {
 }
 
 // Define a structure to represent the state of an event
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct EventState {
 	pub event_type: EventType,
 	pub timestamp: f64,
 	pub payload: String,
+	/// Payload as sent by Python when it was a dict or a list rather than a string, which
+	/// `payload` holds as JSON too. Dropped by serialization; the typed payload accessors fall
+	/// back to parsing `payload` without it.
+	#[serde(skip)]
+	pub(crate) structured_payload: Option<serde_json::Value>,
 	pub file: String,
 	pub doc_source: String,
 	pub image_id: Option<String>,
 }
 
+impl EventState {
+	/// Creates an event with a string payload.
+	pub fn new(
+		event_type: EventType,
+		timestamp: f64,
+		payload: String,
+		file: String,
+		doc_source: String,
+		image_id: Option<String>,
+	) -> Self {
+		EventState {
+			event_type,
+			timestamp,
+			payload,
+			structured_payload: None,
+			file,
+			doc_source,
+			image_id,
+		}
+	}
+
+	/// Payload as sent by Python when it was a dict or a list, if the event was not
+	/// deserialized since.
+	pub fn structured_payload(&self) -> Option<&serde_json::Value> {
+		self.structured_payload.as_ref()
+	}
+}
+
 // Implement conversion from Python object to EventState
 impl<'a> FromPyObject<'a> for EventState {
 	fn extract(ob: &'a PyAny) -> PyResult<Self> {
 		// Extract values for event_type, timestamp, and payload from the Python object
 		let event_type = ob.get_item("event_type")?.extract()?;
 		let timestamp = ob.get_item("timestamp")?.extract()?;
-		let payload = ob.get_item("payload")?.extract()?;
+		let (payload, structured_payload) = extract_payload(ob.get_item("payload")?)?;
 		let file = ob.get_item("file")?.extract()?;
 		let doc_source = ob.get_item("doc_source")?.extract()?;
 		let image_id_res = ob.get_item("image_id");
@@ -58,6 +93,24 @@ impl<'a> FromPyObject<'a> for EventState {
 			Err(_err) => None,
 		};
 		// Create and return an EventState instance
-		Ok(EventState { event_type, timestamp, payload, file, doc_source, image_id })
+		Ok(EventState {
+			event_type,
+			timestamp,
+			payload,
+			structured_payload,
+			file,
+			doc_source,
+			image_id,
+		})
+	}
+}
+
+// Extract a payload sent as a string, or as a dict or list kept as is and encoded to JSON
+// here so that Python does not have to
+fn extract_payload(payload: &PyAny) -> PyResult<(String, Option<serde_json::Value>)> {
+	if payload.is_instance_of::<PyString>() {
+		return Ok((payload.extract()?, None));
 	}
+	let value = serde_json::Value::from(&CLRepr::from_python_ref(payload)?);
+	Ok((value.to_string(), Some(value)))
 }
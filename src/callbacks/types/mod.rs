@@ -12,55 +12,62 @@
 ///
 /// # Usage
 ///
-/// To utilize the callback system, users can import the module and use the provided event types
-/// to define their custom callbacks. These callbacks can then be registered with the querent to
-/// receive notifications about specific events.
+/// Callbacks are registered with `Querent` for an event type, and are called with the
+/// `EventState` of every event of that type the workflows send. Registering returns a
+/// `Subscription`, which removes the callback when unsubscribed.
 ///
 /// ```rust
-/// use your_crate::event::EventType;
-/// use your_crate::Querent;
+/// use querent_synapse::{callbacks::EventType, querent::Querent};
 ///
-/// fn main() {
+/// fn main() -> Result<(), String> {
 ///     // Create a querent instance
-///     let querent = Querent::new();
+///     let querent = Querent::new()?;
 ///
-///     // Define a callback for the 'ChatCompleted' event
-///     querent.register_callback(EventType::ChatCompleted, |event| {
-///         // Custom logic to handle the 'ChatCompleted' event
-///         println!("Chat completed! Event details: {:?}", event);
+///     // Define a callback for the 'Graph' event
+///     let subscription = querent.register_callback(EventType::Graph, |event| {
+///         // Custom logic to handle the 'Graph' event
+///         println!("Graph updated! Event details: {:?}", event);
 ///     });
 ///
-///     // Execute the querent, triggering events and invoking registered callbacks
-///     querent.execute();
+///     // Start the workflows, triggering events and invoking registered callbacks
+///     // ...
+///
+///     // Stop receiving 'Graph' events
+///     subscription.unsubscribe();
+///     Ok(())
 /// }
 /// ```
 ///
 /// # Event Types
 ///
-/// The module exports several event types, such as `ContextualGraphUpdated`,
-/// `SemanticGraphUpdated`, and `ChatCompleted`. Users can match on these event types to
-/// implement specific behavior based on the type of event received.
+/// The module exports the event types `Graph`, `Vector`, `QueryResult`, `Success` and
+/// `Failure`. Users can match on these event types to implement specific behavior based on the
+/// type of event received. The payloads of `Graph` and `Vector` events are read as typed models
+/// with `EventState::graph_triples` and `EventState::vector_embeddings`; Python may send them
+/// as dicts or lists rather than JSON strings.
 ///
 /// # Examples
 ///
+/// Slow work belongs in an async callback, which runs on the tokio runtime without holding
+/// back the other callbacks.
+///
 /// ```rust
-/// use your_crate::event::EventType;
-/// use your_crate::Querent;
+/// use querent_synapse::{callbacks::EventType, querent::Querent};
 ///
-/// fn main() {
-///     // Create a querent instance
-///     let querent = Querent::new();
+/// fn main() -> Result<(), String> {
+///     let querent = Querent::new()?;
 ///
-///     // Define a callback for the 'SemanticGraphUpdated' event
-///     querent.register_callback(EventType::SemanticGraphUpdated, |event| {
-///         // Custom logic to handle the 'SemanticGraphUpdated' event
-///         println!("Semantic graph updated! Event details: {:?}", event);
+///     // Define an async callback for the 'Vector' event
+///     querent.register_async_callback(EventType::Vector, |event| async move {
+///         // Custom logic to handle the 'Vector' event
+///         println!("Vector computed! Payload: {}", event.payload);
 ///     });
-///
-///     // Execute the querent, triggering events and invoking registered callbacks
-///     querent.execute();
+///     Ok(())
 /// }
 /// ```
 ///
 pub mod event;
 pub use event::*;
+
+pub mod payload;
+pub use payload::*;
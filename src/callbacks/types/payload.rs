@@ -0,0 +1,151 @@
+use crate::callbacks::types::event::{EventState, EventType};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Why the payload of an event could not be read as a typed model.
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum PayloadError {
+	#[error("Expected a {expected:?} event, got a {actual:?} event")]
+	WrongEventType { expected: EventType, actual: EventType },
+	#[error("Invalid {event_type:?} payload: {message}")]
+	Invalid { event_type: EventType, message: String },
+}
+
+/// Subject, predicate and object extracted from a document, the payload of `Graph` events.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphTriple {
+	pub subject: String,
+	/// Entity type of the subject, such as `person` or `organization`.
+	#[serde(default)]
+	pub subject_type: Option<String>,
+	pub predicate: String,
+	/// Kind of relation the predicate expresses.
+	#[serde(default)]
+	pub predicate_type: Option<String>,
+	pub object: String,
+	/// Entity type of the object.
+	#[serde(default)]
+	pub object_type: Option<String>,
+	/// Passage of the document the triple was extracted from.
+	#[serde(default, alias = "sentence")]
+	pub context: Option<String>,
+	/// Any other field sent along with the triple.
+	#[serde(flatten)]
+	pub metadata: BTreeMap<String, serde_json::Value>,
+}
+
+/// Embedding of a piece of a document, the payload of `Vector` events.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VectorEmbedding {
+	/// Identifier of the embedded piece, unique within the document.
+	pub id: String,
+	#[serde(alias = "embedding", alias = "embeddings")]
+	pub vector: Vec<f32>,
+	/// Any other field sent along with the embedding.
+	#[serde(default)]
+	pub metadata: BTreeMap<String, serde_json::Value>,
+}
+
+/// A payload holding either a single item or a list of them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+	Many(Vec<T>),
+	One(T),
+}
+
+impl EventState {
+	/// Reads the payload of a `Graph` event as the triples it holds, whether it is a single
+	/// triple or a list of them.
+	pub fn graph_triples(&self) -> Result<Vec<GraphTriple>, PayloadError> {
+		self.typed_payload(EventType::Graph)
+	}
+
+	/// Reads the payload of a `Vector` event as the embeddings it holds, whether it is a
+	/// single embedding or a list of them.
+	pub fn vector_embeddings(&self) -> Result<Vec<VectorEmbedding>, PayloadError> {
+		self.typed_payload(EventType::Vector)
+	}
+
+	fn typed_payload<T: DeserializeOwned>(
+		&self,
+		expected: EventType,
+	) -> Result<Vec<T>, PayloadError> {
+		if self.event_type != expected {
+			return Err(PayloadError::WrongEventType { expected, actual: self.event_type.clone() });
+		}
+		// A payload Python sent as a dict or a list is read as is, a string is parsed.
+		let payload = match &self.structured_payload {
+			Some(value) => serde_json::from_value(value.clone()),
+			None => serde_json::from_str(&self.payload),
+		};
+		match payload {
+			Ok(OneOrMany::Many(items)) => Ok(items),
+			Ok(OneOrMany::One(item)) => Ok(vec![item]),
+			Err(e) => Err(PayloadError::Invalid { event_type: expected, message: e.to_string() }),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn event(event_type: EventType, payload: &str) -> EventState {
+		EventState {
+			event_type,
+			timestamp: 0.0,
+			payload: payload.to_string(),
+			structured_payload: None,
+			file: "file".to_string(),
+			doc_source: "source".to_string(),
+			image_id: None,
+		}
+	}
+
+	#[test]
+	fn event_state_should_parse_graph_triples() {
+		let single = event(
+			EventType::Graph,
+			r#"{"subject": "Marie Curie", "subject_type": "person", "predicate": "discovered",
+				"object": "polonium", "object_type": "element", "sentence": "She discovered polonium.",
+				"score": 0.9}"#,
+		);
+		let triples = single.graph_triples().unwrap();
+		assert_eq!(triples.len(), 1);
+		assert_eq!(triples[0].subject_type.as_deref(), Some("person"));
+		assert_eq!(triples[0].predicate_type, None);
+		assert_eq!(triples[0].context.as_deref(), Some("She discovered polonium."));
+		assert_eq!(triples[0].metadata.get("score"), Some(&serde_json::json!(0.9)));
+
+		let many = event(
+			EventType::Graph,
+			r#"[{"subject": "a", "predicate": "b", "object": "c"},
+				{"subject": "d", "predicate": "e", "object": "f"}]"#,
+		);
+		assert_eq!(many.graph_triples().unwrap()[1].object, "f");
+	}
+
+	#[test]
+	fn event_state_should_reject_mismatched_payloads() {
+		let vector = event(EventType::Vector, r#"{"id": "1", "embeddings": [0.5, 1.0]}"#);
+		assert_eq!(vector.vector_embeddings().unwrap()[0].vector, vec![0.5, 1.0]);
+		assert_eq!(
+			vector.graph_triples().unwrap_err(),
+			PayloadError::WrongEventType { expected: EventType::Graph, actual: EventType::Vector }
+		);
+		let error = event(EventType::Vector, r#"{"id": "1"}"#).vector_embeddings().unwrap_err();
+		assert!(matches!(error, PayloadError::Invalid { event_type: EventType::Vector, .. }));
+		assert!(event(EventType::Graph, "not json").graph_triples().is_err());
+	}
+
+	#[test]
+	fn event_state_should_read_structured_payloads_as_is() {
+		let mut vector = event(EventType::Vector, "");
+		vector.structured_payload =
+			Some(serde_json::json!([{"id": "1", "vector": [0.5], "metadata": {"page": 2}}]));
+		let embeddings = vector.vector_embeddings().unwrap();
+		assert_eq!(embeddings[0].vector, vec![0.5]);
+		assert_eq!(embeddings[0].metadata.get("page"), Some(&serde_json::json!(2)));
+	}
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{callbacks::interface::EventHandler, comm::ChannelHandler};
+
+use super::{
+	config::{CollectorConfig, EngineConfig, ResourceConfig, WorkflowConfig},
+	Config,
+};
+
+/// File representation of a `Config`.
+///
+/// Only the declarative fields are read from the file; channels and event handlers are
+/// runtime objects and are wired in by `ConfigSpec::build`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigSpec {
+	/// Version of the configuration format.
+	#[serde(default = "default_version")]
+	pub version: f32,
+	/// Unique identifier for the querent (user/client).
+	#[serde(default)]
+	pub querent_id: Option<String>,
+	/// Name of the querent.
+	#[serde(default)]
+	pub querent_name: Option<String>,
+	/// Configuration for the workflow.
+	#[serde(default)]
+	pub workflow: Option<WorkflowConfigSpec>,
+	/// List of collector configurations.
+	#[serde(default)]
+	pub collectors: Vec<CollectorConfigSpec>,
+	/// List of engine configurations.
+	#[serde(default)]
+	pub engines: Vec<EngineConfigSpec>,
+	/// Optional resource configuration.
+	#[serde(default)]
+	pub resource: Option<ResourceConfigSpec>,
+}
+
+/// File representation of a `WorkflowConfig`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowConfigSpec {
+	/// Name of the workflow.
+	pub name: String,
+	/// Unique identifier for the workflow.
+	pub id: String,
+	/// Additional configuration options for the workflow.
+	#[serde(default, deserialize_with = "deserialize_options")]
+	pub config: HashMap<String, String>,
+}
+
+/// File representation of a `CollectorConfig`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CollectorConfigSpec {
+	/// Unique identifier for the collector.
+	pub id: String,
+	/// Name of the collector.
+	pub name: String,
+	/// Backend used by the collector.
+	pub backend: String,
+	/// Additional configuration options for the collector.
+	#[serde(default, deserialize_with = "deserialize_options")]
+	pub config: HashMap<String, String>,
+}
+
+/// File representation of an `EngineConfig`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EngineConfigSpec {
+	/// Unique identifier for the engine.
+	pub id: String,
+	/// Name of the engine.
+	pub name: String,
+	/// Config for the engine.
+	#[serde(default, deserialize_with = "deserialize_options")]
+	pub config: HashMap<String, String>,
+}
+
+/// File representation of a `ResourceConfig`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResourceConfigSpec {
+	/// Unique identifier for the resource.
+	pub id: String,
+	/// Maximum number of workers allowed (optional).
+	#[serde(default)]
+	pub max_workers_allowed: Option<u32>,
+	/// Maximum number of workers per collector (optional).
+	#[serde(default)]
+	pub max_workers_per_collector: Option<u32>,
+	/// Maximum number of workers per engine (optional).
+	#[serde(default)]
+	pub max_workers_per_engine: Option<u32>,
+	/// Maximum number of workers per querent (optional).
+	#[serde(default)]
+	pub max_workers_per_querent: Option<u32>,
+}
+
+/// Runtime objects handed to every `Config` built from a file.
+#[derive(Debug, Clone)]
+pub struct ConfigWiring {
+	/// Receives the events sent by Python. When unset, `Querent::add_workflow` connects the
+	/// events to its callbacks.
+	pub event_handler: Option<EventHandler>,
+	/// Exchanges messages between Rust and Python.
+	pub channel: Option<ChannelHandler>,
+	/// Feeds live tokens to the engines.
+	pub tokens_feader: Option<ChannelHandler>,
+}
+
+impl ConfigSpec {
+	/// Builds the `Config`, wiring in the given runtime objects.
+	pub fn build(self, wiring: &ConfigWiring) -> Config {
+		let workflow = self.workflow.unwrap_or_else(|| WorkflowConfigSpec {
+			name: "workflow".to_string(),
+			id: "workflow".to_string(),
+			config: HashMap::new(),
+		});
+		Config {
+			version: self.version,
+			querent_id: self.querent_id.unwrap_or_else(|| "querent".to_string()),
+			querent_name: self.querent_name.unwrap_or_else(|| "Querent".to_string()),
+			workflow: WorkflowConfig {
+				name: workflow.name,
+				id: workflow.id,
+				config: workflow.config,
+				inner_channel: wiring.channel.clone(),
+				channel: None,
+				inner_event_handler: wiring.event_handler.clone(),
+				event_handler: None,
+				inner_tokens_feader: wiring.tokens_feader.clone(),
+				tokens_feader: None,
+			},
+			collectors: self
+				.collectors
+				.into_iter()
+				.map(|collector| CollectorConfig {
+					id: collector.id,
+					name: collector.name,
+					backend: collector.backend,
+					config: collector.config,
+					inner_channel: wiring.channel.clone(),
+					channel: None,
+				})
+				.collect(),
+			engines: self
+				.engines
+				.into_iter()
+				.map(|engine| EngineConfig {
+					id: engine.id,
+					name: engine.name,
+					config: engine.config,
+					inner_channel: wiring.channel.clone(),
+					channel: None,
+				})
+				.collect(),
+			resource: self.resource.map(|resource| ResourceConfig {
+				id: resource.id,
+				max_workers_allowed: resource.max_workers_allowed,
+				max_workers_per_collector: resource.max_workers_per_collector,
+				max_workers_per_engine: resource.max_workers_per_engine,
+				max_workers_per_querent: resource.max_workers_per_querent,
+			}),
+		}
+	}
+}
+
+fn default_version() -> f32 {
+	0.1
+}
+
+/// Reads an option map, accepting numbers and booleans as values so that `port: 8080`
+/// does not have to be quoted.
+fn deserialize_options<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum OptionValue {
+		String(String),
+		Int(i64),
+		Float(f64),
+		Bool(bool),
+	}
+
+	let options = HashMap::<String, OptionValue>::deserialize(deserializer)?;
+	Ok(options
+		.into_iter()
+		.map(|(key, value)| {
+			let value = match value {
+				OptionValue::String(value) => value,
+				OptionValue::Int(value) => value.to_string(),
+				OptionValue::Float(value) => value.to_string(),
+				OptionValue::Bool(value) => value.to_string(),
+			};
+			(key, value)
+		})
+		.collect())
+}
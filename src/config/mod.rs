@@ -60,3 +60,10 @@ pub use config_builder::*;
 /// setting up a connection to a Neo4j database and executing queries.
 pub mod neo4j_query_config;
 pub use neo4j_query_config::Neo4jQueryConfig;
+
+/// Module containing the file representation of the configuration.
+///
+/// `ConfigSpec` is read from YAML or JSON and turned into a `Config` once the runtime
+/// channels and event handlers are known.
+pub mod config_spec;
+pub use config_spec::*;
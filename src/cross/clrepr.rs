@@ -117,3 +117,46 @@ impl CLRepr {
 		}
 	}
 }
+
+impl From<serde_json::Value> for CLRepr {
+	fn from(value: serde_json::Value) -> Self {
+		match value {
+			serde_json::Value::Null => CLRepr::Null,
+			serde_json::Value::Bool(value) => CLRepr::Bool(value),
+			serde_json::Value::Number(number) => match number.as_i64() {
+				Some(value) => CLRepr::Int(value),
+				None => CLRepr::Float(number.as_f64().unwrap_or_default()),
+			},
+			serde_json::Value::String(value) => CLRepr::String(value, StringType::Normal),
+			serde_json::Value::Array(values) =>
+				CLRepr::Array(values.into_iter().map(CLRepr::from).collect()),
+			serde_json::Value::Object(map) => {
+				let mut object = CLReprObject::new();
+				for (key, value) in map {
+					object.insert(key, CLRepr::from(value));
+				}
+				CLRepr::Object(object)
+			},
+		}
+	}
+}
+
+impl From<&CLRepr> for serde_json::Value {
+	/// Python references have no JSON representation and become `null`, as do non-finite
+	/// floats.
+	fn from(value: &CLRepr) -> Self {
+		match value {
+			CLRepr::String(value, _) => serde_json::Value::String(value.clone()),
+			CLRepr::Bool(value) => serde_json::Value::Bool(*value),
+			CLRepr::Float(value) => serde_json::Number::from_f64(*value)
+				.map_or(serde_json::Value::Null, serde_json::Value::Number),
+			CLRepr::Int(value) => serde_json::Value::from(*value),
+			CLRepr::Tuple(values) | CLRepr::Array(values) =>
+				serde_json::Value::Array(values.iter().map(serde_json::Value::from).collect()),
+			CLRepr::Object(object) => serde_json::Value::Object(
+				object.iter().map(|(key, value)| (key.clone(), value.into())).collect(),
+			),
+			CLRepr::PythonRef(_) | CLRepr::Null => serde_json::Value::Null,
+		}
+	}
+}
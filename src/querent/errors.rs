@@ -1,4 +1,5 @@
 use log::SetLoggerError;
+use pyo3::{PyErr, Python};
 use std::{
 	any::Any,
 	backtrace::Backtrace,
@@ -20,6 +21,8 @@ pub struct QuerentError {
 pub enum QuerentErrorCauseType {
 	User(Option<HashMap<String, String>>),
 	Internal(Option<HashMap<String, String>>),
+	Cancelled(Option<HashMap<String, String>>),
+	Timeout(Option<HashMap<String, String>>),
 }
 
 impl QuerentError {
@@ -39,6 +42,46 @@ impl QuerentError {
 		}
 	}
 
+	pub fn internal_with_meta(message: String, meta: HashMap<String, String>) -> Self {
+		Self {
+			message,
+			cause: QuerentErrorCauseType::Internal(Some(meta)),
+			backtrace: Some(Backtrace::capture()),
+		}
+	}
+
+	/// Wraps an exception raised by Python, keeping its class and base classes so callers
+	/// can tell failures apart without parsing the message.
+	pub fn python(err: &PyErr) -> Self {
+		let meta = Python::with_gil(|py| {
+			let exception_type = err.get_type(py);
+			let mut meta = HashMap::new();
+			if let Ok(name) = exception_type.name() {
+				meta.insert("python_exception".to_string(), name.to_string());
+			}
+			let mro: Vec<String> = exception_type
+				.getattr("__mro__")
+				.and_then(|mro| mro.iter())
+				.map(|classes| {
+					classes
+						.filter_map(|class| class.ok()?.getattr("__name__").ok()?.extract().ok())
+						.collect()
+				})
+				.unwrap_or_default();
+			meta.insert("python_exception_mro".to_string(), mro.join(","));
+			meta
+		});
+		Self::internal_with_meta(format!("Python error: {}", err), meta)
+	}
+
+	pub fn cancelled(message: String) -> Self {
+		Self { message, cause: QuerentErrorCauseType::Cancelled(None), backtrace: None }
+	}
+
+	pub fn timeout(message: String) -> Self {
+		Self { message, cause: QuerentErrorCauseType::Timeout(None), backtrace: None }
+	}
+
 	pub fn internal_with_bt(message: String, backtrace: Option<Backtrace>) -> Self {
 		Self { message, cause: QuerentErrorCauseType::Internal(None), backtrace }
 	}
@@ -55,6 +98,35 @@ impl QuerentError {
 }
 
 impl QuerentError {
+	pub fn meta(&self) -> Option<&HashMap<String, String>> {
+		match &self.cause {
+			QuerentErrorCauseType::User(meta) |
+			QuerentErrorCauseType::Internal(meta) |
+			QuerentErrorCauseType::Cancelled(meta) |
+			QuerentErrorCauseType::Timeout(meta) => meta.as_ref(),
+		}
+	}
+
+	/// Class name of the Python exception behind this error, if it came from Python.
+	pub fn python_exception_type(&self) -> Option<&str> {
+		self.meta()?.get("python_exception").map(String::as_str)
+	}
+
+	/// Returns true if this error is a Python exception of the given class or a subclass.
+	pub fn is_python_exception(&self, class_name: &str) -> bool {
+		self.meta()
+			.and_then(|meta| meta.get("python_exception_mro"))
+			.map_or(false, |mro| mro.split(',').any(|class| class == class_name))
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		matches!(self.cause, QuerentErrorCauseType::Cancelled(_))
+	}
+
+	pub fn is_timeout(&self) -> bool {
+		matches!(self.cause, QuerentErrorCauseType::Timeout(_))
+	}
+
 	pub fn backtrace(&self) -> Option<&Backtrace> {
 		self.backtrace.as_ref()
 	}
@@ -70,6 +142,10 @@ impl fmt::Display for QuerentError {
 			QuerentErrorCauseType::User(_) => f.write_fmt(format_args!("User: {}", self.message)),
 			QuerentErrorCauseType::Internal(_) =>
 				f.write_fmt(format_args!("Internal: {}", self.message)),
+			QuerentErrorCauseType::Cancelled(_) =>
+				f.write_fmt(format_args!("Cancelled: {}", self.message)),
+			QuerentErrorCauseType::Timeout(_) =>
+				f.write_fmt(format_args!("Timeout: {}", self.message)),
 		}
 	}
 }
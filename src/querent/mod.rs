@@ -12,3 +12,9 @@ pub mod py_runtime;
 pub use py_runtime::*;
 pub mod py_process;
 pub use py_process::*;
+pub mod py_environment;
+pub use py_environment::*;
+pub mod workers;
+pub use workers::*;
+pub mod task_queue;
+pub use task_queue::*;
@@ -0,0 +1,192 @@
+use crate::querent::QuerentError;
+use pyo3::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Import paths and virtualenv made available to the Python code of workflows.
+///
+/// The embedded interpreter is shared by the whole process, so applying an environment
+/// affects every module imported afterwards.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PythonEnvironment {
+	sys_path: Vec<PathBuf>,
+	virtualenv: Option<PathBuf>,
+}
+
+impl PythonEnvironment {
+	/// Creates an environment that leaves the interpreter untouched.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a directory to `sys.path`, ahead of the interpreter's own entries.
+	pub fn sys_path(mut self, path: impl Into<PathBuf>) -> Self {
+		self.sys_path.push(path.into());
+		self
+	}
+
+	/// Adds every directory of a `PYTHONPATH` style list, separated by `:` (`;` on Windows).
+	pub fn python_path(mut self, python_path: &str) -> Self {
+		self.sys_path
+			.extend(std::env::split_paths(python_path).filter(|p| !p.as_os_str().is_empty()));
+		self
+	}
+
+	/// Makes the packages installed in a virtualenv importable.
+	pub fn virtualenv(mut self, path: impl Into<PathBuf>) -> Self {
+		self.virtualenv = Some(path.into());
+		self
+	}
+
+	/// Applies the environment to the embedded interpreter.
+	///
+	/// Fails without changing anything if a directory does not exist or the virtualenv was
+	/// not created for the version of the embedded interpreter.
+	pub fn apply(&self) -> Result<(), QuerentError> {
+		let sys_path = self
+			.sys_path
+			.iter()
+			.map(|path| {
+				path.canonicalize().ok().filter(|path| path.is_dir()).ok_or_else(|| {
+					QuerentError::user(format!(
+						"Python path entry {} is not a directory",
+						path.display()
+					))
+				})
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Python::with_gil(|py| {
+			let version = py.version_info();
+			let site_packages = match &self.virtualenv {
+				Some(virtualenv) =>
+					Some(site_packages(virtualenv, (version.major, version.minor))?),
+				None => None,
+			};
+			apply_paths(py, &sys_path, site_packages.as_deref()).map_err(|e| {
+				QuerentError::internal(format!("Unable to apply Python environment: {}", e))
+			})
+		})
+	}
+}
+
+/// Puts the directories in front of `sys.path`, the virtualenv's site-packages after them.
+fn apply_paths(py: Python<'_>, sys_path: &[PathBuf], site_packages: Option<&Path>) -> PyResult<()> {
+	let path = py.import("sys")?.getattr("path")?;
+	let move_to_front = |entry: &Path| -> PyResult<()> {
+		let entry = entry.to_string_lossy();
+		if path.contains(entry.as_ref())? {
+			path.call_method1("remove", (entry.as_ref(),))?;
+		}
+		path.call_method1("insert", (0, entry.as_ref()))?;
+		Ok(())
+	};
+	if let Some(site_packages) = site_packages {
+		// `addsitedir` also processes the `.pth` files left by editable installs.
+		py.import("site")?
+			.call_method1("addsitedir", (site_packages.to_string_lossy().as_ref(),))?;
+		move_to_front(site_packages)?;
+		log::info!("Using virtualenv site-packages {}", site_packages.display());
+	}
+	// Inserted in reverse so the first entry ends up first.
+	for entry in sys_path.iter().rev() {
+		move_to_front(entry)?;
+	}
+	py.import("importlib")?.call_method0("invalidate_caches")?;
+	Ok(())
+}
+
+/// Finds the site-packages directory of a virtualenv for the given interpreter version.
+fn site_packages(virtualenv: &Path, (major, minor): (u8, u8)) -> Result<PathBuf, QuerentError> {
+	let config = std::fs::read_to_string(virtualenv.join("pyvenv.cfg")).map_err(|e| {
+		QuerentError::user(format!(
+			"{} is not a virtualenv, unable to read pyvenv.cfg: {}",
+			virtualenv.display(),
+			e
+		))
+	})?;
+	let created_for = config.lines().find_map(|line| {
+		let (key, value) = line.split_once('=')?;
+		matches!(key.trim(), "version" | "version_info").then(|| value.trim().to_string())
+	});
+	if let Some(created_for) = created_for {
+		let interpreter = format!("{}.{}", major, minor);
+		if created_for != interpreter && !created_for.starts_with(&format!("{}.", interpreter)) {
+			return Err(QuerentError::user(format!(
+				"Virtualenv {} was created for Python {}, but the embedded interpreter is Python {}",
+				virtualenv.display(),
+				created_for,
+				interpreter
+			)));
+		}
+	}
+	let candidates = [
+		virtualenv
+			.join("lib")
+			.join(format!("python{}.{}", major, minor))
+			.join("site-packages"),
+		virtualenv.join("Lib").join("site-packages"),
+	];
+	candidates.iter().find(|candidate| candidate.is_dir()).cloned().ok_or_else(|| {
+		QuerentError::user(format!(
+			"Virtualenv {} has no site-packages for Python {}.{}, looked in {}",
+			virtualenv.display(),
+			major,
+			minor,
+			candidates
+				.iter()
+				.map(|c| c.display().to_string())
+				.collect::<Vec<_>>()
+				.join(", ")
+		))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn virtualenv(name: &str, version: &str) -> PathBuf {
+		let dir =
+			std::env::temp_dir().join(format!("querent-venv-{}-{}", name, std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(dir.join("lib").join("python3.11").join("site-packages")).unwrap();
+		std::fs::write(dir.join("pyvenv.cfg"), format!("home = /usr/bin\nversion = {}\n", version))
+			.unwrap();
+		dir
+	}
+
+	#[test]
+	fn site_packages_should_match_interpreter_version() {
+		let venv = virtualenv("match", "3.11.4");
+		assert_eq!(
+			site_packages(&venv, (3, 11)).unwrap(),
+			venv.join("lib").join("python3.11").join("site-packages")
+		);
+		let error = site_packages(&venv, (3, 1)).unwrap_err();
+		assert!(error.message.contains("created for Python 3.11.4"), "{}", error);
+		std::fs::remove_dir_all(&venv).unwrap();
+	}
+
+	#[test]
+	fn site_packages_should_explain_missing_environment() {
+		let missing = std::env::temp_dir().join("querent-venv-missing");
+		let error = site_packages(&missing, (3, 11)).unwrap_err();
+		assert!(error.message.contains("is not a virtualenv"), "{}", error);
+
+		let venv = virtualenv("bare", "3.12.0");
+		std::fs::remove_dir_all(venv.join("lib")).unwrap();
+		let error = site_packages(&venv, (3, 12)).unwrap_err();
+		assert!(error.message.contains("has no site-packages for Python 3.12"), "{}", error);
+		std::fs::remove_dir_all(&venv).unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn python_path_should_split_entries() {
+		let environment = PythonEnvironment::new().python_path("/opt/a::/opt/b").sys_path("/opt/c");
+		assert_eq!(
+			environment.sys_path,
+			vec![PathBuf::from("/opt/a"), PathBuf::from("/opt/b"), PathBuf::from("/opt/c")]
+		);
+	}
+}
@@ -2,17 +2,51 @@ use crate::{
 	callbacks::PyEventCallbackInterface,
 	config::{Config, Neo4jQueryConfig},
 	cross::{CLRepr, CLReprPython},
-	querent::errors::QuerentError,
+	querent::{
+		errors::QuerentError, TaskPriority, TaskQueue, WorkerClaim, WorkerLimits, WorkerPermit,
+		PRIORITY_AGING,
+	},
 	tokio_runtime,
 };
+use futures::stream::{self, BoxStream, StreamExt};
 use log::{error, trace};
 use once_cell::sync::OnceCell;
 use pyo3::{
+	exceptions::{asyncio::CancelledError, PyTypeError},
+	ffi,
 	prelude::*,
-	types::{PyFunction, PyTuple},
+	sync::GILOnceCell,
+	types::{PyDict, PyFunction, PyModule, PyTuple},
 };
-use std::{fmt::Formatter, future::Future, pin::Pin};
-use tokio::sync::oneshot;
+use serde::Deserialize;
+use std::{
+	collections::HashMap,
+	fmt::Formatter,
+	future::Future,
+	os::raw::c_long,
+	pin::Pin,
+	sync::{Arc, Mutex},
+};
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+
+/// Number of values an async generator may yield ahead of the consumer of `call_stream`.
+const DEFAULT_STREAM_BUFFER: usize = 16;
+
+/// Coroutines driving async generators for `call_stream`, since `run_coroutine_threadsafe`
+/// only accepts coroutines.
+const STREAM_HELPERS: &str = r#"
+async def step(generator):
+    try:
+        return False, await generator.__anext__()
+    except StopAsyncIteration:
+        return True, None
+
+async def close(generator):
+    await generator.aclose()
+"#;
+
+static STREAM_HELPERS_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
 
 #[derive(Debug)]
 pub struct PyAsyncFun {
@@ -21,16 +55,56 @@ pub struct PyAsyncFun {
 	callback: PyAsyncCallback,
 	config: Option<Config>,
 	query_config: Option<Neo4jQueryConfig>,
+	options: PyCallOptions,
+}
+
+/// How the `Config` or `Neo4jQueryConfig` of a call is passed to the Python function.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigInjection {
+	/// As the first positional argument.
+	#[default]
+	Positional,
+	/// As the keyword argument with the given name.
+	Keyword(String),
+	/// Not at all.
+	Omitted,
+}
+
+/// Options of a Python call besides its positional arguments, controlling how it is made
+/// and scheduled.
+#[derive(Debug, Clone, Default)]
+pub struct PyCallOptions {
+	/// Keyword arguments of the call.
+	pub kwargs: HashMap<String, CLRepr>,
+	/// How the configuration of the call is passed.
+	pub config_injection: ConfigInjection,
+	/// Cancels the asyncio task of the call once triggered. The Python coroutine sees a
+	/// `CancelledError` and the call resolves with a cancelled `QuerentError`. A synchronous
+	/// function gets the `CancelledError` raised the next time it executes Python code.
+	pub cancel: Option<CancellationToken>,
+	/// Number of values an async generator called through `call_stream` may yield ahead of
+	/// the consumer. Defaults to 16.
+	pub stream_buffer: Option<usize>,
+	/// Workers taken for the duration of the call, see `PyRuntime::workers`. The call waits
+	/// while any of their pools is busy. A stream keeps its workers until it ends or is
+	/// dropped.
+	pub workers: WorkerClaim,
+	/// Priority of the call in the queue of calls waiting for the Python thread.
+	pub priority: TaskPriority,
 }
 
 pub enum PyAsyncCallback {
 	Channel(oneshot::Sender<Result<CLRepr, QuerentError>>),
+	/// Receives every value yielded by an async generator.
+	Stream(mpsc::Sender<Result<CLRepr, QuerentError>>),
 }
 
 impl std::fmt::Debug for PyAsyncCallback {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		match self {
 			PyAsyncCallback::Channel(_) => write!(f, "Channel<hidden>"),
+			PyAsyncCallback::Stream(_) => write!(f, "Stream<hidden>"),
 		}
 	}
 }
@@ -38,17 +112,81 @@ impl std::fmt::Debug for PyAsyncCallback {
 impl PyAsyncFun {
 	pub fn split(
 		self,
-	) -> (Py<PyFunction>, Vec<CLRepr>, PyAsyncCallback, Option<Config>, Option<Neo4jQueryConfig>) {
-		(self.fun, self.args, self.callback, self.config, self.query_config)
+	) -> (
+		Py<PyFunction>,
+		Vec<CLRepr>,
+		PyAsyncCallback,
+		Option<Config>,
+		Option<Neo4jQueryConfig>,
+		PyCallOptions,
+	) {
+		(self.fun, self.args, self.callback, self.config, self.query_config, self.options)
+	}
+}
+
+impl PyAsyncCallback {
+	fn send(self, result: Result<CLRepr, QuerentError>) -> Result<(), QuerentError> {
+		match self {
+			PyAsyncCallback::Channel(chan) => chan.send(result).map_err(|_| {
+				QuerentError::internal("Unable to send result back to consumer".to_string())
+			}),
+			// Only used before the generator starts, while the channel is empty.
+			PyAsyncCallback::Stream(chan) => chan.try_send(result).map_err(|_| {
+				QuerentError::internal("Unable to send result back to consumer".to_string())
+			}),
+		}
 	}
 }
 
 enum PyAsyncFunResult {
 	Poll(Pin<Box<dyn Future<Output = PyResult<PyObject>> + Send>>),
+	/// A coroutine scheduled on the event loop through `asyncio.run_coroutine_threadsafe`,
+	/// which can be cancelled through its `concurrent.futures.Future`.
+	Task(PyObject, oneshot::Receiver<PyTaskOutcome>),
+	/// A synchronous function, called on a blocking worker thread with its arguments and the
+	/// event loop that runs the coroutine it may return.
+	Blocking(Py<PyFunction>, Py<PyTuple>, Option<Py<PyDict>>, PyObject),
+}
+
+/// What a synchronous function returned.
+enum PyBlockingOutcome {
+	Value(PyObject),
+	/// The function returned a coroutine, which was scheduled on the event loop.
+	Task(PyObject, oneshot::Receiver<PyTaskOutcome>),
+}
+
+enum PyTaskOutcome {
+	Done(PyResult<PyObject>),
+	Cancelled,
+}
+
+/// Done callback of a scheduled coroutine, forwarding its outcome back to Rust.
+#[pyclass]
+struct PyTaskCompleter {
+	tx: Option<oneshot::Sender<PyTaskOutcome>>,
+}
+
+#[pymethods]
+impl PyTaskCompleter {
+	fn __call__(&mut self, future: &PyAny) -> PyResult<()> {
+		let outcome = if future.call_method0("cancelled")?.extract()? {
+			PyTaskOutcome::Cancelled
+		} else {
+			PyTaskOutcome::Done(future.call_method0("result").map(|r| r.into()))
+		};
+		if let Some(tx) = self.tx.take() {
+			// The consumer may have gone away, which is not an error.
+			let _ = tx.send(outcome);
+		}
+		Ok(())
+	}
 }
 
 pub struct PyRuntime {
-	sender: tokio::sync::mpsc::Sender<PyAsyncFun>,
+	/// Calls waiting for the Python thread, started by priority.
+	queue: Arc<TaskQueue<PyAsyncFun>>,
+	/// Worker pools declared by the `ResourceConfig` of calls and workflow runs.
+	workers: WorkerLimits,
 }
 
 impl PyRuntime {
@@ -59,60 +197,184 @@ impl PyRuntime {
 		config: Option<Config>,
 		query_config: Option<Neo4jQueryConfig>,
 	) -> Result<CLRepr, QuerentError> {
+		self.call_async_with_options(fun, args, config, query_config, PyCallOptions::default())
+			.await
+	}
+
+	pub async fn call_async_with_options(
+		&self,
+		fun: Py<PyFunction>,
+		args: Vec<CLRepr>,
+		config: Option<Config>,
+		query_config: Option<Neo4jQueryConfig>,
+		options: PyCallOptions,
+	) -> Result<CLRepr, QuerentError> {
+		let _workers = self.acquire_workers(&options).await?;
 		let (rx, tx) = oneshot::channel();
 
-		self.sender
-			.send(PyAsyncFun {
-				fun,
-				args,
-				callback: PyAsyncCallback::Channel(rx),
-				config,
-				query_config,
-			})
-			.await
-			.map_err(|err| {
-				QuerentError::internal(format!("Unable to schedule python function call: {}", err))
-			})?;
+		let priority = options.priority;
+		self.queue
+			.push(
+				PyAsyncFun {
+					fun,
+					args,
+					callback: PyAsyncCallback::Channel(rx),
+					config,
+					query_config,
+					options,
+				},
+				priority,
+			)
+			.await;
 
 		tx.await?
 	}
 
+	/// Calls an async generator function and streams the values it yields.
+	///
+	/// The generator is driven through `__anext__` only while the stream has room for the
+	/// next value, see `PyCallOptions::stream_buffer`. Dropping the stream, or triggering
+	/// `PyCallOptions::cancel`, stops the generator and closes it with `aclose()`. An exception
+	/// raised by the generator ends the stream with an error.
+	pub async fn call_stream(
+		&self,
+		fun: Py<PyFunction>,
+		args: Vec<CLRepr>,
+		config: Option<Config>,
+		query_config: Option<Neo4jQueryConfig>,
+		options: PyCallOptions,
+	) -> Result<BoxStream<'static, Result<CLRepr, QuerentError>>, QuerentError> {
+		let workers = self.acquire_workers(&options).await?;
+		let buffer = options.stream_buffer.unwrap_or(DEFAULT_STREAM_BUFFER).max(1);
+		let (tx, rx) = mpsc::channel(buffer);
+
+		let priority = options.priority;
+		self.queue
+			.push(
+				PyAsyncFun {
+					fun,
+					args,
+					callback: PyAsyncCallback::Stream(tx),
+					config,
+					query_config,
+					options,
+				},
+				priority,
+			)
+			.await;
+
+		Ok(stream::unfold((rx, workers), |(mut rx, workers)| async move {
+			rx.recv().await.map(|item| (item, (rx, workers)))
+		})
+		.boxed())
+	}
+
+	/// Number of calls waiting for the Python thread to start them.
+	pub fn queued_calls(&self) -> usize {
+		self.queue.len()
+	}
+
+	/// Worker pools shared by every call and workflow run going through this runtime.
+	pub fn workers(&self) -> &WorkerLimits {
+		&self.workers
+	}
+
+	/// Takes the workers the call claims, unless it is cancelled while waiting for them.
+	async fn acquire_workers(&self, options: &PyCallOptions) -> Result<WorkerPermit, QuerentError> {
+		let cancel = options.cancel.clone().unwrap_or_default();
+		tokio::select! {
+			workers = self.workers.acquire(&options.workers) => Ok(workers),
+			_ = cancel.cancelled() => Err(QuerentError::cancelled(
+				"Python call cancelled while waiting for workers".to_string(),
+			)),
+		}
+	}
+
+	/// Builds the positional and keyword arguments of a call, injecting the configuration
+	/// as `options.config_injection` asks.
+	fn call_args<'py>(
+		py: Python<'py>,
+		args: Vec<CLRepr>,
+		config: Option<Config>,
+		query_config: Option<Neo4jQueryConfig>,
+		options: &mut PyCallOptions,
+	) -> PyResult<(&'py PyTuple, Option<&'py PyDict>)> {
+		let mut args_tuple = Vec::with_capacity(args.len() + 1);
+		let kwargs = PyDict::new(py);
+		for (name, value) in std::mem::take(&mut options.kwargs) {
+			kwargs.set_item(name, value.into_py(py)?)?;
+		}
+
+		let config = match (config, query_config) {
+			(Some(config), _) => Some(config.to_object(py)),
+			(None, Some(query_config)) => Some(query_config.to_object(py)),
+			(None, None) => None,
+		};
+		match (config, &options.config_injection) {
+			(Some(config), ConfigInjection::Positional) => args_tuple.push(config),
+			(Some(config), ConfigInjection::Keyword(name)) => {
+				if kwargs.contains(name)? {
+					return Err(PyTypeError::new_err(format!(
+						"keyword argument {} is reserved for the configuration",
+						name
+					)));
+				}
+				kwargs.set_item(name, config)?;
+			},
+			(_, _) => {},
+		}
+
+		for arg in args {
+			args_tuple.push(arg.into_py(py)?);
+		}
+		let kwargs = if kwargs.is_empty() { None } else { Some(kwargs) };
+		Ok((PyTuple::new(py, args_tuple), kwargs))
+	}
+
 	fn process_coroutines(task: PyAsyncFun) -> Result<(), QuerentError> {
-		let (fun, args, callback, config, query_config) = task.split();
+		let (fun, args, callback, config, query_config, mut options) = task.split();
 
-		let task_result = Python::with_gil(move |py| -> PyResult<PyAsyncFunResult> {
-			let mut args_tuple = Vec::with_capacity(args.len());
+		if options.cancel.as_ref().map_or(false, |token| token.is_cancelled()) {
+			return callback.send(Err(QuerentError::cancelled(
+				"Python call cancelled before start".to_string(),
+			)));
+		}
 
-			// TODO simplify this code
-			if let Some(config) = config {
-				args_tuple.push(config.to_object(py));
-			} else if let Some(query_config) = query_config {
-				args_tuple.push(query_config.to_object(py));
-			}
+		if let PyAsyncCallback::Stream(tx) = callback {
+			return Self::process_stream(fun, args, config, query_config, options, tx);
+		}
 
-			for arg in args {
-				args_tuple.push(arg.into_py(py)?);
+		let call_options = &mut options;
+		let task_result = Python::with_gil(move |py| -> PyResult<PyAsyncFunResult> {
+			let (args, kwargs) = Self::call_args(py, args, config, query_config, call_options)?;
+			let locals = pyo3_asyncio::tokio::get_current_locals(py)?;
+			let is_async: bool = py
+				.import("inspect")?
+				.call_method1("iscoroutinefunction", (fun.as_ref(py),))?
+				.extract()?;
+			if !is_async {
+				// Running it here would block every other call until it returns.
+				return Ok(PyAsyncFunResult::Blocking(
+					fun,
+					args.into(),
+					kwargs.map(Into::into),
+					locals.event_loop(py).into(),
+				));
+			}
+			let call_res = fun.call(py, args, kwargs)?;
+			if let Some((future, rx)) =
+				Self::schedule_coroutine(py, call_res.as_ref(py), locals.event_loop(py))?
+			{
+				Ok(PyAsyncFunResult::Task(future, rx))
+			} else {
+				let fut = pyo3_asyncio::tokio::into_future(call_res.as_ref(py))?;
+				Ok(PyAsyncFunResult::Poll(Box::pin(fut)))
 			}
-			let args = PyTuple::new(py, args_tuple);
-			let call_res = fun.call1(py, args)?;
-			let fut = pyo3_asyncio::tokio::into_future(call_res.as_ref(py))?;
-			Ok(PyAsyncFunResult::Poll(Box::pin(fut)))
 		});
 		let task_result = match task_result {
 			Ok(r) => r,
 			Err(err) => {
-				match callback {
-					PyAsyncCallback::Channel(chan) => {
-						let send_res = chan
-							.send(Err(QuerentError::internal(format!("Python error: {}", err))));
-						if send_res.is_err() {
-							return Err(QuerentError::internal(
-								"Unable to send result back to consumer".to_string(),
-							));
-						}
-					},
-				};
-
+				callback.send(Err(QuerentError::python(&err)))?;
 				return Ok(());
 			},
 		};
@@ -121,27 +383,48 @@ impl PyRuntime {
 			PyAsyncFunResult::Poll(fut) => {
 				tokio::spawn(async move {
 					let fut_res = fut.await;
-
-					let res = Python::with_gil(move |py| -> Result<CLRepr, PyErr> {
-						let res = match fut_res {
-							Ok(r) => CLRepr::from_python_ref(r.as_ref(py)),
-							Err(err) => Err(err),
-						};
-
-						res
+					let _ = callback.send(Self::to_clrepr(fut_res));
+				});
+			},
+			PyAsyncFunResult::Task(future, done) => {
+				tokio::spawn(async move {
+					let res = Self::await_task(future, done, options.cancel).await;
+					let _ = callback.send(res);
+				});
+			},
+			PyAsyncFunResult::Blocking(fun, args, kwargs, event_loop) => {
+				tokio::spawn(async move {
+					let thread = Arc::new(Mutex::new(None));
+					let mut call = tokio::task::spawn_blocking({
+						let thread = thread.clone();
+						move || Self::call_blocking(fun, args, kwargs, event_loop, &thread)
 					});
-
-					match callback {
-						PyAsyncCallback::Channel(chan) => {
-							let _ = match res {
-								Ok(r) => chan.send(Ok(r)),
-								Err(err) => chan.send(Err(QuerentError::internal(format!(
-									"Python error: {}",
-									err
-								)))),
-							};
+					let called = match &options.cancel {
+						Some(token) => tokio::select! {
+							called = &mut call => called,
+							_ = token.cancelled() => {
+								trace!("Interrupting python function");
+								Self::interrupt_blocking(&thread);
+								call.await
+							},
 						},
-					}
+						None => call.await,
+					};
+					let cancelled = options.cancel.as_ref().map_or(false, |t| t.is_cancelled());
+					let res = match called {
+						Ok(Ok(PyBlockingOutcome::Value(value))) => Self::to_clrepr(Ok(value)),
+						Ok(Ok(PyBlockingOutcome::Task(future, done))) =>
+							Self::await_task(future, done, options.cancel).await,
+						Ok(Err(_)) if cancelled => Err(QuerentError::cancelled(
+							"Python function was interrupted".to_string(),
+						)),
+						Ok(Err(err)) => Err(QuerentError::python(&err)),
+						Err(err) => Err(QuerentError::internal(format!(
+							"Python worker thread failed: {}",
+							err
+						))),
+					};
+					let _ = callback.send(res);
 				});
 			},
 		};
@@ -149,12 +432,241 @@ impl PyRuntime {
 		Ok(())
 	}
 
+	/// Schedules a coroutine on the event loop, returning `None` if `value` is not one.
+	fn schedule_coroutine(
+		py: Python<'_>,
+		value: &PyAny,
+		event_loop: &PyAny,
+	) -> PyResult<Option<(PyObject, oneshot::Receiver<PyTaskOutcome>)>> {
+		let asyncio = py.import("asyncio")?;
+		if !asyncio.call_method1("iscoroutine", (value,))?.extract()? {
+			return Ok(None);
+		}
+		let future = asyncio.call_method1("run_coroutine_threadsafe", (value, event_loop))?;
+		let (tx, rx) = oneshot::channel();
+		future.call_method1("add_done_callback", (PyTaskCompleter { tx: Some(tx) },))?;
+		Ok(Some((future.into(), rx)))
+	}
+
+	/// Waits for a scheduled coroutine, cancelling its task when `cancel` is triggered.
+	async fn await_task(
+		future: PyObject,
+		done: oneshot::Receiver<PyTaskOutcome>,
+		cancel: Option<CancellationToken>,
+	) -> Result<CLRepr, QuerentError> {
+		let cancelled = async move {
+			match cancel {
+				Some(token) => token.cancelled().await,
+				None => futures::future::pending().await,
+			}
+		};
+		match Self::await_outcome(future, done, cancelled).await {
+			Some(fut_res) => Self::to_clrepr(fut_res),
+			None => Err(QuerentError::cancelled("Python task was cancelled".to_string())),
+		}
+	}
+
+	/// Waits for a scheduled coroutine, cancelling its task once `cancelled` resolves.
+	/// Returns `None` if the task ended up cancelled.
+	async fn await_outcome(
+		future: PyObject,
+		mut done: oneshot::Receiver<PyTaskOutcome>,
+		cancelled: impl Future<Output = ()>,
+	) -> Option<PyResult<PyObject>> {
+		let outcome = tokio::select! {
+			outcome = &mut done => outcome,
+			_ = cancelled => {
+				trace!("Cancelling python task");
+				let cancel_res = Python::with_gil(|py| future.call_method0(py, "cancel"));
+				if let Err(err) = cancel_res {
+					error!("Unable to cancel python task: {}", err);
+				}
+				done.await
+			},
+		};
+		match outcome {
+			Ok(PyTaskOutcome::Done(fut_res)) => Some(fut_res),
+			Ok(PyTaskOutcome::Cancelled) | Err(_) => None,
+		}
+	}
+
+	/// Calls an async generator function and spawns the task that streams its values.
+	fn process_stream(
+		fun: Py<PyFunction>,
+		args: Vec<CLRepr>,
+		config: Option<Config>,
+		query_config: Option<Neo4jQueryConfig>,
+		mut options: PyCallOptions,
+		tx: mpsc::Sender<Result<CLRepr, QuerentError>>,
+	) -> Result<(), QuerentError> {
+		let started = Python::with_gil(|py| -> PyResult<(PyObject, PyObject)> {
+			let (args, kwargs) = Self::call_args(py, args, config, query_config, &mut options)?;
+			let is_generator: bool = py
+				.import("inspect")?
+				.call_method1("isasyncgenfunction", (fun.as_ref(py),))?
+				.extract()?;
+			if !is_generator {
+				let name: String = fun.as_ref(py).getattr("__qualname__")?.extract()?;
+				return Err(PyTypeError::new_err(format!(
+					"{} is not an async generator function",
+					name
+				)));
+			}
+			let generator = fun.call(py, args, kwargs)?;
+			let locals = pyo3_asyncio::tokio::get_current_locals(py)?;
+			Ok((generator, locals.event_loop(py).into()))
+		});
+		match started {
+			Ok((generator, event_loop)) => {
+				tokio::spawn(Self::drive_stream(generator, event_loop, options.cancel, tx));
+				Ok(())
+			},
+			Err(err) => PyAsyncCallback::Stream(tx).send(Err(QuerentError::python(&err))),
+		}
+	}
+
+	/// Pulls values from an async generator into `tx`, one `__anext__` at a time, until the
+	/// generator is exhausted, fails, is cancelled or the receiver is dropped.
+	async fn drive_stream(
+		generator: PyObject,
+		event_loop: PyObject,
+		cancel: Option<CancellationToken>,
+		tx: mpsc::Sender<Result<CLRepr, QuerentError>>,
+	) {
+		let cancel = cancel.unwrap_or_default();
+		let stopped = || async {
+			tokio::select! {
+				_ = cancel.cancelled() => {},
+				_ = tx.closed() => {},
+			}
+		};
+		loop {
+			let step = Python::with_gil(|py| {
+				let step = Self::stream_helper(py, "step")?.call1((generator.as_ref(py),))?;
+				Self::schedule_coroutine(py, step, event_loop.as_ref(py))
+			});
+			let (future, done) = match step {
+				Ok(Some(scheduled)) => scheduled,
+				Ok(None) => unreachable!("stream steps are coroutines"),
+				Err(err) => {
+					let _ = tx.try_send(Err(QuerentError::python(&err)));
+					break;
+				},
+			};
+			let value = match Self::await_outcome(future, done, stopped()).await {
+				// A generator that raised is finished, there is nothing left to close.
+				Some(Err(err)) => {
+					let _ = tx.send(Err(QuerentError::python(&err))).await;
+					return;
+				},
+				Some(Ok(step)) => Python::with_gil(|py| -> PyResult<Option<CLRepr>> {
+					let (exhausted, value): (bool, &PyAny) = step.extract(py)?;
+					if exhausted {
+						Ok(None)
+					} else {
+						CLRepr::from_python_ref(value).map(Some)
+					}
+				}),
+				None => {
+					let _ = tx.try_send(Err(QuerentError::cancelled(
+						"Python stream was cancelled".to_string(),
+					)));
+					break;
+				},
+			};
+			match value {
+				Ok(Some(value)) => tokio::select! {
+					sent = tx.send(Ok(value)) => if sent.is_err() { break },
+					_ = cancel.cancelled() => break,
+				},
+				Ok(None) => return,
+				Err(err) => {
+					let _ = tx.send(Err(QuerentError::python(&err))).await;
+					break;
+				},
+			}
+		}
+		trace!("Closing python async generator");
+		let close = Python::with_gil(|py| {
+			let close = Self::stream_helper(py, "close")?.call1((generator.as_ref(py),))?;
+			Self::schedule_coroutine(py, close, event_loop.as_ref(py))
+		});
+		match close {
+			Ok(Some((future, done))) => {
+				if let Some(Err(err)) =
+					Self::await_outcome(future, done, futures::future::pending()).await
+				{
+					error!("Unable to close python async generator: {}", err);
+				}
+			},
+			Ok(None) => {},
+			Err(err) => error!("Unable to close python async generator: {}", err),
+		}
+	}
+
+	fn stream_helper<'py>(py: Python<'py>, name: &str) -> PyResult<&'py PyAny> {
+		let module = STREAM_HELPERS_MODULE.get_or_try_init(py, || -> PyResult<_> {
+			Ok(PyModule::from_code(py, STREAM_HELPERS, "querent_stream.py", "querent_stream")?
+				.into())
+		})?;
+		module.as_ref(py).getattr(name)
+	}
+
+	/// Calls a synchronous function on the current thread, holding the GIL only while Python
+	/// runs. The id of the thread is kept in `thread` during the call so it can be interrupted.
+	fn call_blocking(
+		fun: Py<PyFunction>,
+		args: Py<PyTuple>,
+		kwargs: Option<Py<PyDict>>,
+		event_loop: PyObject,
+		thread: &Mutex<Option<c_long>>,
+	) -> PyResult<PyBlockingOutcome> {
+		Python::with_gil(|py| {
+			let ident: u64 = py.import("threading")?.call_method0("get_ident")?.extract()?;
+			let ident = ident as c_long;
+			*thread.lock().unwrap() = Some(ident);
+			let call_res = fun.call(py, args.as_ref(py), kwargs.as_ref().map(|k| k.as_ref(py)));
+			*thread.lock().unwrap() = None;
+			// An interruption that arrived as the call returned must not hit the next call
+			// made on this worker thread.
+			unsafe { ffi::PyThreadState_SetAsyncExc(ident, std::ptr::null_mut()) };
+			let call_res = call_res?;
+			match Self::schedule_coroutine(py, call_res.as_ref(py), event_loop.as_ref(py))? {
+				Some((future, rx)) => Ok(PyBlockingOutcome::Task(future, rx)),
+				None => Ok(PyBlockingOutcome::Value(call_res)),
+			}
+		})
+	}
+
+	/// Raises `CancelledError` in the thread running a synchronous function, the next time it
+	/// executes Python code.
+	fn interrupt_blocking(thread: &Mutex<Option<c_long>>) {
+		Python::with_gil(|py| {
+			// The worker clears the id while holding the GIL, so it is still in the call.
+			if let Some(ident) = *thread.lock().unwrap() {
+				let exception = py.get_type::<CancelledError>();
+				unsafe { ffi::PyThreadState_SetAsyncExc(ident, exception.as_ptr()) };
+			}
+		});
+	}
+
+	fn to_clrepr(fut_res: PyResult<PyObject>) -> Result<CLRepr, QuerentError> {
+		Python::with_gil(move |py| -> Result<CLRepr, PyErr> {
+			match fut_res {
+				Ok(r) => CLRepr::from_python_ref(r.as_ref(py)),
+				Err(err) => Err(err),
+			}
+		})
+		.map_err(|err| QuerentError::python(&err))
+	}
+
 	pub fn new() -> Self {
-		let (sender, mut receiver) = tokio::sync::mpsc::channel::<PyAsyncFun>(1024);
+		let queue = Arc::new(TaskQueue::new(1024, PRIORITY_AGING));
+		let receiver = queue.clone();
 
 		trace!("New Python runtime");
 
-		std::thread::spawn(|| {
+		std::thread::spawn(move || {
 			trace!("Initializing executor in a separate thread");
 
 			std::thread::spawn(|| {
@@ -165,13 +677,12 @@ impl PyRuntime {
 			let res = Python::with_gil(|py| -> Result<(), PyErr> {
 				pyo3_asyncio::tokio::run(py, async move {
 					loop {
-						if let Some(task) = receiver.recv().await {
-							trace!("New task");
+						let task = receiver.pop().await;
+						trace!("New task");
 
-							if let Err(err) = Self::process_coroutines(task) {
-								error!("Error while processing python task: {:?}", err)
-							};
-						}
+						if let Err(err) = Self::process_coroutines(task) {
+							error!("Error while processing python task: {:?}", err)
+						};
 					}
 				})
 			});
@@ -181,7 +692,7 @@ impl PyRuntime {
 			}
 		});
 
-		Self { sender }
+		Self { queue, workers: WorkerLimits::new() }
 	}
 }
 
@@ -1,35 +1,340 @@
-use std::{collections::HashMap, sync::Mutex};
+use std::{
+	collections::HashMap,
+	future::Future,
+	path::Path,
+	sync::{Arc, Mutex},
+	time::SystemTime,
+};
 
-use crate::config::Config;
+use crate::{
+	callbacks::{
+		interface::EventHandler, journal::EVENT_JOURNAL_CAPACITY, EventJournal, EventReceiver,
+		EventRecvError, EventReplayer, EventState, EventSubscriptions, EventType, Subscription,
+	},
+	config::Config,
+	tokio_runtime,
+};
 
-use super::{QuerentError, Workflow, WorkflowManager};
+use tokio::sync::{broadcast, mpsc};
+
+use super::{
+	pipeline::PIPELINE_EVENTS_CAPACITY, Diagnostic, LabelSelector, PipelineChannels, PipelineSpec,
+	PythonEnvironment, QuerentError, ReloadMode, RunFilter, RunJournal, RunRecord, Schedule,
+	ScheduledWorkflow, Scheduler, StartReport, WorkerUsage, Workflow, WorkflowManager,
+	WorkflowReport, WorkflowResult, WorkflowState, WorkflowStatusEvent,
+};
 
 /// Querent provides a high-level interface for working with workflows.
 pub struct Querent {
-	manager: WorkflowManager,
+	manager: Arc<WorkflowManager>,
+	/// Starts the workflows that have a recurring schedule.
+	scheduler: Scheduler<Workflow>,
+	/// Channels wired into the workflows loaded from a file.
+	channels: Mutex<Option<PipelineChannels>>,
+	/// Callbacks registered for the events of the workflows.
+	subscriptions: EventSubscriptions,
 }
 
 impl Querent {
 	/// Creates a new Querent instance.
 	pub fn new() -> Result<Self, String> {
-		let manager = WorkflowManager::new()?;
-		Ok(Self { manager })
+		let manager = Arc::new(WorkflowManager::new()?);
+		let scheduler = Scheduler::new(manager.clone());
+		let subscriptions = EventSubscriptions::new().map_err(|e| e.to_string())?;
+		Ok(Self { manager, scheduler, channels: Mutex::new(None), subscriptions })
+	}
+
+	/// Creates a Querent instance with the workflows declared in a YAML or JSON file.
+	///
+	/// The message channels of every workflow configuration are created here; their Rust ends
+	/// are available through `take_channels`. Events go to the registered callbacks and event
+	/// receivers like those of any other workflow, and to the event channel of
+	/// `take_channels`, which loses its oldest events rather than holding back the workflows
+	/// while nobody receives them. The Python environment of the file, if any, is applied
+	/// before the workflows are added.
+	pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self, QuerentError> {
+		let path = path.as_ref();
+		let pipeline = PipelineSpec::from_file(path)?
+			.build()
+			.map_err(|e| QuerentError::user(format!("{}: {}", path.display(), e.message)))?;
+		let querent = Self::new().map_err(QuerentError::internal)?;
+		if let Some(python) = &pipeline.python {
+			querent.set_python_environment(python)?;
+		}
+		let (event_sender, events) = mpsc::channel(PIPELINE_EVENTS_CAPACITY);
+		spawn(forward_events(querent.subscribe_events(PIPELINE_EVENTS_CAPACITY), event_sender))?;
+		for workflow in pipeline.workflows {
+			let id = workflow.id.clone();
+			querent.add_workflow(workflow).map_err(|e| {
+				QuerentError::user(format!("{}: workflow {}: {}", path.display(), id, e))
+			})?;
+		}
+		*querent.channels.lock().unwrap() = Some(PipelineChannels {
+			events,
+			message_sender: pipeline.message_sender,
+			message_receiver: pipeline.message_receiver,
+			token_sender: pipeline.token_sender,
+			token_receiver: pipeline.token_receiver,
+		});
+		Ok(querent)
+	}
+
+	/// Takes the Rust ends of the channels wired into the workflows loaded from a file.
+	pub fn take_channels(&self) -> Option<PipelineChannels> {
+		self.channels.lock().unwrap().take()
 	}
 
-	/// Adds a workflow to Querent.
-	pub fn add_workflow(&self, workflow: Workflow) -> Result<(), String> {
+	/// Adds a workflow to Querent. A workflow with a schedule starts recurring right away.
+	///
+	/// Unless its configuration already sends events somewhere, the events of the workflow
+	/// go to the callbacks registered with `register_callback`.
+	pub fn add_workflow(&self, mut workflow: Workflow) -> Result<(), String> {
+		self.connect_events(&mut workflow);
+		let schedule = workflow.schedule.clone();
+		let workflow_id = workflow.id.clone();
 		self.manager.add_workflow(workflow)?;
+		if let Some(schedule) = schedule {
+			self.scheduler.schedule(&workflow_id, schedule).map_err(|e| e.to_string())?;
+		}
 		Ok(())
 	}
 
-	/// Starts all workflows asynchronously.
-	pub async fn start_workflows(&self) -> Result<(), QuerentError> {
-		self.manager.start_workflows().await?;
+	/// Starts all workflows concurrently and reports the outcome of each one.
+	pub async fn start_workflows(&self) -> Result<StartReport, QuerentError> {
+		self.manager.start_workflows().await
+	}
+
+	/// Starts the workflows whose labels match the selector, such as `env in (prod, staging)`,
+	/// and reports the outcome of each one.
+	pub async fn start_workflows_matching(
+		&self,
+		selector: &LabelSelector,
+	) -> Result<StartReport, QuerentError> {
+		self.manager.start_workflows_matching(selector).await
+	}
+
+	/// Runs a single workflow now and reports its outcome.
+	pub async fn start_workflow(&self, workflow_id: &str) -> Result<WorkflowReport, QuerentError> {
+		self.manager.start_workflow(workflow_id).await
+	}
+
+	/// Runs a registered workflow on a recurring schedule, replacing its previous schedule.
+	pub fn schedule_workflow(
+		&self,
+		workflow_id: &str,
+		schedule: Schedule,
+	) -> Result<(), QuerentError> {
+		self.scheduler.schedule(workflow_id, schedule)
+	}
+
+	/// Stops the recurring schedule of a workflow. Returns false if it was not scheduled.
+	pub fn unschedule_workflow(&self, workflow_id: &str) -> bool {
+		self.scheduler.unschedule(workflow_id)
+	}
+
+	/// Returns when the given scheduled workflow runs next.
+	pub fn next_run(&self, workflow_id: &str) -> Option<SystemTime> {
+		self.scheduler.next_run(workflow_id)
+	}
+
+	/// Returns every scheduled workflow with its next run time.
+	pub fn list_schedules(&self) -> Vec<ScheduledWorkflow> {
+		self.scheduler.list()
+	}
+
+	/// Replaces the definition of a registered workflow. A workflow with a schedule is
+	/// rescheduled.
+	pub fn update_workflow(&self, mut workflow: Workflow) -> Result<(), String> {
+		self.connect_events(&mut workflow);
+		let schedule = workflow.schedule.clone();
+		let workflow_id = workflow.id.clone();
+		self.manager.update_workflow(workflow)?;
+		if let Some(schedule) = schedule {
+			self.scheduler.schedule(&workflow_id, schedule).map_err(|e| e.to_string())?;
+		}
 		Ok(())
 	}
 
+	/// Sets the import paths and virtualenv the modules of the workflows are loaded with,
+	/// applied again before every module is loaded.
+	pub fn set_python_environment(
+		&self,
+		environment: &PythonEnvironment,
+	) -> Result<(), QuerentError> {
+		self.manager.set_python_environment(environment)
+	}
+
+	/// Sets when the Python modules of the workflows are loaded again between runs.
+	pub fn set_reload_mode(&self, mode: ReloadMode) {
+		self.manager.set_reload_mode(mode);
+	}
+
+	/// Reloads the Python module of a workflow, or compiles its code again, right away.
+	pub fn reload_workflow(&self, workflow_id: &str) -> Result<(), QuerentError> {
+		self.manager.reload(workflow_id)
+	}
+
+	/// Get the workflows whose labels match the selector, ordered by id.
+	pub fn get_workflows_matching(&self, selector: &LabelSelector) -> Vec<Workflow> {
+		self.manager.workflows_matching(selector)
+	}
+
+	/// Checks every workflow without running it and returns the problems found.
+	pub fn validate_workflows(&self) -> Vec<Diagnostic> {
+		self.manager.validate()
+	}
+
+	/// Returns how busy the worker pools declared by the `ResourceConfig` of the workflows are.
+	pub fn worker_usage(&self) -> Vec<WorkerUsage> {
+		self.manager.worker_usage()
+	}
+
 	/// Get all the workflows
 	pub fn get_workflows(&self) -> Vec<Workflow> {
 		self.manager.get_workflows()
 	}
+
+	/// Stops a running workflow by cancelling its Python task.
+	pub fn stop_workflow(&self, workflow_id: &str) -> Result<(), QuerentError> {
+		self.manager.cancel(workflow_id)
+	}
+
+	/// Stops the running workflows whose labels match the selector and returns their ids.
+	pub fn stop_workflows_matching(&self, selector: &LabelSelector) -> Vec<String> {
+		self.manager.cancel_matching(selector)
+	}
+
+	/// Returns the result of the latest finished run of the given workflow.
+	pub fn workflow_result(&self, workflow_id: &str) -> Option<WorkflowResult> {
+		self.manager.result(workflow_id)
+	}
+
+	/// Waits for the result of the given workflow and returns it. Fails if the workflow is
+	/// not running and has no stored result.
+	pub async fn await_workflow_result(
+		&self,
+		workflow_id: &str,
+	) -> Result<WorkflowResult, QuerentError> {
+		self.manager.await_result(workflow_id).await
+	}
+
+	/// Returns the lifecycle state of the given workflow.
+	pub fn workflow_status(&self, workflow_id: &str) -> Option<WorkflowState> {
+		self.manager.status(workflow_id)
+	}
+
+	/// Returns the lifecycle state of every workflow.
+	pub fn list_workflow_status(&self) -> Vec<WorkflowState> {
+		self.manager.list_status()
+	}
+
+	/// Subscribes to the status transitions of all workflows.
+	pub fn subscribe_status(&self) -> broadcast::Receiver<WorkflowStatusEvent> {
+		self.manager.subscribe_status()
+	}
+
+	/// Calls `callback` with every event of the given type sent by the workflows. Callbacks
+	/// run one after the other on a tokio task, so they should return quickly; use
+	/// `register_async_callback` for slow work.
+	pub fn register_callback<F>(&self, event_type: EventType, callback: F) -> Subscription
+	where
+		F: Fn(&EventState) + Send + Sync + 'static,
+	{
+		self.subscriptions.subscribe(event_type, callback)
+	}
+
+	/// Runs the future returned by `callback` on the tokio runtime for every event of the
+	/// given type sent by the workflows.
+	pub fn register_async_callback<F, Fut>(
+		&self,
+		event_type: EventType,
+		callback: F,
+	) -> Subscription
+	where
+		F: Fn(EventState) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = ()> + Send + 'static,
+	{
+		self.subscriptions.subscribe_async(event_type, callback)
+	}
+
+	/// Adds a receiver of every event sent by the workflows, buffering up to `capacity` events
+	/// of its own. The receiver lags behind, losing its oldest events, rather than holding
+	/// back the workflows; dropping it unsubscribes it.
+	pub fn subscribe_events(&self, capacity: usize) -> EventReceiver {
+		self.subscriptions.event_bus().subscribe(capacity)
+	}
+
+	/// Event handler delivering to the registered callbacks, for configurations built by hand.
+	pub fn event_handler(&self) -> EventHandler {
+		self.subscriptions.event_handler()
+	}
+
+	/// Sends the events of a workflow to the registered callbacks, unless its configuration
+	/// has a connected event handler.
+	fn connect_events(&self, workflow: &mut Workflow) {
+		if let Some(config) = workflow.config.as_mut() {
+			let handler = &mut config.workflow.inner_event_handler;
+			if !handler.as_ref().map_or(false, EventHandler::is_connected) {
+				*handler = Some(self.subscriptions.event_handler());
+			}
+		}
+	}
+
+	/// Records every workflow run from now on in a JSON lines file, appending to it if it
+	/// already exists.
+	pub fn enable_run_journal(&self, path: impl AsRef<Path>) -> Result<(), QuerentError> {
+		self.manager.set_journal(Arc::new(RunJournal::open(path)?));
+		Ok(())
+	}
+
+	/// Records every event sent by the workflows from now on in an Avro file, appending to it
+	/// if it already exists.
+	pub fn record_events(&self, path: impl AsRef<Path>) -> Result<(), QuerentError> {
+		let journal = EventJournal::open(path)?;
+		let receiver = self.subscribe_events(EVENT_JOURNAL_CAPACITY);
+		spawn(async move { journal.record(receiver).await })
+	}
+
+	/// Replays recorded events to the registered callbacks and event receivers, returning how
+	/// many were replayed.
+	pub async fn replay_events(&self, replayer: &EventReplayer) -> Result<usize, QuerentError> {
+		replayer.replay(&mut self.event_handler()).await
+	}
+
+	/// Returns the recorded workflow runs selected by the filter, in the order they started.
+	pub fn run_history(&self, filter: &RunFilter) -> Result<Vec<RunRecord>, QuerentError> {
+		self.manager.run_history(filter)
+	}
+}
+
+/// Runs a future in the background, on the current Tokio runtime if there is one.
+fn spawn<F>(future: F) -> Result<(), QuerentError>
+where
+	F: Future + Send + 'static,
+	F::Output: Send + 'static,
+{
+	match tokio::runtime::Handle::try_current() {
+		Ok(handle) => handle.spawn(future),
+		Err(_) => tokio_runtime()?.spawn(future),
+	};
+	Ok(())
+}
+
+/// Passes the events of the bus on to the event channel of a loaded pipeline, until either
+/// end is closed.
+async fn forward_events(
+	mut receiver: EventReceiver,
+	sender: mpsc::Sender<(EventType, EventState)>,
+) {
+	loop {
+		match receiver.recv().await {
+			Ok(event) =>
+				if sender.send(event).await.is_err() {
+					break;
+				},
+			Err(EventRecvError::Lagged(count)) =>
+				log::warn!("Pipeline event channel lagged behind by {} events", count),
+			Err(EventRecvError::Closed) => break,
+		}
+	}
 }
@@ -0,0 +1,181 @@
+use serde::Deserialize;
+use std::{
+	cmp::Reverse,
+	collections::BinaryHeap,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+use tokio::sync::{Notify, Semaphore};
+
+/// How urgently a Python call is started relative to the other queued calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskPriority {
+	/// Bulk work, such as batch ingestion, that may wait for everything else.
+	Low,
+	/// The default priority of workflow runs.
+	#[default]
+	Normal,
+	/// Latency-sensitive work, such as interactive queries.
+	High,
+}
+
+impl TaskPriority {
+	/// Number of priority levels below this one.
+	fn rank(self) -> u32 {
+		match self {
+			TaskPriority::Low => 0,
+			TaskPriority::Normal => 1,
+			TaskPriority::High => 2,
+		}
+	}
+}
+
+/// How long a queued call waits before it takes precedence over calls one priority level above
+/// it that were queued after it.
+pub const PRIORITY_AGING: Duration = Duration::from_secs(1);
+
+struct Entry<T> {
+	/// Time the entry is due at: when it was queued, pushed back by every priority level
+	/// above its own.
+	due: Duration,
+	/// Tells entries due at the same time apart, in the order they were queued.
+	seq: u64,
+	item: T,
+}
+
+impl<T> PartialEq for Entry<T> {
+	fn eq(&self, other: &Self) -> bool {
+		(self.due, self.seq) == (other.due, other.seq)
+	}
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> PartialOrd for Entry<T> {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl<T> Ord for Entry<T> {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		(self.due, self.seq).cmp(&(other.due, other.seq))
+	}
+}
+
+struct QueueState<T> {
+	heap: BinaryHeap<Reverse<Entry<T>>>,
+	next_seq: u64,
+}
+
+/// Bounded queue handing items to a single consumer by priority.
+///
+/// Every priority level below `High` pushes an item back by `aging`, so an item is overtaken
+/// by items of a higher priority only while they were queued less than `aging` per level
+/// after it. Low priority items are therefore delayed, but never starved.
+pub(crate) struct TaskQueue<T> {
+	state: Mutex<QueueState<T>>,
+	epoch: Instant,
+	aging: Duration,
+	/// Free slots, producers wait for one when the queue is full.
+	room: Semaphore,
+	/// Wakes the consumer up when an item is queued.
+	available: Notify,
+}
+
+impl<T> TaskQueue<T> {
+	pub(crate) fn new(capacity: usize, aging: Duration) -> Self {
+		Self {
+			state: Mutex::new(QueueState { heap: BinaryHeap::new(), next_seq: 0 }),
+			epoch: Instant::now(),
+			aging,
+			room: Semaphore::new(capacity),
+			available: Notify::new(),
+		}
+	}
+
+	/// Queues an item, waiting while the queue is full.
+	pub(crate) async fn push(&self, item: T, priority: TaskPriority) {
+		// The semaphore is never closed.
+		self.room.acquire().await.expect("task queue closed").forget();
+		let due = self.epoch.elapsed() + self.aging * (TaskPriority::High.rank() - priority.rank());
+		{
+			let mut state = self.state.lock().unwrap();
+			let seq = state.next_seq;
+			state.next_seq += 1;
+			state.heap.push(Reverse(Entry { due, seq, item }));
+		}
+		self.available.notify_one();
+	}
+
+	/// Takes the item due first, waiting until there is one.
+	pub(crate) async fn pop(&self) -> T {
+		loop {
+			if let Some(item) = self.try_pop() {
+				return item;
+			}
+			// `notify_one` stores a wakeup when the consumer is not waiting yet, so an item
+			// queued since the check above is not missed.
+			self.available.notified().await;
+		}
+	}
+
+	fn try_pop(&self) -> Option<T> {
+		let Reverse(entry) = self.state.lock().unwrap().heap.pop()?;
+		self.room.add_permits(1);
+		Some(entry.item)
+	}
+
+	/// Number of queued items.
+	pub(crate) fn len(&self) -> usize {
+		self.state.lock().unwrap().heap.len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn task_queue_should_order_by_priority_then_arrival() {
+		let queue = TaskQueue::new(8, Duration::from_secs(60));
+		queue.push("low", TaskPriority::Low).await;
+		queue.push("normal-1", TaskPriority::Normal).await;
+		queue.push("high", TaskPriority::High).await;
+		queue.push("normal-2", TaskPriority::Normal).await;
+		assert_eq!(queue.len(), 4);
+
+		let mut order = Vec::new();
+		for _ in 0..4 {
+			order.push(queue.pop().await);
+		}
+		assert_eq!(order, vec!["high", "normal-1", "normal-2", "low"]);
+	}
+
+	#[tokio::test]
+	async fn task_queue_should_age_waiting_items() {
+		let queue = TaskQueue::new(8, Duration::from_millis(20));
+		queue.push("low", TaskPriority::Low).await;
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		// Queued more than two aging steps later, so even a high priority item comes after.
+		queue.push("high", TaskPriority::High).await;
+		assert_eq!(queue.pop().await, "low");
+		assert_eq!(queue.pop().await, "high");
+	}
+
+	#[tokio::test]
+	async fn task_queue_should_wait_for_room() {
+		let queue = std::sync::Arc::new(TaskQueue::new(1, PRIORITY_AGING));
+		queue.push(1, TaskPriority::Normal).await;
+		let producer = {
+			let queue = queue.clone();
+			tokio::spawn(async move { queue.push(2, TaskPriority::High).await })
+		};
+		tokio::time::sleep(Duration::from_millis(20)).await;
+		assert!(!producer.is_finished());
+		assert_eq!(queue.pop().await, 1);
+		producer.await.unwrap();
+		assert_eq!(queue.pop().await, 2);
+	}
+}
@@ -0,0 +1,292 @@
+use crate::config::Config;
+use std::{
+	collections::HashMap,
+	fmt,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, Mutex,
+	},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Shared limit on the number of workers, as declared by a `ResourceConfig`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum WorkerPool {
+	/// `max_workers_allowed`, shared by every configuration naming the same resource id.
+	Resource(String),
+	/// `max_workers_per_querent`, shared by the configurations of a querent.
+	Querent(String),
+	/// `max_workers_per_collector`, for every collector a configuration lists.
+	Collector(String),
+	/// `max_workers_per_engine`, for every engine a configuration lists.
+	Engine(String),
+}
+
+impl fmt::Display for WorkerPool {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			WorkerPool::Resource(id) => write!(f, "resource:{}", id),
+			WorkerPool::Querent(id) => write!(f, "querent:{}", id),
+			WorkerPool::Collector(id) => write!(f, "collector:{}", id),
+			WorkerPool::Engine(id) => write!(f, "engine:{}", id),
+		}
+	}
+}
+
+/// Worker pools a run takes one worker from, with the size each one is declared with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkerClaim {
+	/// Sorted by pool, so that claims always take their workers in the same order.
+	pools: Vec<(WorkerPool, usize)>,
+}
+
+impl WorkerClaim {
+	/// Claim of a run with the given configuration. A configuration without a
+	/// `ResourceConfig` claims nothing.
+	pub fn from_config(config: &Config) -> Self {
+		let Some(resource) = &config.resource else {
+			return Self::default();
+		};
+		let mut claim = Self::default();
+		if let Some(limit) = resource.max_workers_allowed {
+			claim.push(WorkerPool::Resource(resource.id.clone()), limit);
+		}
+		if let Some(limit) = resource.max_workers_per_querent {
+			claim.push(WorkerPool::Querent(config.querent_id.clone()), limit);
+		}
+		if let Some(limit) = resource.max_workers_per_collector {
+			for collector in &config.collectors {
+				claim.push(WorkerPool::Collector(collector.id.clone()), limit);
+			}
+		}
+		if let Some(limit) = resource.max_workers_per_engine {
+			for engine in &config.engines {
+				claim.push(WorkerPool::Engine(engine.id.clone()), limit);
+			}
+		}
+		claim
+	}
+
+	/// Adds a pool to the claim. A limit of zero would never let the run start, so it is
+	/// ignored.
+	pub fn push(&mut self, pool: WorkerPool, limit: u32) {
+		if limit == 0 {
+			log::warn!("Ignoring worker limit of 0 for {}", pool);
+			return;
+		}
+		match self.pools.binary_search_by(|(existing, _)| existing.cmp(&pool)) {
+			Ok(index) => self.pools[index].1 = self.pools[index].1.min(limit as usize),
+			Err(index) => self.pools.insert(index, (pool, limit as usize)),
+		}
+	}
+
+	/// Returns true if the claim takes no worker at all.
+	pub fn is_empty(&self) -> bool {
+		self.pools.is_empty()
+	}
+
+	/// Pools of the claim, in the order workers are taken from them.
+	pub fn pools(&self) -> impl Iterator<Item = &WorkerPool> {
+		self.pools.iter().map(|(pool, _)| pool)
+	}
+}
+
+/// Current utilisation of a worker pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerUsage {
+	/// Pool the figures are for.
+	pub pool: WorkerPool,
+	/// Number of workers the pool has.
+	pub limit: usize,
+	/// Number of workers taken by runs in progress.
+	pub active: usize,
+	/// Number of runs waiting for a worker of this pool.
+	pub queued: usize,
+}
+
+struct Pool {
+	semaphore: Arc<Semaphore>,
+	size: usize,
+	active: AtomicUsize,
+	queued: AtomicUsize,
+}
+
+impl Pool {
+	fn new(size: usize) -> Self {
+		Self {
+			semaphore: Arc::new(Semaphore::new(size)),
+			size,
+			active: AtomicUsize::new(0),
+			queued: AtomicUsize::new(0),
+		}
+	}
+
+	fn usage(&self, pool: &WorkerPool) -> WorkerUsage {
+		WorkerUsage {
+			pool: pool.clone(),
+			limit: self.size,
+			active: self.active.load(Ordering::SeqCst),
+			queued: self.queued.load(Ordering::SeqCst),
+		}
+	}
+}
+
+/// Worker taken from a single pool, given back when dropped.
+struct PoolPermit {
+	pool: Arc<Pool>,
+	_permit: OwnedSemaphorePermit,
+}
+
+impl Drop for PoolPermit {
+	fn drop(&mut self) {
+		self.pool.active.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+/// Workers taken by a run, given back to their pools when dropped.
+#[must_use = "workers are given back as soon as the permit is dropped"]
+pub struct WorkerPermit {
+	_permits: Vec<PoolPermit>,
+}
+
+/// Counts a run as queued on a pool while it waits for a worker.
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl Drop for QueuedGuard<'_> {
+	fn drop(&mut self) {
+		self.0.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+/// Worker pools of every `ResourceConfig` seen so far, keyed by pool.
+///
+/// A pool keeps the size of the first claim naming it, so that a claim declaring another size
+/// cannot change the limit other runs rely on; such a conflict is logged and ignored.
+#[derive(Default)]
+pub struct WorkerLimits {
+	pools: Mutex<HashMap<WorkerPool, Arc<Pool>>>,
+}
+
+impl WorkerLimits {
+	/// Creates an empty set of pools.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn pools(&self, claim: &WorkerClaim) -> Vec<Arc<Pool>> {
+		let mut pools = self.pools.lock().unwrap();
+		claim
+			.pools
+			.iter()
+			.map(|(key, size)| {
+				let pool = pools.entry(key.clone()).or_insert_with(|| Arc::new(Pool::new(*size)));
+				if pool.size != *size {
+					log::warn!(
+						"Worker pool {} already has {} workers, ignoring a limit of {}",
+						key,
+						pool.size,
+						size
+					);
+				}
+				pool.clone()
+			})
+			.collect()
+	}
+
+	/// Takes a worker from every pool of the claim, waiting for busy pools. Workers are
+	/// taken in a fixed order, so that claims sharing pools cannot deadlock.
+	pub async fn acquire(&self, claim: &WorkerClaim) -> WorkerPermit {
+		let mut permits = Vec::with_capacity(claim.pools.len());
+		for pool in self.pools(claim) {
+			let permit = match pool.semaphore.clone().try_acquire_owned() {
+				Ok(permit) => permit,
+				Err(_) => {
+					pool.queued.fetch_add(1, Ordering::SeqCst);
+					let _queued = QueuedGuard(&pool.queued);
+					// The semaphore is never closed.
+					pool.semaphore.clone().acquire_owned().await.expect("worker pool closed")
+				},
+			};
+			pool.active.fetch_add(1, Ordering::SeqCst);
+			permits.push(PoolPermit { pool, _permit: permit });
+		}
+		WorkerPermit { _permits: permits }
+	}
+
+	/// Takes a worker from every pool of the claim if none of them is busy.
+	pub fn try_acquire(&self, claim: &WorkerClaim) -> Option<WorkerPermit> {
+		let mut permits = Vec::with_capacity(claim.pools.len());
+		for pool in self.pools(claim) {
+			let permit = pool.semaphore.clone().try_acquire_owned().ok()?;
+			pool.active.fetch_add(1, Ordering::SeqCst);
+			permits.push(PoolPermit { pool, _permit: permit });
+		}
+		Some(WorkerPermit { _permits: permits })
+	}
+
+	/// Returns the utilisation of every pool, ordered by pool.
+	pub fn usage(&self) -> Vec<WorkerUsage> {
+		let mut usage: Vec<WorkerUsage> =
+			self.pools.lock().unwrap().iter().map(|(key, pool)| pool.usage(key)).collect();
+		usage.sort_by(|a, b| a.pool.cmp(&b.pool));
+		usage
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	fn claim(pools: &[(WorkerPool, u32)]) -> WorkerClaim {
+		let mut claim = WorkerClaim::default();
+		for (pool, limit) in pools {
+			claim.push(pool.clone(), *limit);
+		}
+		claim
+	}
+
+	#[tokio::test]
+	async fn worker_limits_should_queue_runs_beyond_the_limit() {
+		let limits = WorkerLimits::new();
+		let querent = WorkerPool::Querent("q".to_string());
+		let claim = claim(&[(querent.clone(), 2), (WorkerPool::Engine("e".to_string()), 0)]);
+		assert_eq!(claim.pools().collect::<Vec<_>>(), vec![&querent]);
+
+		let first = limits.acquire(&claim).await;
+		let _second = limits.acquire(&claim).await;
+		assert!(limits.try_acquire(&claim).is_none());
+
+		let waiting = limits.acquire(&claim);
+		tokio::pin!(waiting);
+		assert!(tokio::time::timeout(Duration::from_millis(20), &mut waiting).await.is_err());
+		assert_eq!(
+			limits.usage(),
+			vec![WorkerUsage { pool: querent.clone(), limit: 2, active: 2, queued: 1 }]
+		);
+
+		drop(first);
+		let _third = waiting.await;
+		assert_eq!(
+			limits.usage(),
+			vec![WorkerUsage { pool: querent, limit: 2, active: 2, queued: 0 }]
+		);
+	}
+
+	#[tokio::test]
+	async fn worker_limits_should_keep_the_size_pools_were_created_with() {
+		let limits = WorkerLimits::new();
+		let pool = WorkerPool::Resource("r".to_string());
+		let narrow = claim(&[(pool.clone(), 1)]);
+		let wide = claim(&[(pool.clone(), 3)]);
+
+		let first = limits.acquire(&narrow).await;
+		// A larger limit declared later does not let more runs in.
+		assert!(limits.try_acquire(&wide).is_none());
+		drop(first);
+		let only = limits.try_acquire(&wide).expect("Expected a free worker");
+		assert!(limits.try_acquire(&narrow).is_none());
+		drop(only);
+		assert_eq!(limits.usage(), vec![WorkerUsage { pool, limit: 1, active: 0, queued: 0 }]);
+	}
+}
@@ -0,0 +1,180 @@
+use crate::querent::QuerentError;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use super::Runnable;
+
+/// Dependency of a workflow on another one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkflowDependency {
+	/// Unique identifier of the upstream workflow.
+	pub workflow_id: String,
+	/// Whether the value returned by the upstream workflow is appended to the arguments of
+	/// the downstream one.
+	pub pass_result: bool,
+}
+
+impl WorkflowDependency {
+	/// Waits for the upstream workflow without receiving its result.
+	pub fn after(workflow_id: &str) -> Self {
+		WorkflowDependency { workflow_id: workflow_id.to_string(), pass_result: false }
+	}
+
+	/// Waits for the upstream workflow and receives its result as an argument.
+	pub fn with_result(workflow_id: &str) -> Self {
+		WorkflowDependency { workflow_id: workflow_id.to_string(), pass_result: true }
+	}
+}
+
+/// Maps every workflow id to the ids of the workflows it depends on.
+fn dependency_graph<'a, R: Runnable>(
+	workflows: impl IntoIterator<Item = &'a R>,
+) -> BTreeMap<&'a str, Vec<&'a str>> {
+	workflows
+		.into_iter()
+		.map(|workflow| {
+			let upstream = workflow.dependencies().iter().map(|d| d.workflow_id.as_str()).collect();
+			(workflow.id(), upstream)
+		})
+		.collect()
+}
+
+/// Returns the workflows of a dependency cycle, if there is one. The first workflow is
+/// repeated at the end, e.g. `[a, b, a]`.
+pub fn find_cycle<'a, R: Runnable>(
+	workflows: impl IntoIterator<Item = &'a R>,
+) -> Option<Vec<String>> {
+	#[derive(Clone, Copy, PartialEq)]
+	enum Mark {
+		Visiting,
+		Done,
+	}
+
+	fn visit<'a>(
+		node: &'a str,
+		graph: &BTreeMap<&'a str, Vec<&'a str>>,
+		marks: &mut HashMap<&'a str, Mark>,
+		path: &mut Vec<&'a str>,
+	) -> Option<Vec<String>> {
+		match marks.get(node) {
+			Some(Mark::Done) => return None,
+			Some(Mark::Visiting) => {
+				let start = path.iter().position(|n| *n == node).unwrap_or_default();
+				let mut cycle: Vec<String> = path[start..].iter().map(|n| n.to_string()).collect();
+				cycle.push(node.to_string());
+				return Some(cycle);
+			},
+			None => {},
+		}
+		marks.insert(node, Mark::Visiting);
+		path.push(node);
+		for upstream in graph.get(node).into_iter().flatten() {
+			// Unknown workflows cannot be part of a cycle.
+			if graph.contains_key(upstream) {
+				if let Some(cycle) = visit(upstream, graph, marks, path) {
+					return Some(cycle);
+				}
+			}
+		}
+		path.pop();
+		marks.insert(node, Mark::Done);
+		None
+	}
+
+	let graph = dependency_graph(workflows);
+	let mut marks = HashMap::new();
+	for node in graph.keys() {
+		if let Some(cycle) = visit(node, &graph, &mut marks, &mut Vec::new()) {
+			return Some(cycle);
+		}
+	}
+	None
+}
+
+/// Orders workflows so that every workflow comes after the workflows it depends on.
+///
+/// Dependencies on workflows that are not part of `workflows` are ignored here; they are
+/// reported when the dependent workflow is started.
+pub fn topological_order<'a, R: Runnable>(
+	workflows: impl IntoIterator<Item = &'a R>,
+) -> Result<Vec<String>, QuerentError> {
+	let workflows: Vec<&R> = workflows.into_iter().collect();
+	let graph = dependency_graph(workflows.iter().copied());
+	let mut pending: BTreeMap<&str, usize> = BTreeMap::new();
+	let mut downstream: HashMap<&str, Vec<&str>> = HashMap::new();
+	for (node, upstream) in graph.iter() {
+		let known: Vec<_> = upstream.iter().filter(|u| graph.contains_key(*u)).collect();
+		pending.insert(node, known.len());
+		for upstream in known {
+			downstream.entry(upstream).or_default().push(node);
+		}
+	}
+
+	let mut ready: VecDeque<&str> = pending
+		.iter()
+		.filter(|(_, count)| **count == 0)
+		.map(|(node, _)| *node)
+		.collect();
+	let mut order = Vec::with_capacity(graph.len());
+	while let Some(node) = ready.pop_front() {
+		order.push(node.to_string());
+		for next in downstream.get(node).into_iter().flatten() {
+			let count = pending.get_mut(next).expect("every node has a pending count");
+			*count -= 1;
+			if *count == 0 {
+				ready.push_back(next);
+			}
+		}
+	}
+
+	if order.len() != graph.len() {
+		let cycle = find_cycle(workflows.iter().copied());
+		return Err(QuerentError::user(format!(
+			"Workflows contain a dependency cycle{}",
+			cycle.map(|c| format!(": {}", c.join(" -> "))).unwrap_or_default()
+		)));
+	}
+	Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::querent::{Workflow, WorkflowBuilder};
+
+	fn workflow(id: &str, dependencies: &[&str]) -> Workflow {
+		dependencies
+			.iter()
+			.fold(WorkflowBuilder::new(id), |builder, d| builder.depends_on(d))
+			.build()
+	}
+
+	#[test]
+	fn topological_order_should_put_dependencies_first() {
+		let workflows = vec![
+			workflow("index", &["extract"]),
+			workflow("extract", &["collect"]),
+			workflow("collect", &[]),
+			workflow("report", &["index", "extract", "unknown"]),
+		];
+		let order = topological_order(&workflows).unwrap();
+		let position = |id: &str| order.iter().position(|o| o == id).unwrap();
+		assert_eq!(order.len(), 4);
+		assert!(position("collect") < position("extract"));
+		assert!(position("extract") < position("index"));
+		assert!(position("index") < position("report"));
+	}
+
+	#[test]
+	fn find_cycle_should_report_the_cycle() {
+		let acyclic = vec![workflow("a", &["b"]), workflow("b", &[])];
+		assert_eq!(find_cycle(&acyclic), None);
+
+		let cyclic = vec![workflow("a", &["b"]), workflow("b", &["c"]), workflow("c", &["a"])];
+		let cycle = find_cycle(&cyclic).unwrap();
+		assert_eq!(cycle.len(), 4);
+		assert_eq!(cycle.first(), cycle.last());
+		assert!(topological_order(&cyclic).is_err());
+
+		assert!(find_cycle(&[workflow("self", &["self"])]).is_some());
+	}
+}
@@ -0,0 +1,283 @@
+use crate::querent::QuerentError;
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, HashMap},
+	fs::{File, OpenOptions},
+	io::{BufRead, BufReader, Write},
+	path::{Path, PathBuf},
+	sync::Mutex,
+	time::SystemTime,
+};
+
+use super::WorkflowStatus;
+
+/// A run of a workflow, as recorded in the run journal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+	/// Unique identifier of the run, `<start in epoch milliseconds>-<sequence number>`.
+	pub run_id: String,
+	/// Unique identifier of the workflow.
+	pub workflow_id: String,
+	/// Arguments the entry point was called with, Python objects are recorded as null.
+	pub arguments: Vec<serde_json::Value>,
+	/// Keyword arguments the entry point was called with.
+	#[serde(default)]
+	pub kwargs: BTreeMap<String, serde_json::Value>,
+	/// Fingerprint of the configuration injected into the entry point, if any.
+	pub config_fingerprint: Option<String>,
+	/// When the run started.
+	#[serde(with = "epoch_millis")]
+	pub started_at: SystemTime,
+	/// When the run finished, `None` while it is running or if the process died during it.
+	#[serde(with = "epoch_millis::option")]
+	pub finished_at: Option<SystemTime>,
+	/// Status of the run.
+	pub status: WorkflowStatus,
+	/// Number of attempts made, 0 until the run finished.
+	pub attempts: u32,
+	/// Error that stopped the run, if it did not complete.
+	pub error: Option<String>,
+}
+
+/// Selects runs from the journal.
+#[derive(Debug, Clone, Default)]
+pub struct RunFilter {
+	workflow_id: Option<String>,
+	since: Option<SystemTime>,
+	until: Option<SystemTime>,
+}
+
+impl RunFilter {
+	/// Selects every run.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Only selects runs of the given workflow.
+	pub fn workflow(mut self, workflow_id: &str) -> Self {
+		self.workflow_id = Some(workflow_id.to_string());
+		self
+	}
+
+	/// Only selects runs that started at or after `since`.
+	pub fn since(mut self, since: SystemTime) -> Self {
+		self.since = Some(since);
+		self
+	}
+
+	/// Only selects runs that started before `until`.
+	pub fn until(mut self, until: SystemTime) -> Self {
+		self.until = Some(until);
+		self
+	}
+
+	fn matches(&self, record: &RunRecord) -> bool {
+		self.workflow_id.as_ref().map_or(true, |id| *id == record.workflow_id) &&
+			self.since.map_or(true, |since| record.started_at >= since) &&
+			self.until.map_or(true, |until| record.started_at < until)
+	}
+}
+
+/// Append-only history of workflow runs, stored as JSON lines in a local file.
+///
+/// A run is appended once when it starts and once when it finishes, so runs interrupted by
+/// a crash remain in the history with the `Running` status.
+pub struct RunJournal {
+	path: PathBuf,
+	file: Mutex<File>,
+}
+
+impl RunJournal {
+	/// Opens the journal at the given path, creating the file if it does not exist.
+	pub fn open(path: impl AsRef<Path>) -> Result<Self, QuerentError> {
+		let path = path.as_ref().to_path_buf();
+		let file = OpenOptions::new().create(true).append(true).open(&path).map_err(|e| {
+			QuerentError::internal(format!("Unable to open run journal {}: {}", path.display(), e))
+		})?;
+		Ok(RunJournal { path, file: Mutex::new(file) })
+	}
+
+	/// Path of the journal file.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Appends a record of a run, superseding the previous records of the same run.
+	pub fn append(&self, record: &RunRecord) -> Result<(), QuerentError> {
+		let mut line = serde_json::to_string(record)
+			.map_err(|e| QuerentError::internal(format!("Unable to encode run record: {}", e)))?;
+		line.push('\n');
+		let mut file = self.file.lock().unwrap();
+		// A single write keeps concurrent appends from interleaving.
+		file.write_all(line.as_bytes()).and_then(|_| file.flush()).map_err(|e| {
+			QuerentError::internal(format!(
+				"Unable to write run journal {}: {}",
+				self.path.display(),
+				e
+			))
+		})
+	}
+
+	/// Returns the latest record of every run selected by the filter, in the order the runs
+	/// started.
+	pub fn query(&self, filter: &RunFilter) -> Result<Vec<RunRecord>, QuerentError> {
+		let file = File::open(&self.path).map_err(|e| {
+			QuerentError::internal(format!(
+				"Unable to read run journal {}: {}",
+				self.path.display(),
+				e
+			))
+		})?;
+		let mut records: Vec<RunRecord> = Vec::new();
+		let mut positions: HashMap<String, usize> = HashMap::new();
+		for (number, line) in BufReader::new(file).lines().enumerate() {
+			let line = line.map_err(|e| QuerentError::internal(e.to_string()))?;
+			if line.trim().is_empty() {
+				continue;
+			}
+			let record: RunRecord = match serde_json::from_str(&line) {
+				Ok(record) => record,
+				Err(e) => {
+					// Typically a line cut short by a crash.
+					log::warn!(
+						"Skipping line {} of run journal {}: {}",
+						number + 1,
+						self.path.display(),
+						e
+					);
+					continue;
+				},
+			};
+			match positions.get(&record.run_id) {
+				Some(&position) => records[position] = record,
+				None => {
+					positions.insert(record.run_id.clone(), records.len());
+					records.push(record);
+				},
+			}
+		}
+		records.retain(|record| filter.matches(record));
+		records.sort_by_key(|record| record.started_at);
+		Ok(records)
+	}
+}
+
+/// Serializes times as milliseconds since the Unix epoch.
+mod epoch_millis {
+	use serde::{Deserialize, Deserializer, Serializer};
+	use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+	pub fn to_millis(time: &SystemTime) -> u64 {
+		time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+	}
+
+	pub fn from_millis(millis: u64) -> SystemTime {
+		UNIX_EPOCH + Duration::from_millis(millis)
+	}
+
+	pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_u64(to_millis(time))
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+		u64::deserialize(deserializer).map(from_millis)
+	}
+
+	pub mod option {
+		use serde::{Deserialize, Deserializer, Serialize, Serializer};
+		use std::time::SystemTime;
+
+		pub fn serialize<S: Serializer>(
+			time: &Option<SystemTime>,
+			serializer: S,
+		) -> Result<S::Ok, S::Error> {
+			time.as_ref().map(super::to_millis).serialize(serializer)
+		}
+
+		pub fn deserialize<'de, D: Deserializer<'de>>(
+			deserializer: D,
+		) -> Result<Option<SystemTime>, D::Error> {
+			Ok(Option::<u64>::deserialize(deserializer)?.map(super::from_millis))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{epoch_millis::from_millis, *};
+	use std::time::Duration;
+
+	fn record(run_id: &str, workflow_id: &str, started_at: u64) -> RunRecord {
+		RunRecord {
+			run_id: run_id.to_string(),
+			workflow_id: workflow_id.to_string(),
+			arguments: vec![serde_json::json!(1), serde_json::json!("two")],
+			kwargs: BTreeMap::new(),
+			config_fingerprint: None,
+			started_at: from_millis(started_at),
+			finished_at: None,
+			status: WorkflowStatus::Running,
+			attempts: 0,
+			error: None,
+		}
+	}
+
+	fn temp_journal(name: &str) -> RunJournal {
+		let path = std::env::temp_dir().join(format!(
+			"querent-run-journal-{}-{}.jsonl",
+			name,
+			std::process::id()
+		));
+		let _ = std::fs::remove_file(&path);
+		RunJournal::open(path).unwrap()
+	}
+
+	#[test]
+	fn run_journal_should_keep_latest_record_of_each_run() {
+		let journal = temp_journal("latest");
+		journal.append(&record("1-0", "ingest", 1_000)).unwrap();
+		journal.append(&record("2-1", "index", 2_000)).unwrap();
+		let mut finished = record("1-0", "ingest", 1_000);
+		finished.status = WorkflowStatus::Failed;
+		finished.finished_at = Some(from_millis(1_500));
+		finished.attempts = 2;
+		finished.error = Some("boom".to_string());
+		journal.append(&finished).unwrap();
+
+		let records = journal.query(&RunFilter::new()).unwrap();
+		assert_eq!(records, vec![finished, record("2-1", "index", 2_000)]);
+		std::fs::remove_file(journal.path()).unwrap();
+	}
+
+	#[test]
+	fn run_journal_should_filter_by_workflow_and_time_range() {
+		let journal = temp_journal("filter");
+		for (run_id, workflow_id, started_at) in
+			[("1-0", "ingest", 1_000), ("2-1", "index", 2_000), ("3-2", "ingest", 3_000)]
+		{
+			journal.append(&record(run_id, workflow_id, started_at)).unwrap();
+		}
+		// A torn line is skipped rather than failing the whole query.
+		journal.file.lock().unwrap().write_all(b"{\"run_id\":\"4-3\",\"work").unwrap();
+
+		let run_ids = |filter: RunFilter| -> Vec<String> {
+			journal
+				.query(&filter)
+				.unwrap()
+				.into_iter()
+				.map(|record| record.run_id)
+				.collect()
+		};
+		assert_eq!(run_ids(RunFilter::new().workflow("ingest")), vec!["1-0", "3-2"]);
+		assert_eq!(run_ids(RunFilter::new().since(from_millis(2_000))), vec!["2-1", "3-2"]);
+		assert_eq!(
+			run_ids(
+				RunFilter::new()
+					.workflow("ingest")
+					.until(from_millis(3_000) - Duration::from_millis(1))
+			),
+			vec!["1-0"]
+		);
+		std::fs::remove_file(journal.path()).unwrap();
+	}
+}
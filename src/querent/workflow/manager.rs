@@ -0,0 +1,893 @@
+use crate::{
+	cross::CLRepr,
+	querent::{
+		py_runtime, PyCallOptions, PyRuntime, PythonEnvironment, QuerentError, WorkerClaim,
+		WorkerPermit, WorkerUsage,
+	},
+};
+use futures::{
+	future::{join_all, BoxFuture, Shared},
+	FutureExt,
+};
+use log;
+use pyo3::{prelude::*, types::PyFunction};
+use std::{
+	collections::HashMap,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+	time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use super::{
+	check_entry_point, find_cycle, topological_order, Diagnostic, LabelSelector, ModuleCache,
+	ReloadMode, ResultStore, RunFilter, RunJournal, RunRecord, Runnable, StartReport,
+	WorkflowDependency, WorkflowReport, WorkflowResult, WorkflowState, WorkflowStatus,
+	WorkflowStatusEvent,
+};
+
+/// Number of status events buffered for slow subscribers before they start lagging.
+const STATUS_EVENTS_CAPACITY: usize = 1024;
+
+/// Run of a workflow within a DAG, resolving to its output once it completed.
+type WorkflowNode<'a> = Shared<BoxFuture<'a, Option<CLRepr>>>;
+
+/// A run registered with `begin_run`, which can be cancelled from then on, even while it
+/// waits for its upstream workflows.
+struct ScheduledRun {
+	id: u64,
+	/// Whether other runs of the same workflow were in progress when it was registered.
+	overlapping: bool,
+	cancel: CancellationToken,
+}
+
+/// Manages runnables, such as workflows or query engines, and their execution.
+pub struct RunnableManager<R: Runnable> {
+	/// Mutex-protected map of runnables, keyed by their unique identifier.
+	pub workflows: Mutex<HashMap<String, R>>,
+	/// Reference to the Python runtime.
+	pub runtime: &'static PyRuntime,
+	/// Lifecycle state of every registered workflow, keyed by workflow id.
+	states: Mutex<HashMap<String, WorkflowState>>,
+	/// Publishes every status transition.
+	status_events: broadcast::Sender<WorkflowStatusEvent>,
+	/// Cancellation tokens of the runs scheduled or in progress, keyed by workflow id and
+	/// tagged with a run id. A workflow has several of them while runs overlap.
+	running: Mutex<HashMap<String, Vec<(u64, CancellationToken)>>>,
+	/// Source of run ids.
+	next_run_id: AtomicU64,
+	/// Latest result of every workflow.
+	results: ResultStore,
+	/// History of every run, if enabled.
+	journal: Mutex<Option<Arc<RunJournal>>>,
+	/// Python modules of the runnables.
+	modules: ModuleCache,
+}
+
+impl<R: Runnable> RunnableManager<R> {
+	/// Creates a new `RunnableManager` instance.
+	pub fn new() -> Result<Self, String> {
+		Self::with_result_store(ResultStore::default())
+	}
+
+	/// Creates a new `RunnableManager` instance keeping results in the given store.
+	pub fn with_result_store(results: ResultStore) -> Result<Self, String> {
+		let runtime = py_runtime().map_err(|e| e.to_string())?;
+		let (status_events, _) = broadcast::channel(STATUS_EVENTS_CAPACITY);
+		Ok(Self {
+			workflows: Mutex::new(HashMap::new()),
+			runtime,
+			states: Mutex::new(HashMap::new()),
+			status_events,
+			running: Mutex::new(HashMap::new()),
+			next_run_id: AtomicU64::new(0),
+			results,
+			journal: Mutex::new(None),
+			modules: ModuleCache::default(),
+		})
+	}
+
+	/// Records every run from now on in the given journal, replacing the previous one.
+	pub fn set_journal(&self, journal: Arc<RunJournal>) {
+		*self.journal.lock().unwrap() = Some(journal);
+	}
+
+	/// Returns the runs recorded in the journal that are selected by the filter.
+	pub fn run_history(&self, filter: &RunFilter) -> Result<Vec<RunRecord>, QuerentError> {
+		match self.journal.lock().unwrap().clone() {
+			Some(journal) => journal.query(filter),
+			None => Err(QuerentError::user("The run journal is not enabled".to_string())),
+		}
+	}
+
+	/// Appends a record to the journal, if enabled. A failing journal does not fail the run.
+	fn journal_run(&self, record: &RunRecord) {
+		let journal = self.journal.lock().unwrap().clone();
+		if let Some(journal) = journal {
+			if let Err(e) = journal.append(record) {
+				log::error!("Unable to record run {}: {}", record.run_id, e);
+			}
+		}
+	}
+
+	/// Adds a workflow to the manager.
+	///
+	/// Fails if the dependencies of the workflow would create a cycle. Dependencies on
+	/// workflows that are not registered yet are allowed until the workflows are started.
+	pub fn add_workflow(&self, workflow: R) -> Result<(), String> {
+		let mut workflows =
+			self.workflows.lock().map_err(|e| format!("Mutex lock failed: {}", e))?;
+		if workflows.contains_key(workflow.id()) {
+			return Err("Workflow with the same ID already exists.".to_string());
+		}
+		if let Some(cycle) = find_cycle(workflows.values().chain(std::iter::once(&workflow))) {
+			return Err(format!(
+				"Workflow {} would create a dependency cycle: {}",
+				workflow.id(),
+				cycle.join(" -> ")
+			));
+		}
+		workflows.insert(workflow.id().to_string(), workflow.clone());
+		let mut states = self.states.lock().map_err(|e| format!("Mutex lock failed: {}", e))?;
+		states.insert(workflow.id().to_string(), WorkflowState::new(workflow.id()));
+		Ok(())
+	}
+
+	/// Replaces the definition of a registered workflow, for instance with new code. Runs in
+	/// progress keep the previous definition.
+	pub fn update_workflow(&self, workflow: R) -> Result<(), String> {
+		let mut workflows =
+			self.workflows.lock().map_err(|e| format!("Mutex lock failed: {}", e))?;
+		if !workflows.contains_key(workflow.id()) {
+			return Err(format!("Workflow {} is not registered", workflow.id()));
+		}
+		let others = workflows.values().filter(|other| other.id() != workflow.id());
+		if let Some(cycle) = find_cycle(others.chain(std::iter::once(&workflow))) {
+			return Err(format!(
+				"Workflow {} would create a dependency cycle: {}",
+				workflow.id(),
+				cycle.join(" -> ")
+			));
+		}
+		workflows.insert(workflow.id().to_string(), workflow);
+		Ok(())
+	}
+
+	/// Sets the import paths and virtualenv the modules of the workflows are loaded with.
+	///
+	/// The environment is checked right away, then applied again before every module is
+	/// loaded, so that it takes precedence over changes other code made to `sys.path` since.
+	/// The interpreter is shared by the whole process, so the paths stay visible to other
+	/// Python code once applied.
+	pub fn set_python_environment(
+		&self,
+		environment: &PythonEnvironment,
+	) -> Result<(), QuerentError> {
+		environment.apply()?;
+		self.modules.set_environment(environment.clone());
+		Ok(())
+	}
+
+	/// Sets when the Python modules of the workflows are loaded again between runs.
+	pub fn set_reload_mode(&self, mode: ReloadMode) {
+		self.modules.set_mode(mode);
+	}
+
+	/// Returns when the Python modules of the workflows are loaded again between runs.
+	pub fn reload_mode(&self) -> ReloadMode {
+		self.modules.mode()
+	}
+
+	/// Reloads the Python module of a workflow now, or compiles its code again, so the next
+	/// runs pick up changes made since it was loaded.
+	pub fn reload(&self, workflow_id: &str) -> Result<(), QuerentError> {
+		let workflow = self.workflows.lock().unwrap().get(workflow_id).cloned();
+		let Some(workflow) = workflow else {
+			return Err(QuerentError::user(format!("Workflow {} is not registered", workflow_id)));
+		};
+		Python::with_gil(|py| {
+			self.modules.load(py, &workflow, true).map(|_| ()).map_err(|e| {
+				log::error!("Failed to reload module of workflow {}: {}", workflow_id, e);
+				QuerentError::python(&e)
+			})
+		})
+	}
+
+	/// Returns the utilisation of the worker pools declared by the `ResourceConfig` of the
+	/// workflows run so far. The pools are shared by every manager of the process.
+	pub fn worker_usage(&self) -> Vec<WorkerUsage> {
+		self.runtime.workers().usage()
+	}
+
+	/// Retrieves a list of all workflows managed by this manager.
+	pub fn get_workflows(&self) -> Vec<R> {
+		let workflows = self.workflows.lock().unwrap();
+		workflows.values().cloned().collect()
+	}
+
+	/// Retrieves the workflows whose labels match the selector, ordered by id.
+	pub fn workflows_matching(&self, selector: &LabelSelector) -> Vec<R> {
+		let mut workflows: Vec<R> = self
+			.workflows
+			.lock()
+			.unwrap()
+			.values()
+			.filter(|workflow| selector.matches(&workflow.labels()))
+			.cloned()
+			.collect();
+		workflows.sort_by(|a, b| a.id().cmp(b.id()));
+		workflows
+	}
+
+	/// Returns the lifecycle state of the given workflow.
+	pub fn status(&self, workflow_id: &str) -> Option<WorkflowState> {
+		let states = self.states.lock().unwrap();
+		states.get(workflow_id).cloned()
+	}
+
+	/// Returns the lifecycle state of every registered workflow.
+	pub fn list_status(&self) -> Vec<WorkflowState> {
+		let states = self.states.lock().unwrap();
+		states.values().cloned().collect()
+	}
+
+	/// Subscribes to the status transitions of all workflows.
+	pub fn subscribe_status(&self) -> broadcast::Receiver<WorkflowStatusEvent> {
+		self.status_events.subscribe()
+	}
+
+	/// Returns the result of the latest finished run of the given workflow, if it is still
+	/// in the result store. The result is removed when the workflow is started again.
+	pub fn result(&self, workflow_id: &str) -> Option<WorkflowResult> {
+		self.results.get(workflow_id)
+	}
+
+	/// Waits for the result of the given workflow and returns it.
+	///
+	/// Returns the stored result if there is one, otherwise waits for the run in progress to
+	/// finish. Fails if the workflow is not running and no result of it is stored, because it
+	/// never ran or its result was evicted or expired.
+	pub async fn await_result(&self, workflow_id: &str) -> Result<WorkflowResult, QuerentError> {
+		if !self.workflows.lock().unwrap().contains_key(workflow_id) {
+			return Err(QuerentError::user(format!("Workflow {} is not registered", workflow_id)));
+		}
+		self.results
+			.wait(workflow_id, || self.is_running(workflow_id))
+			.await
+			.ok_or_else(|| {
+				QuerentError::user(format!(
+					"Workflow {} is not running and has no stored result",
+					workflow_id
+				))
+			})
+	}
+
+	/// Returns true if at least one run of the given workflow is in progress.
+	pub fn is_running(&self, workflow_id: &str) -> bool {
+		self.running
+			.lock()
+			.unwrap()
+			.get(workflow_id)
+			.map_or(false, |runs| !runs.is_empty())
+	}
+
+	/// Cancels a running workflow, including all of its overlapping runs.
+	///
+	/// The asyncio task of the workflow is cancelled, so the Python coroutine sees a
+	/// `CancelledError`, and the workflow ends up in the `Cancelled` status. A workflow started
+	/// by `start_workflows` can be cancelled while it waits for its upstream workflows too, in
+	/// which case it never starts.
+	pub fn cancel(&self, workflow_id: &str) -> Result<(), QuerentError> {
+		let running = self.running.lock().unwrap();
+		match running.get(workflow_id).filter(|runs| !runs.is_empty()) {
+			Some(runs) => {
+				log::info!("Cancelling workflow {}", workflow_id);
+				runs.iter().for_each(|(_, token)| token.cancel());
+				Ok(())
+			},
+			None => Err(QuerentError::user(format!("Workflow {} is not running", workflow_id))),
+		}
+	}
+
+	/// Cancels the running workflows whose labels match the selector and returns their ids.
+	pub fn cancel_matching(&self, selector: &LabelSelector) -> Vec<String> {
+		let mut cancelled: Vec<String> = self
+			.workflows_matching(selector)
+			.iter()
+			.filter(|workflow| self.cancel(workflow.id()).is_ok())
+			.map(|workflow| workflow.id().to_string())
+			.collect();
+		cancelled.sort();
+		cancelled
+	}
+
+	/// Registers a run, so that it can be cancelled, before it waits for anything.
+	fn begin_run(&self, workflow_id: &str) -> ScheduledRun {
+		let id = self.next_run_id.fetch_add(1, Ordering::Relaxed);
+		let cancel = CancellationToken::new();
+		let mut running = self.running.lock().unwrap();
+		let runs = running.entry(workflow_id.to_string()).or_default();
+		runs.push((id, cancel.clone()));
+		ScheduledRun { id, overlapping: runs.len() > 1, cancel }
+	}
+
+	/// Unregisters a run and returns true if it was the last one in progress.
+	fn end_run(&self, workflow_id: &str, run_id: u64) -> bool {
+		let last = {
+			let mut running = self.running.lock().unwrap();
+			let last = running.get_mut(workflow_id).map_or(true, |runs| {
+				runs.retain(|(id, _)| *id != run_id);
+				runs.is_empty()
+			});
+			if last {
+				running.remove(workflow_id);
+			}
+			last
+		};
+		// Those awaiting a result give up once no run is left to store one.
+		self.results.wake_waiters();
+		last
+	}
+
+	/// Returns true if no run of the given workflow overlaps with the current one.
+	fn is_sole_run(&self, workflow_id: &str) -> bool {
+		self.running
+			.lock()
+			.unwrap()
+			.get(workflow_id)
+			.map_or(true, |runs| runs.len() <= 1)
+	}
+
+	/// Moves a workflow to a new status and publishes the transition.
+	fn transition(
+		&self,
+		workflow_id: &str,
+		status: WorkflowStatus,
+		message: Option<String>,
+	) -> Result<(), QuerentError> {
+		let mut states = self.states.lock().unwrap();
+		let state = states
+			.entry(workflow_id.to_string())
+			.or_insert_with(|| WorkflowState::new(workflow_id));
+		if status != WorkflowStatus::Pending && state.status.is_terminal() {
+			let event = state.transition(WorkflowStatus::Pending, None)?;
+			let _ = self.status_events.send(event);
+		}
+		let event = state.transition(status, message)?;
+		// Sending only fails when nobody is subscribed.
+		let _ = self.status_events.send(event);
+		Ok(())
+	}
+
+	/// Records the final status of the last run of a workflow to finish. An overlapping run
+	/// that finished or gave up earlier may have left the status `Queued` or `Retrying`, in
+	/// which case the workflow goes through `Running` first.
+	fn finish_run(&self, workflow_id: &str, status: WorkflowStatus, message: Option<String>) {
+		let mut states = self.states.lock().unwrap();
+		let state = states
+			.entry(workflow_id.to_string())
+			.or_insert_with(|| WorkflowState::new(workflow_id));
+		let mut result = Ok(());
+		if !state.status.can_transition_to(status) &&
+			state.status.can_transition_to(WorkflowStatus::Running)
+		{
+			result = state.transition(WorkflowStatus::Running, None).map(|event| {
+				let _ = self.status_events.send(event);
+			});
+		}
+		let result = result.and_then(|_| state.transition(status, message)).map(|event| {
+			let _ = self.status_events.send(event);
+		});
+		if let Err(e) = result {
+			log::error!("Unable to record status of workflow {}: {}", workflow_id, e);
+		}
+	}
+
+	/// Moves a workflow waiting for workers to `Running`, whichever of its runs got them.
+	fn start_running(&self, workflow_id: &str) {
+		let mut states = self.states.lock().unwrap();
+		let Some(state) = states.get_mut(workflow_id) else { return };
+		if state.status == WorkflowStatus::Queued {
+			match state.transition(WorkflowStatus::Running, None) {
+				Ok(event) => {
+					let _ = self.status_events.send(event);
+				},
+				Err(e) => log::error!("Unable to start workflow {}: {}", workflow_id, e),
+			}
+		}
+	}
+
+	/// Like `transition`, for transitions that are expected to be valid.
+	fn record_transition(
+		&self,
+		workflow_id: &str,
+		status: WorkflowStatus,
+		message: Option<String>,
+	) {
+		if let Err(e) = self.transition(workflow_id, status, message) {
+			log::error!("Unable to record status of workflow {}: {}", workflow_id, e);
+		}
+	}
+
+	/// Starts all workflows and waits for every one of them to finish.
+	///
+	/// Workflows run concurrently, except that a workflow only starts once the workflows it
+	/// depends on completed. A failing workflow does not stop the others, but the workflows
+	/// depending on it are skipped. The outcome of each one is recorded in the returned
+	/// `StartReport`, keyed by workflow id.
+	pub async fn start_workflows(&self) -> Result<StartReport, QuerentError> {
+		self.start_workflows_matching(&LabelSelector::new()).await
+	}
+
+	/// Starts the workflows whose labels match the selector, like `start_workflows`.
+	///
+	/// Workflows they depend on are not started unless they match too. The latest result of
+	/// such a workflow stands in for it: its output is passed on if it completed, otherwise
+	/// the dependent workflow is skipped.
+	pub async fn start_workflows_matching(
+		&self,
+		selector: &LabelSelector,
+	) -> Result<StartReport, QuerentError> {
+		let registered = self.workflows.lock().unwrap().clone();
+		let workflows: HashMap<String, R> = registered
+			.iter()
+			.filter(|(_, workflow)| selector.matches(&workflow.labels()))
+			.map(|(id, workflow)| (id.clone(), workflow.clone()))
+			.collect();
+		let order = topological_order(workflows.values())?;
+		let report = Mutex::new(StartReport::new());
+		let mut nodes: HashMap<String, WorkflowNode<'_>> = HashMap::new();
+		for id in order {
+			let workflow = workflows[&id].clone();
+			let upstream = workflow
+				.dependencies()
+				.iter()
+				.map(|dependency| {
+					let node = nodes.get(&dependency.workflow_id).cloned().or_else(|| {
+						registered
+							.contains_key(&dependency.workflow_id)
+							.then(|| self.previous_output(&dependency.workflow_id))
+					});
+					(dependency.clone(), node)
+				})
+				.collect();
+			// Registered right away, so that the node can be cancelled while it waits.
+			let run = self.begin_run(&id);
+			let node = self.run_node(workflow, upstream, run, &report).boxed().shared();
+			nodes.insert(id, node);
+		}
+		join_all(nodes.into_values()).await;
+		Ok(report.into_inner().unwrap())
+	}
+
+	/// Checks every workflow before it is started: its module is imported or compiled, its
+	/// start function resolved and its signature matched against the arguments, keyword
+	/// arguments and configuration it would be given. Nothing is called.
+	///
+	/// Returns the problems found, ordered by workflow id; an empty list means every workflow
+	/// can be started.
+	pub fn validate(&self) -> Vec<Diagnostic> {
+		let mut workflows: Vec<R> = self.workflows.lock().unwrap().values().cloned().collect();
+		workflows.sort_by(|a, b| a.id().cmp(b.id()));
+		let known: std::collections::HashSet<String> =
+			workflows.iter().map(|workflow| workflow.id().to_string()).collect();
+		Python::with_gil(|py| {
+			let mut diagnostics = Vec::new();
+			for workflow in &workflows {
+				for dependency in workflow.dependencies() {
+					if !known.contains(&dependency.workflow_id) {
+						diagnostics.push(Diagnostic::error(
+							workflow.id(),
+							format!("Depends on unknown workflow {}", dependency.workflow_id),
+						));
+					}
+				}
+				let upstream_results = workflow
+					.dependencies()
+					.iter()
+					.filter(|dependency| dependency.pass_result)
+					.count();
+				diagnostics.extend(check_entry_point(
+					py,
+					&self.modules,
+					workflow,
+					upstream_results,
+				));
+			}
+			diagnostics
+		})
+	}
+
+	/// Output of the latest run of a workflow as a finished node, `None` unless it completed.
+	fn previous_output<'a>(&self, workflow_id: &str) -> WorkflowNode<'a> {
+		let output = self
+			.results
+			.get(workflow_id)
+			.filter(|result| result.status == WorkflowStatus::Completed)
+			.and_then(|result| result.output);
+		futures::future::ready(output).boxed().shared()
+	}
+
+	/// Runs a single workflow now and waits for it to finish.
+	///
+	/// Unlike `start_workflows`, dependencies are neither awaited nor passed their results.
+	/// If the workflow is already running, the new run overlaps with the one in progress.
+	pub async fn start_workflow(&self, workflow_id: &str) -> Result<WorkflowReport, QuerentError> {
+		let workflow = self.workflows.lock().unwrap().get(workflow_id).cloned();
+		match workflow {
+			Some(workflow) => {
+				let run = self.begin_run(workflow.id());
+				Ok(self.run_workflow(workflow, run).await)
+			},
+			None => Err(QuerentError::user(format!("Workflow {} is not registered", workflow_id))),
+		}
+	}
+
+	/// Waits for the upstream workflows, then runs the workflow with the upstream results it
+	/// asked for appended to its arguments. Returns the output of the workflow if it completed.
+	async fn run_node(
+		&self,
+		mut workflow: R,
+		upstream: Vec<(WorkflowDependency, Option<WorkflowNode<'_>>)>,
+		run: ScheduledRun,
+		report: &Mutex<StartReport>,
+	) -> Option<CLRepr> {
+		let started = Instant::now();
+		let workflow_id = workflow.id().to_string();
+		let cancelled = || {
+			QuerentError::cancelled(format!(
+				"Workflow {} was cancelled while waiting for its dependencies",
+				workflow_id
+			))
+		};
+		for (dependency, node) in upstream {
+			let output = match node {
+				Some(node) => tokio::select! {
+					biased;
+					_ = run.cancel.cancelled() => {
+						let workflow_report = self.finish_unstarted(
+							workflow.id(),
+							&run,
+							WorkflowStatus::Cancelled,
+							cancelled(),
+							started,
+						);
+						report.lock().unwrap().insert(workflow_report);
+						return None;
+					},
+					output = node => output,
+				},
+				None => {
+					let error = QuerentError::user(format!(
+						"Workflow {} depends on unknown workflow {}",
+						workflow.id(),
+						dependency.workflow_id
+					));
+					let workflow_report = self.finish_unstarted(
+						workflow.id(),
+						&run,
+						WorkflowStatus::Failed,
+						error,
+						started,
+					);
+					report.lock().unwrap().insert(workflow_report);
+					return None;
+				},
+			};
+			match output {
+				Some(value) if dependency.pass_result => workflow.push_argument(value),
+				Some(_) => {},
+				None => {
+					let error = QuerentError::user(format!(
+						"Workflow {} skipped because workflow {} did not complete",
+						workflow.id(),
+						dependency.workflow_id
+					));
+					let workflow_report = self.finish_unstarted(
+						workflow.id(),
+						&run,
+						WorkflowStatus::Skipped,
+						error,
+						started,
+					);
+					report.lock().unwrap().insert(workflow_report);
+					return None;
+				},
+			}
+		}
+		let workflow_report = if run.cancel.is_cancelled() {
+			self.finish_unstarted(
+				workflow.id(),
+				&run,
+				WorkflowStatus::Cancelled,
+				cancelled(),
+				started,
+			)
+		} else {
+			self.run_workflow(workflow, run).await
+		};
+		let output = workflow_report.output().cloned();
+		report.lock().unwrap().insert(workflow_report);
+		output
+	}
+
+	/// Records a workflow that ended without being started and unregisters its run.
+	fn finish_unstarted(
+		&self,
+		workflow_id: &str,
+		run: &ScheduledRun,
+		status: WorkflowStatus,
+		error: QuerentError,
+		started: Instant,
+	) -> WorkflowReport {
+		log::warn!("{}", error);
+		let message = error.to_string();
+		let report = WorkflowReport {
+			workflow_id: workflow_id.to_string(),
+			duration: started.elapsed(),
+			attempts: 0,
+			status,
+			result: Err(error),
+		};
+		// Stored before the run is unregistered, so that `await_result` finds it.
+		self.results.insert(WorkflowResult::from(&report));
+		if self.end_run(workflow_id, run.id) {
+			self.record_transition(workflow_id, status, Some(message));
+		}
+		report
+	}
+
+	/// Runs a single workflow to completion, retrying it according to its retry policy, and
+	/// records how it went.
+	///
+	/// While runs of the same workflow overlap, the status reflects the first run that
+	/// started and the last run that finished; every run still gets its own report.
+	async fn run_workflow(&self, workflow: R, run: ScheduledRun) -> WorkflowReport {
+		let started = Instant::now();
+		let ScheduledRun { id: run_id, overlapping, cancel } = run;
+		let claim = workflow.config().worker_claim();
+		// Runs limited by a `ResourceConfig` are queued until they have their workers.
+		let start_status =
+			if claim.is_empty() { WorkflowStatus::Running } else { WorkflowStatus::Queued };
+		let started_at = SystemTime::now();
+		let mut record = RunRecord {
+			run_id: format!(
+				"{}-{}",
+				started_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis(),
+				run_id
+			),
+			workflow_id: workflow.id().to_string(),
+			arguments: workflow.arguments().iter().map(serde_json::Value::from).collect(),
+			kwargs: workflow
+				.kwargs()
+				.iter()
+				.map(|(name, value)| (name.clone(), serde_json::Value::from(value)))
+				.collect(),
+			config_fingerprint: workflow.config().fingerprint(),
+			started_at,
+			finished_at: None,
+			status: WorkflowStatus::Running,
+			attempts: 0,
+			error: None,
+		};
+		self.journal_run(&record);
+		if overlapping {
+			log::info!(
+				"Workflow {} is already running, starting an overlapping run",
+				workflow.id()
+			);
+		} else {
+			if let Err(e) = self.transition(workflow.id(), start_status, None) {
+				log::error!("Unable to start workflow {}: {}", workflow.id(), e);
+				self.end_run(workflow.id(), run_id);
+				record.finished_at = Some(SystemTime::now());
+				record.status = WorkflowStatus::Failed;
+				record.error = Some(e.to_string());
+				self.journal_run(&record);
+				return WorkflowReport {
+					workflow_id: workflow.id().to_string(),
+					duration: started.elapsed(),
+					attempts: 0,
+					status: WorkflowStatus::Failed,
+					result: Err(e),
+				};
+			}
+			// The result of the previous run is stale from now on.
+			self.results.remove(workflow.id());
+		}
+		let mut attempt = 1;
+		let result = loop {
+			let result = match self.acquire_workers(&workflow, &claim, &cancel).await {
+				Ok(workers) => {
+					if workers.is_some() {
+						self.start_running(workflow.id());
+					}
+					let result = self.run_attempt(&workflow, &cancel).await;
+					drop(workers);
+					result
+				},
+				Err(e) => break Err(e),
+			};
+			let error = match (&result, workflow.retry()) {
+				(Err(e), Some(policy)) if policy.should_retry(attempt, e) => e,
+				_ => break result,
+			};
+			let delay = workflow.retry().map(|policy| policy.delay(attempt)).unwrap_or_default();
+			// Waiting past the deadline is pointless, the next attempt would time out at once.
+			let until_deadline = workflow
+				.deadline()
+				.map(|deadline| deadline.duration_since(SystemTime::now()).unwrap_or_default());
+			if until_deadline.map_or(false, |remaining| delay >= remaining) {
+				log::warn!(
+					"Attempt {} of workflow {} failed, no time left to retry before its deadline: {}",
+					attempt,
+					workflow.id(),
+					error
+				);
+				break result;
+			}
+			log::warn!(
+				"Attempt {} of workflow {} failed, retrying in {:?}: {}",
+				attempt,
+				workflow.id(),
+				delay,
+				error
+			);
+			let sole_run = self.is_sole_run(workflow.id());
+			if sole_run {
+				self.record_transition(
+					workflow.id(),
+					WorkflowStatus::Retrying,
+					Some(error.to_string()),
+				);
+			}
+			tokio::select! {
+				_ = tokio::time::sleep(delay) => {},
+				_ = cancel.cancelled() => break Err(QuerentError::cancelled(format!(
+					"Workflow {} was cancelled while waiting to retry",
+					workflow.id()
+				))),
+			}
+			attempt += 1;
+			if sole_run {
+				self.record_transition(workflow.id(), start_status, None);
+			}
+		};
+		let (status, message) = match &result {
+			Ok(_) => {
+				log::info!("Workflow {} completed.", workflow.id());
+				(WorkflowStatus::Completed, None)
+			},
+			Err(e) if e.is_cancelled() => {
+				log::info!("Workflow {} cancelled.", workflow.id());
+				(WorkflowStatus::Cancelled, Some(e.to_string()))
+			},
+			Err(e) => {
+				log::error!("Workflow {} failed: {}", workflow.id(), e);
+				(WorkflowStatus::Failed, Some(e.to_string()))
+			},
+		};
+		let report = WorkflowReport {
+			workflow_id: workflow.id().to_string(),
+			duration: started.elapsed(),
+			attempts: attempt,
+			status,
+			result,
+		};
+		// Stored before the run is unregistered, so that `await_result` finds it.
+		self.results.insert(WorkflowResult::from(&report));
+		if self.end_run(workflow.id(), run_id) {
+			self.finish_run(workflow.id(), status, message.clone());
+		}
+		record.finished_at = Some(SystemTime::now());
+		record.status = status;
+		record.attempts = attempt;
+		record.error = message;
+		self.journal_run(&record);
+		report
+	}
+
+	/// Makes a single attempt at running the workflow's Python entry point.
+	async fn run_attempt(
+		&self,
+		workflow: &R,
+		cancel: &CancellationToken,
+	) -> Result<CLRepr, QuerentError> {
+		let querent_py_fun = self.load_entry_point(workflow)?;
+		// A timeout only cancels the current attempt, not the retries after it.
+		let attempt_cancel = cancel.child_token();
+		let (config, query_config) = workflow.config().into_call_slots();
+		let arguments = workflow.arguments().to_vec();
+		let call = self.runtime.call_async_with_options(
+			querent_py_fun,
+			arguments,
+			config,
+			query_config,
+			PyCallOptions {
+				kwargs: workflow.kwargs(),
+				config_injection: workflow.config_injection(),
+				cancel: Some(attempt_cancel.clone()),
+				priority: workflow.priority(),
+				..Default::default()
+			},
+		);
+		match workflow.time_budget() {
+			Some(budget) => match tokio::time::timeout(budget, call).await {
+				Ok(result) => result,
+				Err(_) => {
+					// Dropping the call does not stop Python, the token does.
+					attempt_cancel.cancel();
+					Err(QuerentError::timeout(format!(
+						"Workflow {} did not finish within {:?}",
+						workflow.id(),
+						budget
+					)))
+				},
+			},
+			None => call.await,
+		}
+	}
+
+	/// Takes the workers claimed by the workflow's `ResourceConfig`, waiting while any of their
+	/// pools is busy. Returns `None` if the workflow claims no worker.
+	async fn acquire_workers(
+		&self,
+		workflow: &R,
+		claim: &WorkerClaim,
+		cancel: &CancellationToken,
+	) -> Result<Option<WorkerPermit>, QuerentError> {
+		if claim.is_empty() {
+			return Ok(None);
+		}
+		let workers = self.runtime.workers();
+		if let Some(permit) = workers.try_acquire(claim) {
+			return Ok(Some(permit));
+		}
+		log::info!("Workflow {} is waiting for a free worker", workflow.id());
+		tokio::select! {
+			permit = workers.acquire(claim) => Ok(Some(permit)),
+			_ = cancel.cancelled() => Err(QuerentError::cancelled(format!(
+				"Workflow {} was cancelled while waiting for a worker",
+				workflow.id()
+			))),
+		}
+	}
+
+	/// Imports or compiles the workflow's Python module, unless it is already loaded and up
+	/// to date according to the reload mode, and resolves its start function.
+	fn load_entry_point(&self, workflow: &R) -> Result<Py<PyFunction>, QuerentError> {
+		Python::with_gil(|py| {
+			let module = self.modules.load(py, workflow, false).map_err(|e| {
+				match (workflow.code(), workflow.path()) {
+					(Some(_), _) =>
+						log::error!("Failed to compile code of workflow {}: {}", workflow.id(), e),
+					(None, Some(path)) => log::error!("Failed to load {}: {}", path.display(), e),
+					(None, None) =>
+						log::error!("Failed to import module {}: {}", workflow.import(), e),
+				}
+				QuerentError::internal(e.to_string())
+			})?;
+
+			let attr_fun = module.getattr(workflow.attr()).map_err(|_| {
+				log::error!("Failed to find start function.");
+				QuerentError::internal("Failed to find start function.".to_string())
+			})?;
+
+			attr_fun.extract().map_err(|e| {
+				log::error!("Failed to extract function: {}", e);
+				QuerentError::internal(e.to_string())
+			})
+		})
+	}
+}
+
+impl<R: Runnable> Drop for RunnableManager<R> {
+	/// Drops the `RunnableManager` instance, cleaning up resources.
+	fn drop(&mut self) {
+		log::info!("Dropping RunnableManager");
+		let _ = self.runtime;
+	}
+}
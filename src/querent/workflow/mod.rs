@@ -2,3 +2,29 @@ pub mod workflow;
 pub use workflow::*;
 pub mod workflow_builder;
 pub use workflow_builder::*;
+pub mod report;
+pub use report::*;
+pub mod status;
+pub use status::*;
+pub mod retry;
+pub use retry::*;
+pub mod dag;
+pub use dag::*;
+pub mod pipeline;
+pub use pipeline::*;
+pub mod result_store;
+pub use result_store::*;
+pub mod runnable;
+pub use runnable::*;
+pub mod manager;
+pub use manager::*;
+pub mod scheduler;
+pub use scheduler::*;
+pub mod journal;
+pub use journal::*;
+pub mod reload;
+pub use reload::*;
+pub mod validation;
+pub use validation::*;
+pub mod selector;
+pub use selector::*;
@@ -0,0 +1,576 @@
+use crate::{
+	callbacks::{EventState, EventType},
+	comm::{ChannelHandler, IngestedTokens, MessageState, MessageType},
+	config::{ConfigSpec, ConfigWiring},
+	cross::CLRepr,
+	querent::{ConfigInjection, PythonEnvironment, QuerentError, TaskPriority},
+};
+use serde::Deserialize;
+use std::{
+	collections::{HashMap, HashSet},
+	path::{Path, PathBuf},
+	time::Duration,
+};
+use tokio::sync::mpsc;
+
+use super::{OverlapPolicy, RetryPolicy, Schedule, Workflow, WorkflowBuilder};
+
+/// Number of events buffered between Python and Rust for pipelines loaded from a file.
+pub(crate) const PIPELINE_EVENTS_CAPACITY: usize = 1024;
+
+/// File representation of a set of workflows and their configuration.
+///
+/// ```yaml
+/// python:
+///   sys_path: [lib]
+///   virtualenv: .venv
+/// config:
+///   querent_id: querent
+///   engines:
+///     - id: engine
+///       name: knowledge graph
+///       config:
+///         batch_size: 16
+/// workflows:
+///   - id: collect
+///     import: my_pipeline.collect
+///     attr: start
+///     timeout_secs: 60
+///   - id: extract
+///     path: extract.py
+///     attr: start
+///     depends_on_result: [collect]
+///     retry:
+///       max_attempts: 3
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineSpec {
+	/// Import paths and virtualenv applied before the workflows are imported.
+	#[serde(default)]
+	pub python: Option<PythonSpec>,
+	/// Configuration passed to every workflow that does not declare its own.
+	#[serde(default)]
+	pub config: Option<ConfigSpec>,
+	/// Workflows of the pipeline.
+	pub workflows: Vec<WorkflowSpec>,
+}
+
+/// File representation of a `Workflow`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowSpec {
+	/// Unique identifier for the workflow.
+	pub id: String,
+	/// Name of the workflow, defaults to the id.
+	#[serde(default)]
+	pub name: Option<String>,
+	/// Python module to import for the workflow.
+	#[serde(default)]
+	pub import: Option<String>,
+	/// Attribute of the Python module containing the start function.
+	pub attr: String,
+	/// Optional Python code to execute instead of importing a module.
+	#[serde(default)]
+	pub code: Option<String>,
+	/// Optional Python file or package directory to load instead of importing a module.
+	#[serde(default)]
+	pub path: Option<PathBuf>,
+	/// Arguments to pass to the workflow's start function.
+	#[serde(default)]
+	pub arguments: Vec<serde_json::Value>,
+	/// Keyword arguments to pass to the workflow's start function.
+	#[serde(default)]
+	pub kwargs: HashMap<String, serde_json::Value>,
+	/// Configuration of the workflow, overriding the pipeline configuration.
+	#[serde(default)]
+	pub config: Option<ConfigSpec>,
+	/// How the configuration is passed: `positional`, `omitted` or `{keyword: <name>}`.
+	#[serde(default, with = "serde_yaml::with::singleton_map")]
+	pub inject_config: ConfigInjection,
+	/// Maximum time in seconds a single run may take.
+	#[serde(default)]
+	pub timeout_secs: Option<f64>,
+	/// Workflows that must complete before this one starts.
+	#[serde(default)]
+	pub depends_on: Vec<String>,
+	/// Workflows that must complete before this one starts, whose results are appended to
+	/// the arguments.
+	#[serde(default)]
+	pub depends_on_result: Vec<String>,
+	/// Optional policy to start the workflow again when it fails.
+	#[serde(default)]
+	pub retry: Option<RetrySpec>,
+	/// Optional recurring schedule.
+	#[serde(default)]
+	pub schedule: Option<ScheduleSpec>,
+	/// Labels to select the workflow by.
+	#[serde(default)]
+	pub labels: HashMap<String, String>,
+	/// Tags of the workflow, labels without a value.
+	#[serde(default)]
+	pub tags: Vec<String>,
+	/// Priority of the workflow's runs: `low`, `normal` or `high`.
+	#[serde(default)]
+	pub priority: TaskPriority,
+}
+
+/// File representation of a `PythonEnvironment`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PythonSpec {
+	/// Directories added to `sys.path`.
+	#[serde(default)]
+	pub sys_path: Vec<PathBuf>,
+	/// `PYTHONPATH` style list of directories added to `sys.path` after `sys_path`.
+	#[serde(default)]
+	pub python_path: Option<String>,
+	/// Virtualenv whose packages are made importable.
+	#[serde(default)]
+	pub virtualenv: Option<PathBuf>,
+}
+
+/// File representation of a `Schedule`, with exactly one of `cron` and `every_secs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleSpec {
+	/// Five field cron expression, evaluated in UTC.
+	#[serde(default)]
+	pub cron: Option<String>,
+	/// Fixed interval in seconds.
+	#[serde(default)]
+	pub every_secs: Option<f64>,
+	/// What happens when a run is due while the previous one is still in progress: `skip`,
+	/// `queue` or `parallel`.
+	#[serde(default)]
+	pub overlap: OverlapPolicy,
+}
+
+/// File representation of a `RetryPolicy`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetrySpec {
+	/// Maximum number of attempts, including the first one.
+	pub max_attempts: u32,
+	/// Delay in seconds before the second attempt.
+	#[serde(default)]
+	pub initial_backoff_secs: Option<f64>,
+	/// Upper bound in seconds for the delay between two attempts.
+	#[serde(default)]
+	pub max_backoff_secs: Option<f64>,
+	/// Factor applied to the delay after every failed attempt.
+	#[serde(default)]
+	pub multiplier: Option<f64>,
+	/// Fraction of the delay randomly added or removed.
+	#[serde(default)]
+	pub jitter: Option<f64>,
+	/// Python exception classes that are retried.
+	#[serde(default)]
+	pub retry_on: Vec<String>,
+	/// Whether a run that hit its timeout is retried.
+	#[serde(default)]
+	pub retry_on_timeout: bool,
+}
+
+/// Rust ends of the channels of a pipeline loaded with `Querent::load_from_file`.
+#[derive(Debug)]
+pub struct PipelineChannels {
+	/// Events sent by the Python workflows.
+	pub events: mpsc::Receiver<(EventType, EventState)>,
+	/// Sends messages to the Python workflows.
+	pub message_sender: crossbeam_channel::Sender<(MessageType, MessageState)>,
+	/// Messages sent by the Python workflows.
+	pub message_receiver: crossbeam_channel::Receiver<(MessageType, MessageState)>,
+	/// Feeds live tokens to the Python engines.
+	pub token_sender: crossbeam_channel::Sender<IngestedTokens>,
+	/// Tokens sent by the Python workflows.
+	pub token_receiver: crossbeam_channel::Receiver<IngestedTokens>,
+}
+
+/// Workflows built from a `PipelineSpec`, with the message channels they were wired to.
+#[derive(Debug)]
+pub struct Pipeline {
+	/// Workflows in the order they were declared.
+	pub workflows: Vec<Workflow>,
+	/// Sends messages to the Python workflows.
+	pub message_sender: crossbeam_channel::Sender<(MessageType, MessageState)>,
+	/// Messages sent by the Python workflows.
+	pub message_receiver: crossbeam_channel::Receiver<(MessageType, MessageState)>,
+	/// Feeds live tokens to the Python engines.
+	pub token_sender: crossbeam_channel::Sender<IngestedTokens>,
+	/// Tokens sent by the Python workflows.
+	pub token_receiver: crossbeam_channel::Receiver<IngestedTokens>,
+	/// Environment to apply before the workflows are imported.
+	pub python: Option<PythonEnvironment>,
+}
+
+impl PipelineSpec {
+	/// Reads a pipeline from a file. Files ending in `.json` are read as JSON, anything else
+	/// as YAML. Errors are prefixed with the path and point at the offending line.
+	///
+	/// Relative workflow paths, `sys_path` entries and virtualenvs are resolved against the
+	/// directory of the file.
+	pub fn from_file(path: impl AsRef<Path>) -> Result<Self, QuerentError> {
+		let path = path.as_ref();
+		let content = std::fs::read_to_string(path).map_err(|e| {
+			QuerentError::user(format!("Unable to read pipeline {}: {}", path.display(), e))
+		})?;
+		let is_json = path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("json"));
+		let spec =
+			if is_json { Self::from_json_str(&content) } else { Self::from_yaml_str(&content) };
+		let mut spec =
+			spec.map_err(|e| QuerentError::user(format!("{}: {}", path.display(), e.message)))?;
+		if let Some(base) = path.parent() {
+			spec.resolve_paths(base);
+		}
+		Ok(spec)
+	}
+
+	fn resolve_paths(&mut self, base: &Path) {
+		let resolve = |path: &mut PathBuf| {
+			if path.is_relative() {
+				*path = base.join(&*path);
+			}
+		};
+		if let Some(python) = &mut self.python {
+			python.sys_path.iter_mut().for_each(resolve);
+			python.virtualenv.iter_mut().for_each(resolve);
+		}
+		for workflow in &mut self.workflows {
+			workflow.path.iter_mut().for_each(resolve);
+		}
+	}
+
+	/// Reads a pipeline from YAML.
+	pub fn from_yaml_str(content: &str) -> Result<Self, QuerentError> {
+		serde_yaml::from_str(content)
+			.map_err(|e| QuerentError::user(format!("Invalid pipeline: {}", e)))
+	}
+
+	/// Reads a pipeline from JSON.
+	pub fn from_json_str(content: &str) -> Result<Self, QuerentError> {
+		serde_json::from_str(content)
+			.map_err(|e| QuerentError::user(format!("Invalid pipeline: {}", e)))
+	}
+
+	/// Builds the workflows, wiring shared message channels into the configuration of every
+	/// workflow. Events are left unconnected, for `Querent::add_workflow` to deliver them.
+	///
+	/// Errors name the offending workflow; `Querent::load_from_file` also prefixes them with
+	/// the path of the file.
+	pub fn build(self) -> Result<Pipeline, QuerentError> {
+		let (message_sender, py_message_receiver) = crossbeam_channel::unbounded();
+		let (py_message_sender, message_receiver) = crossbeam_channel::unbounded();
+		let (token_sender, py_token_receiver) = crossbeam_channel::unbounded();
+		let (py_token_sender, token_receiver) = crossbeam_channel::unbounded();
+		let wiring = ConfigWiring {
+			event_handler: None,
+			channel: Some(ChannelHandler::new(
+				Some(py_token_sender.clone()),
+				None,
+				Some(py_message_receiver),
+				Some(py_message_sender),
+			)),
+			tokens_feader: Some(ChannelHandler::new(
+				Some(py_token_sender),
+				Some(py_token_receiver),
+				None,
+				None,
+			)),
+		};
+
+		let mut ids = HashSet::new();
+		for (index, spec) in self.workflows.iter().enumerate() {
+			if !ids.insert(spec.id.as_str()) {
+				return Err(QuerentError::user(format!(
+					"workflows[{}] ({}): duplicate workflow id",
+					index, spec.id
+				)));
+			}
+		}
+		for (index, spec) in self.workflows.iter().enumerate() {
+			let unknown = spec
+				.depends_on
+				.iter()
+				.chain(&spec.depends_on_result)
+				.find(|dependency| !ids.contains(dependency.as_str()));
+			if let Some(dependency) = unknown {
+				return Err(QuerentError::user(format!(
+					"workflows[{}] ({}): depends on unknown workflow {}",
+					index, spec.id, dependency
+				)));
+			}
+		}
+
+		let mut workflows = Vec::with_capacity(self.workflows.len());
+		for (index, spec) in self.workflows.into_iter().enumerate() {
+			let id = spec.id.clone();
+			let config = spec.config.clone().or_else(|| self.config.clone());
+			let workflow = spec.build(config.map(|config| config.build(&wiring))).map_err(|e| {
+				QuerentError::user(format!("workflows[{}] ({}): {}", index, id, e.message))
+			})?;
+			workflows.push(workflow);
+		}
+
+		Ok(Pipeline {
+			workflows,
+			message_sender,
+			message_receiver,
+			token_sender,
+			token_receiver,
+			python: self.python.map(PythonSpec::build),
+		})
+	}
+}
+
+impl WorkflowSpec {
+	fn build(self, config: Option<crate::config::Config>) -> Result<Workflow, QuerentError> {
+		if self.import.is_none() && self.code.is_none() && self.path.is_none() {
+			return Err(QuerentError::user(format!(
+				"workflow {} needs one of `import`, `code` or `path`",
+				self.id
+			)));
+		}
+		let mut builder = WorkflowBuilder::new(&self.id)
+			.import(self.import)
+			.attr(Some(self.attr))
+			.code(self.code)
+			.arguments(self.arguments.into_iter().map(CLRepr::from).collect())
+			.kwargs(
+				self.kwargs
+					.into_iter()
+					.map(|(name, value)| (name, CLRepr::from(value)))
+					.collect(),
+			)
+			.config_injection(self.inject_config);
+		if let Some(name) = &self.name {
+			builder = builder.name(name);
+		}
+		if let Some(path) = self.path {
+			builder = builder.path(path);
+		}
+		if let Some(config) = config {
+			builder = builder.config(config);
+		}
+		if let Some(timeout) = self.timeout_secs {
+			builder = builder.timeout(seconds(timeout, "timeout_secs")?);
+		}
+		for dependency in &self.depends_on {
+			builder = builder.depends_on(dependency);
+		}
+		for dependency in &self.depends_on_result {
+			builder = builder.depends_on_result(dependency);
+		}
+		if let Some(retry) = self.retry {
+			builder = builder.retry(retry.build()?);
+		}
+		if let Some(schedule) = self.schedule {
+			builder = builder.schedule(schedule.build()?);
+		}
+		for tag in &self.tags {
+			builder = builder.tag(tag);
+		}
+		for (key, value) in &self.labels {
+			builder = builder.label(key, value);
+		}
+		Ok(builder.priority(self.priority).build())
+	}
+}
+
+impl PythonSpec {
+	fn build(self) -> PythonEnvironment {
+		let mut environment = PythonEnvironment::new();
+		for path in self.sys_path {
+			environment = environment.sys_path(path);
+		}
+		if let Some(python_path) = &self.python_path {
+			environment = environment.python_path(python_path);
+		}
+		if let Some(virtualenv) = self.virtualenv {
+			environment = environment.virtualenv(virtualenv);
+		}
+		environment
+	}
+}
+
+impl ScheduleSpec {
+	fn build(self) -> Result<Schedule, QuerentError> {
+		let schedule = match (self.cron, self.every_secs) {
+			(Some(cron), None) => Schedule::cron(&cron)
+				.map_err(|e| QuerentError::user(format!("schedule.cron: {}", e.message)))?,
+			(None, Some(secs)) if secs <= 0.0 =>
+				return Err(QuerentError::user(format!(
+					"schedule.every_secs: expected a number of seconds above zero, got {}",
+					secs
+				))),
+			(None, Some(secs)) => Schedule::every(seconds(secs, "schedule.every_secs")?)
+				.map_err(|e| QuerentError::user(format!("schedule.every_secs: {}", e.message)))?,
+			_ =>
+				return Err(QuerentError::user(
+					"schedule: expected exactly one of `cron` and `every_secs`".to_string(),
+				)),
+		};
+		Ok(schedule.overlap(self.overlap))
+	}
+}
+
+impl RetrySpec {
+	fn build(self) -> Result<RetryPolicy, QuerentError> {
+		let defaults = RetryPolicy::default();
+		let initial_backoff = match self.initial_backoff_secs {
+			Some(secs) => seconds(secs, "retry.initial_backoff_secs")?,
+			None => defaults.initial_backoff,
+		};
+		let max_backoff = match self.max_backoff_secs {
+			Some(secs) => seconds(secs, "retry.max_backoff_secs")?,
+			None => defaults.max_backoff,
+		};
+		let mut policy = RetryPolicy::new(self.max_attempts)
+			.backoff(initial_backoff, max_backoff)
+			.multiplier(self.multiplier.unwrap_or(defaults.multiplier))
+			.jitter(self.jitter.unwrap_or(defaults.jitter))
+			.retry_on_timeout(self.retry_on_timeout);
+		for exception in &self.retry_on {
+			policy = policy.retry_on(exception);
+		}
+		Ok(policy)
+	}
+}
+
+fn seconds(secs: f64, field: &str) -> Result<Duration, QuerentError> {
+	Duration::try_from_secs_f64(secs).map_err(|_| {
+		QuerentError::user(format!(
+			"{}: expected a positive number of seconds, got {}",
+			field, secs
+		))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::querent::LabelSelector;
+
+	const PIPELINE: &str = r#"
+config:
+  querent_id: tests
+  engines:
+    - id: engine
+      name: graph
+      config:
+        batch_size: 16
+        verbose: true
+  resource:
+    id: resource
+    max_workers_allowed: 4
+workflows:
+  - id: collect
+    attr: start
+    code: |
+      async def start(config, path):
+          return path
+    arguments: ["data/", 2]
+    kwargs:
+      recursive: true
+    inject_config:
+      keyword: settings
+    timeout_secs: 1.5
+  - id: extract
+    import: pipeline.extract
+    attr: start
+    depends_on_result: [collect]
+    retry:
+      max_attempts: 4
+      retry_on: [ConnectionError]
+    schedule:
+      cron: "0 2 * * *"
+      overlap: queue
+    priority: low
+    labels:
+      env: prod
+    tags: [nightly]
+"#;
+
+	#[test]
+	fn pipeline_spec_should_build_workflows_with_wired_config() {
+		let pipeline = PipelineSpec::from_yaml_str(PIPELINE).unwrap().build().unwrap();
+		assert_eq!(pipeline.workflows.len(), 2);
+
+		let collect = &pipeline.workflows[0];
+		assert_eq!(collect.name, "collect");
+		assert_eq!(collect.timeout, Some(Duration::from_millis(1500)));
+		assert!(matches!(collect.arguments[1], CLRepr::Int(2)));
+		assert!(matches!(collect.kwargs["recursive"], CLRepr::Bool(true)));
+		assert_eq!(collect.config_injection, ConfigInjection::Keyword("settings".to_string()));
+		let config = collect.config.as_ref().unwrap();
+		assert_eq!(config.querent_id, "tests");
+		assert_eq!(config.engines[0].config["batch_size"], "16");
+		assert_eq!(config.engines[0].config["verbose"], "true");
+		assert_eq!(config.resource.as_ref().unwrap().max_workers_allowed, Some(4));
+		assert!(config.workflow.inner_event_handler.is_none());
+		assert!(config.workflow.inner_channel.is_some());
+
+		let extract = &pipeline.workflows[1];
+		assert_eq!(extract.import, "pipeline.extract");
+		assert_eq!(extract.config_injection, ConfigInjection::Positional);
+		assert!(extract.dependencies[0].pass_result);
+		let retry = extract.retry.as_ref().unwrap();
+		assert_eq!(retry.max_attempts, 4);
+		assert_eq!(retry.retry_on, vec!["ConnectionError".to_string()]);
+		let schedule = extract.schedule.as_ref().unwrap();
+		assert!(matches!(schedule.trigger, crate::querent::Trigger::Cron(_)));
+		assert_eq!(schedule.overlap, OverlapPolicy::Queue);
+		assert_eq!(extract.priority, TaskPriority::Low);
+		let nightly: LabelSelector = "nightly, env in (prod, staging)".parse().unwrap();
+		assert!(nightly.matches(&extract.labels));
+		assert!(!nightly.matches(&collect.labels));
+		assert_eq!(collect.priority, TaskPriority::Normal);
+	}
+
+	#[test]
+	fn pipeline_spec_should_point_at_invalid_fields() {
+		let invalid_type = "workflows:\n  - id: collect\n    attr: start\n    timeout_secs: soon\n";
+		let error = PipelineSpec::from_yaml_str(invalid_type).unwrap_err();
+		assert!(error.message.contains("workflows[0].timeout_secs"), "{}", error.message);
+		assert!(error.message.contains("line 4"), "{}", error.message);
+
+		let unknown_field = r#"{"workflows": [{"id": "a", "attr": "start",
+			"tiemout_secs": 3}]}"#;
+		let error = PipelineSpec::from_json_str(unknown_field).unwrap_err();
+		assert!(error.message.contains("tiemout_secs"), "{}", error.message);
+		assert!(error.message.contains("line 2"), "{}", error.message);
+
+		let missing_source = "workflows:\n  - id: collect\n    attr: start\n";
+		let error = PipelineSpec::from_yaml_str(missing_source).unwrap().build().unwrap_err();
+		assert!(error.message.starts_with("workflows[0] (collect)"), "{}", error.message);
+
+		let unknown_dependency = "workflows:\n  - id: collect\n    code: pass\n    attr: start\n  \
+			- id: extract\n    code: pass\n    attr: start\n    depends_on: [colect]\n";
+		let error = PipelineSpec::from_yaml_str(unknown_dependency).unwrap().build().unwrap_err();
+		assert!(error.message.starts_with("workflows[1] (extract)"), "{}", error.message);
+		assert!(error.message.contains("unknown workflow colect"), "{}", error.message);
+
+		let zero_interval = "workflows:\n  - id: collect\n    code: pass\n    attr: start\n    \
+			schedule:\n      every_secs: 0\n";
+		let error = PipelineSpec::from_yaml_str(zero_interval).unwrap().build().unwrap_err();
+		assert!(error.message.contains("schedule.every_secs"), "{}", error.message);
+	}
+
+	#[test]
+	fn pipeline_spec_should_resolve_paths_against_file() {
+		let dir = std::env::temp_dir().join(format!("querent-pipeline-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let file = dir.join("pipeline.yaml");
+		let content = "python:\n  sys_path: [lib, /opt/shared]\n  virtualenv: .venv\nworkflows:\n  - id: extract\n    path: tasks/extract.py\n    attr: start\n";
+		std::fs::write(&file, content).unwrap();
+
+		let spec = PipelineSpec::from_file(&file).unwrap();
+		let python = spec.python.as_ref().unwrap();
+		assert_eq!(python.sys_path, vec![dir.join("lib"), PathBuf::from("/opt/shared")]);
+		assert_eq!(python.virtualenv, Some(dir.join(".venv")));
+		let pipeline = spec.build().unwrap();
+		assert_eq!(pipeline.workflows[0].path, Some(dir.join("tasks").join("extract.py")));
+		assert!(pipeline.python.is_some());
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,302 @@
+use pyo3::{
+	exceptions::{PyFileNotFoundError, PyImportError},
+	prelude::*,
+	types::{IntoPyDict, PyDict, PyModule},
+};
+use serde::Deserialize;
+use std::{
+	collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+	hash::{Hash, Hasher},
+	path::Path,
+	sync::Mutex,
+	time::SystemTime,
+};
+
+use super::Runnable;
+use crate::querent::PythonEnvironment;
+
+/// Package under which the modules loaded from files are registered in `sys.modules`, so that
+/// they never shadow nor clobber a module of the same name.
+const WORKFLOW_MODULES_PACKAGE: &str = "querent_workflows";
+
+/// When the Python modules of runnables are loaded again between runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadMode {
+	/// Modules and files are loaded once; inline code is compiled again only when it changes.
+	#[default]
+	Never,
+	/// Modules and files are reloaded when their source file changed since they were loaded.
+	/// Imported packages are reloaded, with their loaded submodules, when the source of any of
+	/// them changed.
+	OnChange,
+	/// Modules and files are reloaded and inline code is compiled again before every run.
+	Always,
+}
+
+/// Module compiled from the inline code of a runnable.
+struct CompiledCode {
+	hash: u64,
+	module: Py<PyModule>,
+}
+
+/// Module loaded from the file or package directory of a runnable.
+struct LoadedFile {
+	modified: Option<SystemTime>,
+	module: Py<PyModule>,
+}
+
+/// Loads the Python modules of runnables, reloading them according to a `ReloadMode`.
+///
+/// Locks are never held while Python code runs, since importing a module may release the GIL.
+#[derive(Default)]
+pub(crate) struct ModuleCache {
+	mode: Mutex<ReloadMode>,
+	/// Modules compiled from inline code, keyed by runnable id.
+	compiled: Mutex<HashMap<String, CompiledCode>>,
+	/// Modules loaded from a file or package directory, keyed by runnable id.
+	files: Mutex<HashMap<String, LoadedFile>>,
+	/// Modification times of the source files of imported modules and their submodules when
+	/// they were last loaded, keyed by module name.
+	source_modified: Mutex<HashMap<String, BTreeMap<String, SystemTime>>>,
+	/// Import paths and virtualenv applied before every load.
+	environment: Mutex<Option<PythonEnvironment>>,
+}
+
+impl ModuleCache {
+	pub(crate) fn mode(&self) -> ReloadMode {
+		*self.mode.lock().unwrap()
+	}
+
+	pub(crate) fn set_mode(&self, mode: ReloadMode) {
+		*self.mode.lock().unwrap() = mode;
+	}
+
+	pub(crate) fn set_environment(&self, environment: PythonEnvironment) {
+		*self.environment.lock().unwrap() = Some(environment);
+	}
+
+	/// Returns the module of a runnable, importing or compiling it if needed. With `force`,
+	/// the module is reloaded regardless of the mode.
+	pub(crate) fn load<'py, R: Runnable>(
+		&self,
+		py: Python<'py>,
+		runnable: &R,
+		force: bool,
+	) -> PyResult<&'py PyModule> {
+		// `sys.path` is shared with the rest of the process, which may have changed it since
+		// the environment was last applied.
+		let environment = self.environment.lock().unwrap().clone();
+		if let Some(environment) = environment {
+			environment.apply().map_err(|e| PyImportError::new_err(e.to_string()))?;
+		}
+		match (runnable.code(), runnable.path()) {
+			(Some(code), _) => self.compile(py, runnable, code, force),
+			(None, Some(path)) => self.load_file(py, runnable, path, force),
+			(None, None) => self.import(py, runnable.import(), force),
+		}
+	}
+
+	fn compile<'py, R: Runnable>(
+		&self,
+		py: Python<'py>,
+		runnable: &R,
+		code: &str,
+		force: bool,
+	) -> PyResult<&'py PyModule> {
+		let mut hasher = DefaultHasher::new();
+		code.hash(&mut hasher);
+		let hash = hasher.finish();
+		if !force && self.mode() != ReloadMode::Always {
+			let compiled = self.compiled.lock().unwrap();
+			if let Some(cached) = compiled.get(runnable.id()).filter(|cached| cached.hash == hash) {
+				return Ok(cached.module.clone_ref(py).into_ref(py));
+			}
+		}
+		let module_file = runnable.id().to_string() + ".py";
+		let module = PyModule::from_code(py, code, &module_file, runnable.name())?;
+		log::debug!("Compiled code of {}", runnable.id());
+		self.compiled
+			.lock()
+			.unwrap()
+			.insert(runnable.id().to_string(), CompiledCode { hash, module: module.into() });
+		Ok(module)
+	}
+
+	/// Loads a Python file, or a package directory through its `__init__.py`. The module is
+	/// named after the runnable, under the `querent_workflows` package, and reloaded when that
+	/// file changes.
+	fn load_file<'py, R: Runnable>(
+		&self,
+		py: Python<'py>,
+		runnable: &R,
+		path: &Path,
+		force: bool,
+	) -> PyResult<&'py PyModule> {
+		let is_package = path.is_dir();
+		let file = if is_package { path.join("__init__.py") } else { path.to_path_buf() };
+		if !file.is_file() {
+			return Err(if is_package {
+				PyImportError::new_err(format!(
+					"{} is not a Python package, it has no __init__.py",
+					path.display()
+				))
+			} else {
+				PyFileNotFoundError::new_err(format!(
+					"Python source {} of {} does not exist",
+					path.display(),
+					runnable.id()
+				))
+			});
+		}
+		let modified = std::fs::metadata(&file).and_then(|m| m.modified()).ok();
+		let reuse = !force &&
+			match self.mode() {
+				ReloadMode::Always => false,
+				ReloadMode::Never => true,
+				ReloadMode::OnChange => self
+					.files
+					.lock()
+					.unwrap()
+					.get(runnable.id())
+					.map_or(true, |loaded| loaded.modified == modified),
+			};
+		if reuse {
+			if let Some(loaded) = self.files.lock().unwrap().get(runnable.id()) {
+				return Ok(loaded.module.clone_ref(py).into_ref(py));
+			}
+		}
+
+		let name = module_name(runnable.id());
+		let modules = py.import("sys")?.getattr("modules")?;
+		// Submodules of a package are loaded again along with it.
+		let prefix = format!("{}.", name);
+		let stale: Vec<String> = modules
+			.call_method0("keys")?
+			.iter()?
+			.filter_map(|key| key.ok()?.extract::<String>().ok())
+			.filter(|key| key.starts_with(&prefix))
+			.collect();
+		for key in stale {
+			modules.del_item(key)?;
+		}
+
+		let util = py.import("importlib.util")?;
+		let file_name = file.to_string_lossy();
+		let kwargs = is_package.then(|| {
+			[("submodule_search_locations", vec![path.to_string_lossy().into_owned()])]
+				.into_py_dict(py)
+		});
+		let spec =
+			util.call_method("spec_from_file_location", (&name, file_name.as_ref()), kwargs)?;
+		if spec.is_none() {
+			return Err(PyImportError::new_err(format!(
+				"Unable to load {} as a Python module",
+				path.display()
+			)));
+		}
+		let module: &PyModule = util.call_method1("module_from_spec", (spec,))?.downcast()?;
+		// Registered before it runs, as an import would, so relative imports resolve.
+		modules.set_item(&name, module)?;
+		if let Err(e) = spec.getattr("loader")?.call_method1("exec_module", (module,)) {
+			modules.del_item(&name)?;
+			return Err(e);
+		}
+		log::info!("Loaded module {} from {}", name, path.display());
+		self.files
+			.lock()
+			.unwrap()
+			.insert(runnable.id().to_string(), LoadedFile { modified, module: module.into() });
+		Ok(module)
+	}
+
+	/// Imports a module by name. A package is considered changed when the source of any of its
+	/// loaded submodules changed, and its submodules are reloaded along with it.
+	fn import<'py>(&self, py: Python<'py>, name: &str, force: bool) -> PyResult<&'py PyModule> {
+		let module = py.import(name)?;
+		let loaded = submodules(py, name)?;
+		let modified = sources_modified(module, &loaded)?;
+		let reload = force ||
+			match self.mode() {
+				ReloadMode::Never => false,
+				ReloadMode::Always => true,
+				ReloadMode::OnChange => self
+					.source_modified
+					.lock()
+					.unwrap()
+					.get(name)
+					.map_or(false, |seen| *seen != modified),
+			};
+		if !reload {
+			self.source_modified.lock().unwrap().entry(name.to_string()).or_insert(modified);
+			return Ok(module);
+		}
+
+		let importlib = py.import("importlib")?;
+		// Finders cache directory listings, which would hide new files of a package.
+		importlib.call_method0("invalidate_caches")?;
+		// Deepest first, so that a module picks up the new code of the submodules it imports.
+		for submodule in loaded.iter().rev() {
+			importlib.call_method1("reload", (*submodule,))?;
+		}
+		let module: &PyModule = importlib.call_method1("reload", (module,))?.downcast()?;
+		log::info!("Reloaded module {}", name);
+		// Reloading may have imported new submodules.
+		let modified = sources_modified(module, &submodules(py, name)?)?;
+		self.source_modified.lock().unwrap().insert(name.to_string(), modified);
+		Ok(module)
+	}
+}
+
+/// Name of the module loaded from the file of a runnable. Characters other than ASCII letters
+/// and digits are escaped, so that two runnables never share a module.
+fn module_name(runnable_id: &str) -> String {
+	let mut name = format!("{}.", WORKFLOW_MODULES_PACKAGE);
+	for byte in runnable_id.bytes() {
+		match byte {
+			b'_' => name.push_str("__"),
+			byte if byte.is_ascii_alphanumeric() => name.push(byte as char),
+			byte => name.push_str(&format!("_{:02x}", byte)),
+		}
+	}
+	name
+}
+
+/// Loaded submodules of a package, from `sys.modules`, sorted by name.
+fn submodules<'py>(py: Python<'py>, package: &str) -> PyResult<Vec<&'py PyModule>> {
+	let prefix = format!("{}.", package);
+	let mut submodules: Vec<(String, &PyModule)> = py
+		.import("sys")?
+		.getattr("modules")?
+		.downcast::<PyDict>()?
+		.iter()
+		.filter_map(|(key, value)| {
+			let key: String = key.extract().ok()?;
+			let module = value.downcast::<PyModule>().ok()?;
+			key.starts_with(&prefix).then_some((key, module))
+		})
+		.collect();
+	submodules.sort_by(|(a, _), (b, _)| a.cmp(b));
+	Ok(submodules.into_iter().map(|(_, module)| module).collect())
+}
+
+/// Modification times of the source files of a module and its submodules, keyed by module
+/// name. Modules without a source file are left out.
+fn sources_modified(
+	module: &PyModule,
+	submodules: &[&PyModule],
+) -> PyResult<BTreeMap<String, SystemTime>> {
+	let mut modified = BTreeMap::new();
+	for module in std::iter::once(module).chain(submodules.iter().copied()) {
+		if let Some(time) = source_modified(module) {
+			modified.insert(module.name()?.to_string(), time);
+		}
+	}
+	Ok(modified)
+}
+
+/// Modification time of the source file of a module, if it has one.
+fn source_modified(module: &PyModule) -> Option<SystemTime> {
+	let file: String = module.getattr("__file__").ok()?.extract().ok()?;
+	std::fs::metadata(file).ok()?.modified().ok()
+}
@@ -0,0 +1,119 @@
+use crate::{cross::CLRepr, querent::QuerentError};
+
+use super::WorkflowStatus;
+use std::{
+	collections::{hash_map::Iter, HashMap},
+	time::Duration,
+};
+
+/// Outcome of a single workflow run.
+#[derive(Debug)]
+pub struct WorkflowReport {
+	/// Unique identifier of the workflow.
+	pub workflow_id: String,
+	/// Wall-clock time spent running the workflow, including retries.
+	pub duration: Duration,
+	/// Number of attempts made, counting from 1. Zero if the workflow never started.
+	pub attempts: u32,
+	/// Status the workflow ended in.
+	pub status: WorkflowStatus,
+	/// Value returned by the Python entry point, or the error that stopped it.
+	pub result: Result<CLRepr, QuerentError>,
+}
+
+impl WorkflowReport {
+	/// Returns true if the workflow finished without an error.
+	pub fn is_success(&self) -> bool {
+		self.result.is_ok()
+	}
+
+	/// Returns the value returned by the workflow, if it succeeded.
+	pub fn output(&self) -> Option<&CLRepr> {
+		self.result.as_ref().ok()
+	}
+
+	/// Returns the error of the workflow, if it failed.
+	pub fn error(&self) -> Option<&QuerentError> {
+		self.result.as_ref().err()
+	}
+}
+
+/// Per-workflow outcomes of a `start_workflows` call, keyed by workflow id.
+#[derive(Debug, Default)]
+pub struct StartReport {
+	reports: HashMap<String, WorkflowReport>,
+}
+
+impl StartReport {
+	/// Creates an empty report.
+	pub fn new() -> Self {
+		Default::default()
+	}
+
+	/// Records the outcome of a workflow, replacing any previous one for the same id.
+	pub fn insert(&mut self, report: WorkflowReport) {
+		self.reports.insert(report.workflow_id.clone(), report);
+	}
+
+	/// Returns the outcome of the given workflow.
+	pub fn get(&self, workflow_id: &str) -> Option<&WorkflowReport> {
+		self.reports.get(workflow_id)
+	}
+
+	/// Returns true if every workflow in the report succeeded.
+	pub fn is_success(&self) -> bool {
+		self.reports.values().all(WorkflowReport::is_success)
+	}
+
+	/// Returns the ids of the workflows that succeeded.
+	pub fn succeeded(&self) -> Vec<&str> {
+		self.reports
+			.values()
+			.filter(|r| r.is_success())
+			.map(|r| r.workflow_id.as_str())
+			.collect()
+	}
+
+	/// Returns the ids of the workflows that were skipped because an upstream workflow did
+	/// not complete.
+	pub fn skipped(&self) -> Vec<&str> {
+		self.reports
+			.values()
+			.filter(|r| r.status == WorkflowStatus::Skipped)
+			.map(|r| r.workflow_id.as_str())
+			.collect()
+	}
+
+	/// Returns the ids of the workflows that failed or were cancelled.
+	pub fn failed(&self) -> Vec<&str> {
+		self.reports
+			.values()
+			.filter(|r| !r.is_success() && r.status != WorkflowStatus::Skipped)
+			.map(|r| r.workflow_id.as_str())
+			.collect()
+	}
+
+	/// Number of workflows in the report.
+	pub fn len(&self) -> usize {
+		self.reports.len()
+	}
+
+	/// Returns true if no workflow was run.
+	pub fn is_empty(&self) -> bool {
+		self.reports.is_empty()
+	}
+
+	/// Iterates over the outcomes, keyed by workflow id.
+	pub fn iter(&self) -> Iter<'_, String, WorkflowReport> {
+		self.reports.iter()
+	}
+}
+
+impl IntoIterator for StartReport {
+	type Item = (String, WorkflowReport);
+	type IntoIter = std::collections::hash_map::IntoIter<String, WorkflowReport>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.reports.into_iter()
+	}
+}
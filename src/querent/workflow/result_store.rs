@@ -0,0 +1,261 @@
+use crate::cross::CLRepr;
+use std::{
+	collections::HashMap,
+	sync::Mutex,
+	time::{Duration, Instant, SystemTime},
+};
+use tokio::sync::Notify;
+
+use super::{WorkflowReport, WorkflowStatus};
+
+/// Number of results kept by default.
+const DEFAULT_RESULT_CAPACITY: usize = 1024;
+
+/// Result of the most recent finished run of a workflow.
+#[derive(Debug, Clone)]
+pub struct WorkflowResult {
+	/// Unique identifier of the workflow.
+	pub workflow_id: String,
+	/// Status the run ended in.
+	pub status: WorkflowStatus,
+	/// Number of attempts made, counting from 1.
+	pub attempts: u32,
+	/// Wall-clock time spent running the workflow, including retries.
+	pub duration: Duration,
+	/// When the run finished.
+	pub finished_at: SystemTime,
+	/// Value returned by the Python entry point, if the run completed.
+	pub output: Option<CLRepr>,
+	/// Error that stopped the run, if it did not complete.
+	pub error: Option<String>,
+}
+
+impl From<&WorkflowReport> for WorkflowResult {
+	fn from(report: &WorkflowReport) -> Self {
+		WorkflowResult {
+			workflow_id: report.workflow_id.clone(),
+			status: report.status,
+			attempts: report.attempts,
+			duration: report.duration,
+			finished_at: SystemTime::now(),
+			output: report.output().cloned(),
+			error: report.error().map(|e| e.to_string()),
+		}
+	}
+}
+
+/// Which result is dropped when the store is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+	/// Drops the result that was read or written least recently.
+	#[default]
+	LeastRecentlyUsed,
+	/// Drops the result that was written first.
+	OldestFirst,
+}
+
+struct StoredResult {
+	result: WorkflowResult,
+	inserted_at: Instant,
+	/// Logical clock of the last write, or of the last read with `LeastRecentlyUsed`.
+	last_used: u64,
+}
+
+#[derive(Default)]
+struct ResultStoreInner {
+	results: HashMap<String, StoredResult>,
+	clock: u64,
+}
+
+/// Bounded store of the latest result of every workflow.
+pub struct ResultStore {
+	capacity: usize,
+	eviction: EvictionPolicy,
+	ttl: Option<Duration>,
+	inner: Mutex<ResultStoreInner>,
+	inserted: Notify,
+}
+
+impl Default for ResultStore {
+	fn default() -> Self {
+		ResultStore::new(DEFAULT_RESULT_CAPACITY, EvictionPolicy::default())
+	}
+}
+
+impl ResultStore {
+	/// Creates a store keeping at most `capacity` results.
+	pub fn new(capacity: usize, eviction: EvictionPolicy) -> Self {
+		ResultStore {
+			capacity: capacity.max(1),
+			eviction,
+			ttl: None,
+			inner: Mutex::new(ResultStoreInner::default()),
+			inserted: Notify::new(),
+		}
+	}
+
+	/// Drops results once they are older than `ttl`.
+	pub fn ttl(mut self, ttl: Duration) -> Self {
+		self.ttl = Some(ttl);
+		self
+	}
+
+	/// Stores the result of a run, replacing the previous result of the same workflow and
+	/// evicting another one if the store is full.
+	pub fn insert(&self, result: WorkflowResult) {
+		let mut inner = self.inner.lock().unwrap();
+		self.purge_expired(&mut inner);
+		inner.clock += 1;
+		let stored = StoredResult { result, inserted_at: Instant::now(), last_used: inner.clock };
+		inner.results.insert(stored.result.workflow_id.clone(), stored);
+		while inner.results.len() > self.capacity {
+			let victim = inner
+				.results
+				.iter()
+				.min_by_key(|(_, stored)| stored.last_used)
+				.map(|(id, _)| id.clone());
+			match victim {
+				Some(id) => {
+					log::debug!("Evicting result of workflow {}", id);
+					inner.results.remove(&id);
+				},
+				None => break,
+			}
+		}
+		drop(inner);
+		self.inserted.notify_waiters();
+	}
+
+	/// Returns the latest result of the given workflow, if it is still stored.
+	pub fn get(&self, workflow_id: &str) -> Option<WorkflowResult> {
+		let mut inner = self.inner.lock().unwrap();
+		self.purge_expired(&mut inner);
+		inner.clock += 1;
+		let clock = inner.clock;
+		let stored = inner.results.get_mut(workflow_id)?;
+		if self.eviction == EvictionPolicy::LeastRecentlyUsed {
+			stored.last_used = clock;
+		}
+		Some(stored.result.clone())
+	}
+
+	/// Waits until a result of the given workflow is stored and returns it.
+	///
+	/// `pending` tells whether a run that will store a result is still in progress. Once it
+	/// returns false and no result is stored, because the workflow never ran or its result was
+	/// evicted or expired, `None` is returned rather than waiting forever.
+	pub async fn wait(
+		&self,
+		workflow_id: &str,
+		pending: impl Fn() -> bool,
+	) -> Option<WorkflowResult> {
+		loop {
+			let inserted = self.inserted.notified();
+			tokio::pin!(inserted);
+			// Registers the waiter before checking, so an insert in between is not missed.
+			inserted.as_mut().enable();
+			if let Some(result) = self.get(workflow_id) {
+				return Some(result);
+			}
+			if !pending() {
+				return None;
+			}
+			inserted.await;
+		}
+	}
+
+	/// Wakes the waiters up, so that they check again whether a result is still pending.
+	pub fn wake_waiters(&self) {
+		self.inserted.notify_waiters();
+	}
+
+	/// Removes the result of the given workflow.
+	pub fn remove(&self, workflow_id: &str) -> Option<WorkflowResult> {
+		let mut inner = self.inner.lock().unwrap();
+		inner.results.remove(workflow_id).map(|stored| stored.result)
+	}
+
+	/// Number of stored results.
+	pub fn len(&self) -> usize {
+		let mut inner = self.inner.lock().unwrap();
+		self.purge_expired(&mut inner);
+		inner.results.len()
+	}
+
+	/// Returns true if no result is stored.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	fn purge_expired(&self, inner: &mut ResultStoreInner) {
+		if let Some(ttl) = self.ttl {
+			inner.results.retain(|_, stored| stored.inserted_at.elapsed() < ttl);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::Arc;
+
+	fn result(workflow_id: &str, value: i64) -> WorkflowResult {
+		WorkflowResult {
+			workflow_id: workflow_id.to_string(),
+			status: WorkflowStatus::Completed,
+			attempts: 1,
+			duration: Duration::ZERO,
+			finished_at: SystemTime::now(),
+			output: Some(CLRepr::Int(value)),
+			error: None,
+		}
+	}
+
+	#[test]
+	fn result_store_should_evict_according_to_policy() {
+		let lru = ResultStore::new(2, EvictionPolicy::LeastRecentlyUsed);
+		lru.insert(result("a", 1));
+		lru.insert(result("b", 2));
+		assert!(lru.get("a").is_some());
+		lru.insert(result("c", 3));
+		assert!(lru.get("a").is_some());
+		assert!(lru.get("b").is_none());
+
+		let fifo = ResultStore::new(2, EvictionPolicy::OldestFirst);
+		fifo.insert(result("a", 1));
+		fifo.insert(result("b", 2));
+		assert!(fifo.get("a").is_some());
+		fifo.insert(result("c", 3));
+		assert!(fifo.get("a").is_none());
+		assert_eq!(fifo.len(), 2);
+
+		fifo.insert(result("b", 4));
+		assert!(matches!(fifo.get("b").unwrap().output, Some(CLRepr::Int(4))));
+		assert_eq!(fifo.len(), 2);
+	}
+
+	#[test]
+	fn result_store_should_expire_results() {
+		let store = ResultStore::new(10, EvictionPolicy::default()).ttl(Duration::from_millis(20));
+		store.insert(result("a", 1));
+		assert!(store.get("a").is_some());
+		std::thread::sleep(Duration::from_millis(30));
+		assert!(store.get("a").is_none());
+		assert!(store.is_empty());
+	}
+
+	#[tokio::test]
+	async fn result_store_should_wake_waiters() {
+		let store = Arc::new(ResultStore::default());
+		let waiter = tokio::spawn({
+			let store = store.clone();
+			async move { store.wait("a", || true).await }
+		});
+		tokio::time::sleep(Duration::from_millis(10)).await;
+		store.insert(result("b", 1));
+		store.insert(result("a", 2));
+		let received = tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+		assert!(matches!(received.unwrap().output, Some(CLRepr::Int(2))));
+		assert!(store.wait("c", || false).await.is_none());
+	}
+}
@@ -0,0 +1,156 @@
+use crate::querent::QuerentError;
+use std::{
+	collections::hash_map::RandomState,
+	hash::{BuildHasher, Hasher},
+	time::Duration,
+};
+
+/// Describes when and how often a failed workflow is started again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+	/// Maximum number of attempts, including the first one.
+	pub max_attempts: u32,
+	/// Delay before the second attempt.
+	pub initial_backoff: Duration,
+	/// Upper bound for the delay between two attempts.
+	pub max_backoff: Duration,
+	/// Factor applied to the delay after every failed attempt.
+	pub multiplier: f64,
+	/// Fraction of the delay, between 0 and 1, randomly added or removed to spread retries.
+	pub jitter: f64,
+	/// Python exception classes that are retried. A failure is retried when one of these
+	/// names is the raised exception or one of its base classes. Empty retries any failure.
+	pub retry_on: Vec<String>,
+	/// Whether a run that hit its timeout is retried.
+	pub retry_on_timeout: bool,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		RetryPolicy {
+			max_attempts: 3,
+			initial_backoff: Duration::from_secs(1),
+			max_backoff: Duration::from_secs(60),
+			multiplier: 2.0,
+			jitter: 0.2,
+			retry_on: Vec::new(),
+			retry_on_timeout: false,
+		}
+	}
+}
+
+impl RetryPolicy {
+	/// Creates a policy allowing `max_attempts` attempts with the default backoff.
+	pub fn new(max_attempts: u32) -> Self {
+		RetryPolicy { max_attempts, ..Default::default() }
+	}
+
+	/// Sets the delay before the second attempt and the upper bound of the delay.
+	pub fn backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+		self.initial_backoff = initial_backoff;
+		self.max_backoff = max_backoff;
+		self
+	}
+
+	/// Sets the factor applied to the delay after every failed attempt.
+	pub fn multiplier(mut self, multiplier: f64) -> Self {
+		self.multiplier = multiplier;
+		self
+	}
+
+	/// Sets the random fraction of the delay, clamped between 0 and 1.
+	pub fn jitter(mut self, jitter: f64) -> Self {
+		self.jitter = jitter.clamp(0.0, 1.0);
+		self
+	}
+
+	/// Only retries failures raising the given Python exception class or a subclass of it.
+	pub fn retry_on(mut self, exception: &str) -> Self {
+		self.retry_on.push(exception.to_string());
+		self
+	}
+
+	/// Sets whether a run that hit its timeout is retried.
+	pub fn retry_on_timeout(mut self, retry_on_timeout: bool) -> Self {
+		self.retry_on_timeout = retry_on_timeout;
+		self
+	}
+
+	/// Returns true if the failure of the given attempt, counting from 1, is retried.
+	pub fn should_retry(&self, attempt: u32, error: &QuerentError) -> bool {
+		if attempt >= self.max_attempts || error.is_cancelled() {
+			return false;
+		}
+		if error.is_timeout() {
+			return self.retry_on_timeout;
+		}
+		self.retry_on.is_empty() ||
+			self.retry_on.iter().any(|exception| error.is_python_exception(exception))
+	}
+
+	/// Delay to wait after the failure of the given attempt, counting from 1.
+	pub fn delay(&self, attempt: u32) -> Duration {
+		let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+		let base = self.initial_backoff.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+		let base = base.min(self.max_backoff.as_secs_f64());
+		// A random value in [-1, 1), good enough to spread retries without a rand dependency.
+		let random =
+			RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64 * 2.0 - 1.0;
+		let jittered = base * (1.0 + self.jitter.clamp(0.0, 1.0) * random);
+		Duration::from_secs_f64(jittered.clamp(0.0, self.max_backoff.as_secs_f64()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashMap;
+
+	fn python_error(exception: &str, mro: &str) -> QuerentError {
+		QuerentError::internal_with_meta(
+			"Python error".to_string(),
+			HashMap::from([
+				("python_exception".to_string(), exception.to_string()),
+				("python_exception_mro".to_string(), mro.to_string()),
+			]),
+		)
+	}
+
+	#[test]
+	fn retry_policy_should_grow_delay_up_to_the_maximum() {
+		let policy = RetryPolicy::new(10)
+			.backoff(Duration::from_millis(100), Duration::from_millis(500))
+			.jitter(0.0);
+		assert_eq!(policy.delay(1), Duration::from_millis(100));
+		assert_eq!(policy.delay(2), Duration::from_millis(200));
+		assert_eq!(policy.delay(3), Duration::from_millis(400));
+		assert_eq!(policy.delay(4), Duration::from_millis(500));
+
+		let jittered = RetryPolicy::new(10)
+			.backoff(Duration::from_millis(100), Duration::from_secs(1))
+			.jitter(0.5);
+		for _ in 0..20 {
+			let delay = jittered.delay(1);
+			assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+		}
+	}
+
+	#[test]
+	fn retry_policy_should_match_exception_types() {
+		let policy = RetryPolicy::new(3).retry_on("ConnectionError");
+		let refused = python_error(
+			"ConnectionRefusedError",
+			"ConnectionRefusedError,ConnectionError,OSError,Exception,BaseException,object",
+		);
+		let value = python_error("ValueError", "ValueError,Exception,BaseException,object");
+		assert!(policy.should_retry(1, &refused));
+		assert!(!policy.should_retry(3, &refused));
+		assert!(!policy.should_retry(1, &value));
+		assert!(!policy.should_retry(1, &QuerentError::cancelled("stop".to_string())));
+		assert!(!policy.should_retry(1, &QuerentError::timeout("slow".to_string())));
+		assert!(RetryPolicy::new(3).should_retry(1, &value));
+		assert!(RetryPolicy::new(3)
+			.retry_on_timeout(true)
+			.should_retry(1, &QuerentError::timeout("slow".to_string())));
+	}
+}
@@ -0,0 +1,200 @@
+use crate::{
+	config::{Config, Neo4jQueryConfig},
+	cross::CLRepr,
+	querent::{ConfigInjection, TaskPriority, WorkerClaim},
+};
+use std::{
+	collections::{BTreeMap, HashMap},
+	path::Path,
+	time::{Duration, SystemTime},
+};
+
+use super::{RetryPolicy, WorkflowDependency};
+
+/// Configuration injected into a Python entry point.
+#[derive(Debug, Clone, Default)]
+pub enum RunnableConfig {
+	/// Nothing is injected.
+	#[default]
+	None,
+	/// A workflow `Config`.
+	Config(Config),
+	/// A `Neo4jQueryConfig` for query engines.
+	Query(Neo4jQueryConfig),
+}
+
+impl RunnableConfig {
+	/// Splits the configuration into the slots expected by `PyRuntime::call_async`.
+	pub fn into_call_slots(self) -> (Option<Config>, Option<Neo4jQueryConfig>) {
+		match self {
+			RunnableConfig::None => (None, None),
+			RunnableConfig::Config(config) => (Some(config), None),
+			RunnableConfig::Query(config) => (None, Some(config)),
+		}
+	}
+
+	/// Workers a run with this configuration takes, as limited by its `ResourceConfig`.
+	pub fn worker_claim(&self) -> WorkerClaim {
+		match self {
+			RunnableConfig::Config(config) => WorkerClaim::from_config(config),
+			_ => WorkerClaim::default(),
+		}
+	}
+
+	/// Stable hash of the declarative fields of the configuration, used to tell runs with
+	/// different configurations apart. Channels, event handlers and passwords are left out.
+	pub fn fingerprint(&self) -> Option<String> {
+		let mut hasher = Fnv1a::default();
+		match self {
+			RunnableConfig::None => return None,
+			RunnableConfig::Config(config) => {
+				hasher.write("config");
+				hasher.write(&config.version.to_string());
+				hasher.write(&config.querent_id);
+				hasher.write(&config.querent_name);
+				hasher.write(&config.workflow.id);
+				hasher.write(&config.workflow.name);
+				hasher.write_map(&config.workflow.config);
+				for collector in &config.collectors {
+					hasher.write(&collector.id);
+					hasher.write(&collector.name);
+					hasher.write(&collector.backend);
+					hasher.write_map(&collector.config);
+				}
+				for engine in &config.engines {
+					hasher.write(&engine.id);
+					hasher.write(&engine.name);
+					hasher.write_map(&engine.config);
+				}
+				if let Some(resource) = &config.resource {
+					hasher.write(&format!("{:?}", resource));
+				}
+			},
+			RunnableConfig::Query(config) => {
+				hasher.write("query");
+				hasher.write(&config.db_name);
+				hasher.write(&config.url);
+				hasher.write(&config.username);
+			},
+		}
+		Some(format!("{:016x}", hasher.0))
+	}
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is guaranteed to stay the same across Rust
+/// releases.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+	fn default() -> Self {
+		Fnv1a(0xcbf2_9ce4_8422_2325)
+	}
+}
+
+impl Fnv1a {
+	fn write(&mut self, value: &str) {
+		// A separator keeps ("ab", "c") and ("a", "bc") apart.
+		for byte in value.bytes().chain(std::iter::once(0xff)) {
+			self.0 ^= u64::from(byte);
+			self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+		}
+	}
+
+	fn write_map(&mut self, map: &std::collections::HashMap<String, String>) {
+		for (key, value) in map.iter().collect::<BTreeMap<_, _>>() {
+			self.write(key);
+			self.write(value);
+		}
+	}
+}
+
+/// A Python entry point plus the configuration injected into it.
+///
+/// Anything implementing `Runnable` can be registered with a `RunnableManager`, which takes
+/// care of starting, tracking, cancelling and retrying it and of keeping its result. Only
+/// the entry point and its arguments are required; scheduling options default to none.
+pub trait Runnable: Clone + Send + Sync + 'static {
+	/// Unique identifier.
+	fn id(&self) -> &str;
+
+	/// Human readable name, also used as the name of modules compiled from `code`.
+	fn name(&self) -> &str;
+
+	/// Python module to import.
+	fn import(&self) -> &str;
+
+	/// Attribute of the Python module containing the start function.
+	fn attr(&self) -> &str;
+
+	/// Optional Python code to execute instead of importing a module.
+	fn code(&self) -> Option<&str>;
+
+	/// Optional Python file or package directory to load instead of importing a module.
+	/// Ignored when `code` is set.
+	fn path(&self) -> Option<&Path> {
+		None
+	}
+
+	/// Arguments passed to the start function after the injected configuration.
+	fn arguments(&self) -> &[CLRepr];
+
+	/// Appends an argument, used to pass the results of upstream runnables.
+	fn push_argument(&mut self, argument: CLRepr);
+
+	/// Keyword arguments passed to the start function.
+	fn kwargs(&self) -> HashMap<String, CLRepr> {
+		HashMap::new()
+	}
+
+	/// Configuration injected into the start function.
+	fn config(&self) -> RunnableConfig {
+		RunnableConfig::None
+	}
+
+	/// How the configuration is passed to the start function, as the first positional
+	/// argument by default.
+	fn config_injection(&self) -> ConfigInjection {
+		ConfigInjection::Positional
+	}
+
+	/// Maximum time a single run may take before it is cancelled.
+	fn timeout(&self) -> Option<Duration> {
+		None
+	}
+
+	/// Point in time after which a run is cancelled, regardless of when it started.
+	fn deadline(&self) -> Option<SystemTime> {
+		None
+	}
+
+	/// Policy to start the runnable again when it fails.
+	fn retry(&self) -> Option<&RetryPolicy> {
+		None
+	}
+
+	/// Runnables that must complete before this one starts.
+	fn dependencies(&self) -> &[WorkflowDependency] {
+		&[]
+	}
+
+	/// Labels `LabelSelector`s select the runnable by.
+	fn labels(&self) -> HashMap<String, String> {
+		HashMap::new()
+	}
+
+	/// Priority of the runnable's calls in the queue of the Python runtime.
+	fn priority(&self) -> TaskPriority {
+		TaskPriority::Normal
+	}
+
+	/// Time left for a run starting now, combining the timeout and the deadline.
+	fn time_budget(&self) -> Option<Duration> {
+		let until_deadline = self
+			.deadline()
+			.map(|deadline| deadline.duration_since(SystemTime::now()).unwrap_or_default());
+		match (self.timeout(), until_deadline) {
+			(Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+			(timeout, remaining) => timeout.or(remaining),
+		}
+	}
+}
@@ -0,0 +1,264 @@
+use crate::{querent::QuerentError, tokio_runtime, util::cron::CronExpression};
+use serde::Deserialize;
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex},
+	time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::{Runnable, RunnableManager};
+
+/// Number of runs waiting behind a run in progress with `OverlapPolicy::Queue`. Further
+/// occurrences are skipped.
+const QUEUED_RUNS_CAPACITY: usize = 16;
+
+/// When a scheduled workflow runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+	/// At every occurrence of a cron expression, evaluated in UTC.
+	Cron(CronExpression),
+	/// At a fixed interval, the first run one interval after scheduling.
+	Interval(Duration),
+}
+
+/// What happens when a workflow is due while a previous run is still in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapPolicy {
+	/// The occurrence is skipped.
+	#[default]
+	Skip,
+	/// The run starts once the previous runs finished.
+	Queue,
+	/// The run starts right away, alongside the previous one.
+	Parallel,
+}
+
+/// Recurring schedule of a workflow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+	/// When the workflow runs.
+	pub trigger: Trigger,
+	/// What happens when a run is due while the previous one is still in progress.
+	pub overlap: OverlapPolicy,
+}
+
+impl Schedule {
+	/// Runs at every occurrence of a five field cron expression.
+	pub fn cron(expression: &str) -> Result<Self, QuerentError> {
+		let expression = CronExpression::parse(expression).map_err(QuerentError::user)?;
+		Ok(Schedule { trigger: Trigger::Cron(expression), overlap: OverlapPolicy::default() })
+	}
+
+	/// Runs every `interval`. A zero interval would start runs back to back, so it is
+	/// rejected.
+	pub fn every(interval: Duration) -> Result<Self, QuerentError> {
+		if interval.is_zero() {
+			return Err(QuerentError::user(
+				"Schedule interval must be longer than zero".to_string(),
+			));
+		}
+		Ok(Schedule { trigger: Trigger::Interval(interval), overlap: OverlapPolicy::default() })
+	}
+
+	/// Sets what happens when a run is due while the previous one is still in progress.
+	pub fn overlap(mut self, overlap: OverlapPolicy) -> Self {
+		self.overlap = overlap;
+		self
+	}
+
+	/// Next time the workflow is due after `now`, given the previous due time.
+	fn next_due(&self, now: SystemTime, previous: Option<SystemTime>) -> Option<SystemTime> {
+		match &self.trigger {
+			Trigger::Cron(expression) => expression.next_after(now),
+			// Counting from the previous due time keeps the interval from drifting.
+			Trigger::Interval(interval) => Some((previous.unwrap_or(now) + *interval).max(now)),
+		}
+	}
+}
+
+/// A scheduled workflow and how its schedule went so far.
+#[derive(Debug, Clone)]
+pub struct ScheduledWorkflow {
+	/// Unique identifier of the workflow.
+	pub workflow_id: String,
+	/// Schedule of the workflow.
+	pub schedule: Schedule,
+	/// When the workflow is due next, `None` if it will not run again.
+	pub next_run: Option<SystemTime>,
+	/// When the workflow was last due.
+	pub last_run: Option<SystemTime>,
+	/// Number of runs started by the schedule.
+	pub runs_started: u64,
+	/// Number of occurrences skipped because a previous run was still in progress.
+	pub runs_skipped: u64,
+}
+
+/// Starts workflows of a `RunnableManager` according to their schedule.
+///
+/// Every schedule is driven by a task on the crate's `tokio_runtime()`.
+pub struct Scheduler<R: Runnable> {
+	manager: Arc<RunnableManager<R>>,
+	entries: Arc<Mutex<HashMap<String, ScheduledWorkflow>>>,
+	/// Stops the task driving each schedule.
+	tasks: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl<R: Runnable> Scheduler<R> {
+	/// Creates a scheduler starting workflows of the given manager.
+	pub fn new(manager: Arc<RunnableManager<R>>) -> Self {
+		Scheduler {
+			manager,
+			entries: Arc::new(Mutex::new(HashMap::new())),
+			tasks: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Schedules a registered workflow, replacing its previous schedule.
+	pub fn schedule(&self, workflow_id: &str, schedule: Schedule) -> Result<(), QuerentError> {
+		if !self.manager.workflows.lock().unwrap().contains_key(workflow_id) {
+			return Err(QuerentError::user(format!("Workflow {} is not registered", workflow_id)));
+		}
+		let runtime = tokio_runtime()?;
+		self.unschedule(workflow_id);
+
+		let entry = ScheduledWorkflow {
+			workflow_id: workflow_id.to_string(),
+			next_run: schedule.next_due(SystemTime::now(), None),
+			schedule: schedule.clone(),
+			last_run: None,
+			runs_started: 0,
+			runs_skipped: 0,
+		};
+		self.entries.lock().unwrap().insert(workflow_id.to_string(), entry);
+		let stop = CancellationToken::new();
+		self.tasks.lock().unwrap().insert(workflow_id.to_string(), stop.clone());
+		log::info!("Scheduling workflow {}: {:?}", workflow_id, schedule);
+		runtime.spawn(drive_schedule(
+			self.manager.clone(),
+			self.entries.clone(),
+			workflow_id.to_string(),
+			schedule,
+			stop,
+		));
+		Ok(())
+	}
+
+	/// Removes the schedule of a workflow. Runs in progress are not cancelled. Returns false
+	/// if the workflow was not scheduled.
+	pub fn unschedule(&self, workflow_id: &str) -> bool {
+		let task = self.tasks.lock().unwrap().remove(workflow_id);
+		self.entries.lock().unwrap().remove(workflow_id);
+		match task {
+			Some(stop) => {
+				stop.cancel();
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Returns when the given workflow is due next.
+	pub fn next_run(&self, workflow_id: &str) -> Option<SystemTime> {
+		self.entries.lock().unwrap().get(workflow_id).and_then(|entry| entry.next_run)
+	}
+
+	/// Returns the given scheduled workflow.
+	pub fn get(&self, workflow_id: &str) -> Option<ScheduledWorkflow> {
+		self.entries.lock().unwrap().get(workflow_id).cloned()
+	}
+
+	/// Returns every scheduled workflow.
+	pub fn list(&self) -> Vec<ScheduledWorkflow> {
+		self.entries.lock().unwrap().values().cloned().collect()
+	}
+}
+
+impl<R: Runnable> Drop for Scheduler<R> {
+	/// Stops every schedule; runs in progress are not cancelled.
+	fn drop(&mut self) {
+		for stop in self.tasks.lock().unwrap().values() {
+			stop.cancel();
+		}
+	}
+}
+
+/// Waits for every occurrence of a schedule and starts the workflow according to the
+/// overlap policy, until `stop` is cancelled.
+async fn drive_schedule<R: Runnable>(
+	manager: Arc<RunnableManager<R>>,
+	entries: Arc<Mutex<HashMap<String, ScheduledWorkflow>>>,
+	workflow_id: String,
+	schedule: Schedule,
+	stop: CancellationToken,
+) {
+	let queue = match schedule.overlap {
+		OverlapPolicy::Queue => {
+			let (queue, mut queued) = mpsc::channel::<()>(QUEUED_RUNS_CAPACITY);
+			let manager = manager.clone();
+			let workflow_id = workflow_id.clone();
+			tokio::spawn(async move {
+				// Ends when the schedule stops and drops the sender.
+				while queued.recv().await.is_some() {
+					log_run_error(&workflow_id, manager.start_workflow(&workflow_id).await);
+				}
+			});
+			Some(queue)
+		},
+		_ => None,
+	};
+	let update = |f: &dyn Fn(&mut ScheduledWorkflow)| {
+		if let Some(entry) = entries.lock().unwrap().get_mut(&workflow_id) {
+			f(entry);
+		}
+	};
+
+	let mut previous = None;
+	loop {
+		let Some(due) = schedule.next_due(SystemTime::now(), previous) else {
+			log::info!("Schedule of workflow {} has no further occurrence", workflow_id);
+			update(&|entry| entry.next_run = None);
+			break;
+		};
+		update(&|entry| entry.next_run = Some(due));
+		let wait = due.duration_since(SystemTime::now()).unwrap_or_default();
+		tokio::select! {
+			_ = tokio::time::sleep(wait) => {},
+			_ = stop.cancelled() => break,
+		}
+		previous = Some(due);
+
+		let started = match (&queue, schedule.overlap) {
+			(_, OverlapPolicy::Skip) if manager.is_running(&workflow_id) => false,
+			(Some(queue), _) => queue.try_send(()).is_ok(),
+			_ => {
+				let manager = manager.clone();
+				let workflow_id = workflow_id.clone();
+				tokio::spawn(async move {
+					log_run_error(&workflow_id, manager.start_workflow(&workflow_id).await);
+				});
+				true
+			},
+		};
+		if started {
+			update(&|entry| {
+				entry.last_run = Some(due);
+				entry.runs_started += 1;
+			});
+		} else {
+			log::warn!("Skipping scheduled run of workflow {}, it is still running", workflow_id);
+			update(&|entry| {
+				entry.last_run = Some(due);
+				entry.runs_skipped += 1;
+			});
+		}
+	}
+}
+
+fn log_run_error<T>(workflow_id: &str, result: Result<T, QuerentError>) {
+	if let Err(e) = result {
+		log::error!("Unable to start scheduled workflow {}: {}", workflow_id, e);
+	}
+}
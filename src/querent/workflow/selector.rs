@@ -0,0 +1,277 @@
+use crate::querent::QuerentError;
+use std::{
+	collections::{BTreeSet, HashMap},
+	fmt,
+	str::FromStr,
+};
+
+/// Condition a single label has to meet for a `LabelSelector` to match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelRequirement {
+	/// `key=value` or `key==value`.
+	Equals(String, String),
+	/// `key!=value`, also met when the label is missing.
+	NotEquals(String, String),
+	/// `key in (a, b)`.
+	In(String, BTreeSet<String>),
+	/// `key notin (a, b)`, also met when the label is missing.
+	NotIn(String, BTreeSet<String>),
+	/// `key`, the label is set whatever its value.
+	Exists(String),
+	/// `!key`, the label is not set.
+	DoesNotExist(String),
+}
+
+impl LabelRequirement {
+	/// Returns true if the labels meet the requirement.
+	pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+		match self {
+			LabelRequirement::Equals(key, value) => labels.get(key) == Some(value),
+			LabelRequirement::NotEquals(key, value) => labels.get(key) != Some(value),
+			LabelRequirement::In(key, values) =>
+				labels.get(key).map_or(false, |value| values.contains(value)),
+			LabelRequirement::NotIn(key, values) =>
+				labels.get(key).map_or(true, |value| !values.contains(value)),
+			LabelRequirement::Exists(key) => labels.contains_key(key),
+			LabelRequirement::DoesNotExist(key) => !labels.contains_key(key),
+		}
+	}
+}
+
+impl fmt::Display for LabelRequirement {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let set = |values: &BTreeSet<String>| values.iter().cloned().collect::<Vec<_>>().join(", ");
+		match self {
+			LabelRequirement::Equals(key, value) => write!(f, "{}={}", key, value),
+			LabelRequirement::NotEquals(key, value) => write!(f, "{}!={}", key, value),
+			LabelRequirement::In(key, values) => write!(f, "{} in ({})", key, set(values)),
+			LabelRequirement::NotIn(key, values) => write!(f, "{} notin ({})", key, set(values)),
+			LabelRequirement::Exists(key) => write!(f, "{}", key),
+			LabelRequirement::DoesNotExist(key) => write!(f, "!{}", key),
+		}
+	}
+}
+
+/// Selects workflows by their labels, in the syntax of Kubernetes label selectors.
+///
+/// Requirements are separated by commas and must all be met, e.g.
+/// `tier=nightly, env in (prod, staging), !experimental`. The empty selector matches every
+/// workflow.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+	requirements: Vec<LabelRequirement>,
+}
+
+impl LabelSelector {
+	/// Creates a selector matching every workflow.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a requirement the labels have to meet.
+	pub fn require(mut self, requirement: LabelRequirement) -> Self {
+		self.requirements.push(requirement);
+		self
+	}
+
+	/// Requires the label `key` to be set to `value`.
+	pub fn equals(self, key: &str, value: &str) -> Self {
+		self.require(LabelRequirement::Equals(key.to_string(), value.to_string()))
+	}
+
+	/// Requirements of the selector.
+	pub fn requirements(&self) -> &[LabelRequirement] {
+		&self.requirements
+	}
+
+	/// Returns true if the labels meet every requirement.
+	pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+		self.requirements.iter().all(|requirement| requirement.matches(labels))
+	}
+}
+
+impl fmt::Display for LabelSelector {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let requirements: Vec<String> = self.requirements.iter().map(|r| r.to_string()).collect();
+		write!(f, "{}", requirements.join(", "))
+	}
+}
+
+impl FromStr for LabelSelector {
+	type Err = QuerentError;
+
+	fn from_str(selector: &str) -> Result<Self, Self::Err> {
+		let invalid = |reason: &str| {
+			QuerentError::user(format!("Invalid label selector `{}`: {}", selector, reason))
+		};
+		let mut requirements = Vec::new();
+		for part in split_requirements(selector).map_err(|reason| invalid(&reason))? {
+			let part = part.trim();
+			if part.is_empty() {
+				if selector.trim().is_empty() {
+					continue;
+				}
+				return Err(invalid("empty requirement"));
+			}
+			requirements.push(parse_requirement(part).map_err(|reason| invalid(&reason))?);
+		}
+		Ok(LabelSelector { requirements })
+	}
+}
+
+/// Splits a selector on the commas that are not inside a set.
+fn split_requirements(selector: &str) -> Result<Vec<&str>, String> {
+	let mut parts = Vec::new();
+	let mut depth = 0;
+	let mut start = 0;
+	for (index, c) in selector.char_indices() {
+		match c {
+			'(' if depth == 0 => depth = 1,
+			'(' => return Err("nested parentheses".to_string()),
+			')' if depth == 1 => depth = 0,
+			')' => return Err("unbalanced parentheses".to_string()),
+			',' if depth == 0 => {
+				parts.push(&selector[start..index]);
+				start = index + 1;
+			},
+			_ => {},
+		}
+	}
+	if depth != 0 {
+		return Err("unbalanced parentheses".to_string());
+	}
+	parts.push(&selector[start..]);
+	Ok(parts)
+}
+
+fn parse_requirement(part: &str) -> Result<LabelRequirement, String> {
+	if let Some(key) = part.strip_prefix('!') {
+		return Ok(LabelRequirement::DoesNotExist(parse_key(key)?));
+	}
+	if let Some((key, value)) = part.split_once("!=") {
+		return Ok(LabelRequirement::NotEquals(parse_key(key)?, parse_value(value)?));
+	}
+	if let Some((key, value)) = part.split_once("==").or_else(|| part.split_once('=')) {
+		return Ok(LabelRequirement::Equals(parse_key(key)?, parse_value(value)?));
+	}
+	if let Some(open) = part.find('(') {
+		let (head, set) = part.split_at(open);
+		let mut words = head.split_whitespace();
+		let (Some(key), Some(operator), None) = (words.next(), words.next(), words.next()) else {
+			return Err(format!("expected `<key> in (...)` or `<key> notin (...)`, got `{}`", part));
+		};
+		let values = parse_set(set)?;
+		return match operator {
+			"in" => Ok(LabelRequirement::In(parse_key(key)?, values)),
+			"notin" => Ok(LabelRequirement::NotIn(parse_key(key)?, values)),
+			_ => Err(format!("unknown operator `{}`", operator)),
+		};
+	}
+	Ok(LabelRequirement::Exists(parse_key(part)?))
+}
+
+fn parse_set(set: &str) -> Result<BTreeSet<String>, String> {
+	let inner = set
+		.trim()
+		.strip_prefix('(')
+		.and_then(|set| set.strip_suffix(')'))
+		.ok_or_else(|| format!("expected a set in parentheses, got `{}`", set.trim()))?;
+	let values = inner.split(',').map(parse_value).collect::<Result<BTreeSet<_>, _>>()?;
+	if values.iter().all(String::is_empty) {
+		return Err("empty set".to_string());
+	}
+	Ok(values)
+}
+
+fn parse_key(key: &str) -> Result<String, String> {
+	let key = key.trim();
+	if key.is_empty() {
+		return Err("missing label key".to_string());
+	}
+	if !key.chars().all(is_label_char) {
+		return Err(format!("invalid label key `{}`", key));
+	}
+	Ok(key.to_string())
+}
+
+fn parse_value(value: &str) -> Result<String, String> {
+	let value = value.trim();
+	if !value.chars().all(is_label_char) {
+		return Err(format!("invalid label value `{}`", value));
+	}
+	Ok(value.to_string())
+}
+
+/// Characters allowed in label keys and values, where `/` separates the prefix of a key.
+fn is_label_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/')
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+		pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+	}
+
+	#[test]
+	fn label_selector_should_parse_every_requirement() {
+		let selector: LabelSelector =
+			"tier=nightly, owner==data,env in (prod, staging), region notin (eu),!experimental, gpu, team!=ops"
+				.parse()
+				.unwrap();
+		assert_eq!(
+			selector.requirements(),
+			&[
+				LabelRequirement::Equals("tier".to_string(), "nightly".to_string()),
+				LabelRequirement::Equals("owner".to_string(), "data".to_string()),
+				LabelRequirement::In(
+					"env".to_string(),
+					["prod".to_string(), "staging".to_string()].into()
+				),
+				LabelRequirement::NotIn("region".to_string(), ["eu".to_string()].into()),
+				LabelRequirement::DoesNotExist("experimental".to_string()),
+				LabelRequirement::Exists("gpu".to_string()),
+				LabelRequirement::NotEquals("team".to_string(), "ops".to_string()),
+			]
+		);
+		assert_eq!(
+			selector.to_string(),
+			"tier=nightly, owner=data, env in (prod, staging), region notin (eu), !experimental, gpu, team!=ops"
+		);
+		assert_eq!(selector.to_string().parse::<LabelSelector>().unwrap(), selector);
+	}
+
+	#[test]
+	fn label_selector_should_match_labels() {
+		let selector: LabelSelector =
+			"env in (prod, staging), !experimental, region notin (eu)".parse().unwrap();
+		assert!(selector.matches(&labels(&[("env", "prod")])));
+		assert!(selector.matches(&labels(&[("env", "staging"), ("region", "us")])));
+		assert!(!selector.matches(&labels(&[("env", "dev")])));
+		assert!(!selector.matches(&labels(&[("env", "prod"), ("experimental", "")])));
+		assert!(!selector.matches(&labels(&[("env", "prod"), ("region", "eu")])));
+		assert!(!selector.matches(&labels(&[])));
+
+		assert!("".parse::<LabelSelector>().unwrap().matches(&labels(&[])));
+		assert!("nightly".parse::<LabelSelector>().unwrap().matches(&labels(&[("nightly", "")])));
+		assert!("team!=ops".parse::<LabelSelector>().unwrap().matches(&labels(&[])));
+	}
+
+	#[test]
+	fn label_selector_should_reject_malformed_selectors() {
+		for selector in [
+			"env in (prod",
+			"env in prod)",
+			"env within (prod)",
+			"env in ()",
+			"a,,b",
+			"=prod",
+			"env=a b",
+		] {
+			let error = selector.parse::<LabelSelector>().unwrap_err();
+			assert!(error.message.starts_with("Invalid label selector"), "{}", error);
+		}
+	}
+}
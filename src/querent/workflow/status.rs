@@ -0,0 +1,240 @@
+use crate::querent::QuerentError;
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::SystemTime};
+
+/// Lifecycle status of a workflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WorkflowStatus {
+	/// Registered and waiting to be started.
+	Pending,
+	/// Started, waiting for a worker of its `ResourceConfig` limits to become available.
+	Queued,
+	/// The Python entry point is executing.
+	Running,
+	/// An attempt failed and the workflow waits before being started again.
+	Retrying,
+	/// The entry point returned successfully.
+	Completed,
+	/// The entry point raised an error or could not be loaded.
+	Failed,
+	/// The run was cancelled before it finished.
+	Cancelled,
+	/// Not started because a workflow it depends on did not complete.
+	Skipped,
+}
+
+impl WorkflowStatus {
+	/// Returns true if no further transition happens without a new run.
+	pub fn is_terminal(&self) -> bool {
+		matches!(
+			self,
+			WorkflowStatus::Completed |
+				WorkflowStatus::Failed |
+				WorkflowStatus::Cancelled |
+				WorkflowStatus::Skipped
+		)
+	}
+
+	/// Returns true if a workflow in this status may move to `next`.
+	pub fn can_transition_to(&self, next: WorkflowStatus) -> bool {
+		use WorkflowStatus::*;
+		match (self, next) {
+			(Pending, Running) | (Pending, Cancelled) | (Pending, Failed) | (Pending, Skipped) =>
+				true,
+			(Pending, Queued) | (Retrying, Queued) => true,
+			(Queued, Running) | (Queued, Cancelled) => true,
+			(Running, Completed) | (Running, Failed) | (Running, Cancelled) => true,
+			(Running, Retrying) | (Retrying, Running) | (Retrying, Cancelled) => true,
+			// A finished workflow goes back to pending when it is started again.
+			(Completed, Pending) |
+			(Failed, Pending) |
+			(Cancelled, Pending) |
+			(Skipped, Pending) => true,
+			_ => false,
+		}
+	}
+}
+
+impl fmt::Display for WorkflowStatus {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(self, f)
+	}
+}
+
+/// A single entry of a workflow's status history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusTransition {
+	/// Status the workflow moved to.
+	pub status: WorkflowStatus,
+	/// Attempt of the run the transition belongs to, counting from 1. Zero before the
+	/// first attempt started.
+	pub attempt: u32,
+	/// When the transition happened.
+	pub timestamp: SystemTime,
+	/// Optional detail, such as the error that failed the workflow.
+	pub message: Option<String>,
+}
+
+/// Current status of a workflow together with every transition it went through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkflowState {
+	/// Unique identifier of the workflow.
+	pub workflow_id: String,
+	/// Current status.
+	pub status: WorkflowStatus,
+	/// Attempt of the current run, counting from 1. Zero before the first attempt started.
+	pub attempt: u32,
+	/// All transitions, oldest first. The first entry is the registration.
+	pub history: Vec<StatusTransition>,
+}
+
+impl WorkflowState {
+	/// Creates the state of a freshly registered workflow.
+	pub fn new(workflow_id: &str) -> Self {
+		WorkflowState {
+			workflow_id: workflow_id.to_string(),
+			status: WorkflowStatus::Pending,
+			attempt: 0,
+			history: vec![StatusTransition {
+				status: WorkflowStatus::Pending,
+				attempt: 0,
+				timestamp: SystemTime::now(),
+				message: None,
+			}],
+		}
+	}
+
+	/// Moves the workflow to `next`, recording the transition in the history.
+	///
+	/// Every move to `Running` starts a new attempt; going back to `Pending` starts a new run.
+	pub fn transition(
+		&mut self,
+		next: WorkflowStatus,
+		message: Option<String>,
+	) -> Result<WorkflowStatusEvent, QuerentError> {
+		if !self.status.can_transition_to(next) {
+			return Err(QuerentError::user(format!(
+				"Workflow {} cannot move from {} to {}",
+				self.workflow_id, self.status, next
+			)));
+		}
+		match next {
+			WorkflowStatus::Pending => self.attempt = 0,
+			WorkflowStatus::Running => self.attempt += 1,
+			_ => {},
+		}
+		let transition = StatusTransition {
+			status: next,
+			attempt: self.attempt,
+			timestamp: SystemTime::now(),
+			message,
+		};
+		let event = WorkflowStatusEvent {
+			workflow_id: self.workflow_id.clone(),
+			from: self.status,
+			to: next,
+			attempt: self.attempt,
+			timestamp: transition.timestamp,
+			message: transition.message.clone(),
+		};
+		self.status = next;
+		self.history.push(transition);
+		Ok(event)
+	}
+
+	/// When the most recent run started, if the workflow has been started.
+	pub fn started_at(&self) -> Option<SystemTime> {
+		self.last_transition_to(WorkflowStatus::Running)
+	}
+
+	/// When the most recent run finished, if it has finished.
+	pub fn finished_at(&self) -> Option<SystemTime> {
+		if self.status.is_terminal() {
+			self.history.last().map(|t| t.timestamp)
+		} else {
+			None
+		}
+	}
+
+	fn last_transition_to(&self, status: WorkflowStatus) -> Option<SystemTime> {
+		self.history.iter().rev().find(|t| t.status == status).map(|t| t.timestamp)
+	}
+}
+
+/// Published by the `WorkflowManager` every time a workflow changes status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkflowStatusEvent {
+	/// Unique identifier of the workflow.
+	pub workflow_id: String,
+	/// Status before the transition.
+	pub from: WorkflowStatus,
+	/// Status after the transition.
+	pub to: WorkflowStatus,
+	/// Attempt of the run the transition belongs to, counting from 1.
+	pub attempt: u32,
+	/// When the transition happened.
+	pub timestamp: SystemTime,
+	/// Optional detail, such as the error that failed the workflow.
+	pub message: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn workflow_state_should_record_every_transition() {
+		let mut state = WorkflowState::new("wf");
+		assert_eq!(state.status, WorkflowStatus::Pending);
+		assert!(state.started_at().is_none());
+
+		let event = state.transition(WorkflowStatus::Running, None).unwrap();
+		assert_eq!(event.from, WorkflowStatus::Pending);
+		assert_eq!(event.to, WorkflowStatus::Running);
+		assert!(state.started_at().is_some());
+		assert!(state.finished_at().is_none());
+
+		state.transition(WorkflowStatus::Failed, Some("boom".to_string())).unwrap();
+		assert!(state.finished_at().is_some());
+		assert_eq!(state.history.len(), 3);
+		assert_eq!(state.history[2].message.as_deref(), Some("boom"));
+	}
+
+	#[test]
+	fn workflow_state_should_reject_invalid_transitions() {
+		let mut state = WorkflowState::new("wf");
+		assert!(state.transition(WorkflowStatus::Completed, None).is_err());
+
+		state.transition(WorkflowStatus::Running, None).unwrap();
+		assert!(state.transition(WorkflowStatus::Running, None).is_err());
+
+		state.transition(WorkflowStatus::Completed, None).unwrap();
+		assert!(state.transition(WorkflowStatus::Running, None).is_err());
+		assert!(state.transition(WorkflowStatus::Pending, None).is_ok());
+		assert_eq!(state.history.len(), 4);
+	}
+
+	#[test]
+	fn workflow_state_should_count_attempts() {
+		let mut state = WorkflowState::new("wf");
+		state.transition(WorkflowStatus::Running, None).unwrap();
+		state.transition(WorkflowStatus::Retrying, Some("flaky".to_string())).unwrap();
+		let event = state.transition(WorkflowStatus::Running, None).unwrap();
+		assert_eq!(event.attempt, 2);
+		state.transition(WorkflowStatus::Completed, None).unwrap();
+		let attempts: Vec<_> = state.history.iter().map(|t| (t.status, t.attempt)).collect();
+		assert_eq!(
+			attempts,
+			vec![
+				(WorkflowStatus::Pending, 0),
+				(WorkflowStatus::Running, 1),
+				(WorkflowStatus::Retrying, 1),
+				(WorkflowStatus::Running, 2),
+				(WorkflowStatus::Completed, 2),
+			]
+		);
+
+		state.transition(WorkflowStatus::Pending, None).unwrap();
+		assert_eq!(state.attempt, 0);
+	}
+}
@@ -0,0 +1,229 @@
+use crate::querent::ConfigInjection;
+use pyo3::{
+	prelude::*,
+	types::{PyDict, PyFunction, PyTuple},
+};
+use std::fmt;
+
+use super::{ModuleCache, Runnable, RunnableConfig};
+
+/// How serious a problem found by `RunnableManager::validate` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+	/// The runnable may still run, but probably not as intended.
+	Warning,
+	/// The runnable is bound to fail when started.
+	Error,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Severity::Warning => write!(f, "warning"),
+			Severity::Error => write!(f, "error"),
+		}
+	}
+}
+
+/// Problem found in a runnable before starting it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+	/// Unique identifier of the runnable.
+	pub workflow_id: String,
+	/// How serious the problem is.
+	pub severity: Severity,
+	/// What is wrong, in a form meant for people.
+	pub message: String,
+}
+
+impl Diagnostic {
+	pub(crate) fn error(workflow_id: &str, message: String) -> Self {
+		Self { workflow_id: workflow_id.to_string(), severity: Severity::Error, message }
+	}
+
+	pub(crate) fn warning(workflow_id: &str, message: String) -> Self {
+		Self { workflow_id: workflow_id.to_string(), severity: Severity::Warning, message }
+	}
+
+	/// Returns true if the runnable is bound to fail when started.
+	pub fn is_error(&self) -> bool {
+		self.severity == Severity::Error
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} in {}: {}", self.severity, self.workflow_id, self.message)
+	}
+}
+
+/// Loads the module of a runnable and checks that its start function can be awaited with the
+/// arguments it will be given, without calling it. `upstream_results` is the number of
+/// arguments appended by the workflows it depends on.
+pub(crate) fn check_entry_point<R: Runnable>(
+	py: Python<'_>,
+	modules: &ModuleCache,
+	runnable: &R,
+	upstream_results: usize,
+) -> Vec<Diagnostic> {
+	let id = runnable.id();
+	let module = match modules.load(py, runnable, false) {
+		Ok(module) => module,
+		Err(e) => {
+			let source = match (runnable.code(), runnable.path()) {
+				(Some(_), _) => "Unable to compile code".to_string(),
+				(None, Some(path)) => format!("Unable to load {}", path.display()),
+				(None, None) => format!("Unable to import module {}", runnable.import()),
+			};
+			return vec![Diagnostic::error(id, format!("{}: {}", source, e))];
+		},
+	};
+
+	let attr = runnable.attr();
+	let fun = match module.getattr(attr) {
+		Ok(fun) => fun,
+		Err(_) => {
+			let suggestion = close_match(py, module, attr)
+				.map(|name| format!(", did you mean {}?", name))
+				.unwrap_or_default();
+			return vec![Diagnostic::error(
+				id,
+				format!("Module {} has no attribute {}{}", module_name(module), attr, suggestion),
+			)];
+		},
+	};
+	// Entry points are resolved as Python functions, other callables are rejected on start.
+	if fun.downcast::<PyFunction>().is_err() {
+		return vec![Diagnostic::error(
+			id,
+			format!("Start function {} is not a function but a {}", attr, type_name(fun)),
+		)];
+	}
+
+	let mut diagnostics = Vec::new();
+	match entry_point_kind(py, fun) {
+		Ok(EntryPointKind::Coroutine) => {},
+		Ok(EntryPointKind::AsyncGenerator) => diagnostics.push(Diagnostic::error(
+			id,
+			format!(
+				"Start function {} is an async generator, which only PyRuntime::call_stream can run",
+				attr
+			),
+		)),
+		Ok(EntryPointKind::Sync) => diagnostics.push(Diagnostic::warning(
+			id,
+			format!(
+				"Start function {} is not a coroutine function, it will run on a blocking worker thread",
+				attr
+			),
+		)),
+		Err(e) => diagnostics.push(Diagnostic::warning(
+			id,
+			format!("Unable to inspect start function {}: {}", attr, e),
+		)),
+	}
+	if let Some(diagnostic) = check_arity(py, fun, runnable, upstream_results) {
+		diagnostics.push(diagnostic);
+	}
+	diagnostics
+}
+
+enum EntryPointKind {
+	Coroutine,
+	AsyncGenerator,
+	Sync,
+}
+
+fn entry_point_kind(py: Python<'_>, fun: &PyAny) -> PyResult<EntryPointKind> {
+	let inspect = py.import("inspect")?;
+	if inspect.call_method1("iscoroutinefunction", (fun,))?.is_true()? {
+		Ok(EntryPointKind::Coroutine)
+	} else if inspect.call_method1("isasyncgenfunction", (fun,))?.is_true()? {
+		Ok(EntryPointKind::AsyncGenerator)
+	} else {
+		Ok(EntryPointKind::Sync)
+	}
+}
+
+/// Binds placeholders for the arguments, keyword arguments and configuration the runnable
+/// will be called with to the signature of its start function.
+fn check_arity<R: Runnable>(
+	py: Python<'_>,
+	fun: &PyAny,
+	runnable: &R,
+	upstream_results: usize,
+) -> Option<Diagnostic> {
+	let mut positional = runnable.arguments().len() + upstream_results;
+	let mut keywords: Vec<String> = runnable.kwargs().into_keys().collect();
+	keywords.sort();
+	if !matches!(runnable.config(), RunnableConfig::None) {
+		match runnable.config_injection() {
+			ConfigInjection::Positional => positional += 1,
+			ConfigInjection::Keyword(name) => keywords.push(name),
+			ConfigInjection::Omitted => {},
+		}
+	}
+
+	let signature = match py
+		.import("inspect")
+		.and_then(|inspect| inspect.call_method1("signature", (fun,)))
+	{
+		Ok(signature) => signature,
+		// Builtins and some extension functions have no signature to check against.
+		Err(e) =>
+			return Some(Diagnostic::warning(
+				runnable.id(),
+				format!("Unable to read the signature of {}: {}", runnable.attr(), e),
+			)),
+	};
+	let bound = (|| -> PyResult<()> {
+		let args = PyTuple::new(py, vec![py.None(); positional]);
+		let kwargs = PyDict::new(py);
+		for keyword in &keywords {
+			kwargs.set_item(keyword, py.None())?;
+		}
+		signature.call_method("bind", args, Some(kwargs))?;
+		Ok(())
+	})();
+	bound.err().map(|e| {
+		let keywords = if keywords.is_empty() {
+			String::new()
+		} else {
+			format!(" and keyword arguments {}", keywords.join(", "))
+		};
+		Diagnostic::error(
+			runnable.id(),
+			format!(
+				"Start function {}{} cannot be called with {} positional arguments{}: {}",
+				runnable.attr(),
+				signature,
+				positional,
+				keywords,
+				e.value(py)
+			),
+		)
+	})
+}
+
+/// Closest attribute name of the module, to point out misspellings.
+fn close_match(py: Python<'_>, module: &PyModule, attr: &str) -> Option<String> {
+	let names = module.dir();
+	let matches = py
+		.import("difflib")
+		.ok()?
+		.call_method1("get_close_matches", (attr, names, 1))
+		.ok()?;
+	matches.get_item(0).ok()?.extract().ok()
+}
+
+fn module_name(module: &PyModule) -> String {
+	module.name().map(str::to_string).unwrap_or_else(|_| "<unknown>".to_string())
+}
+
+fn type_name(value: &PyAny) -> String {
+	value
+		.get_type()
+		.name()
+		.map(str::to_string)
+		.unwrap_or_else(|_| "<unknown>".to_string())
+}
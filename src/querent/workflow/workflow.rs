@@ -1,19 +1,19 @@
 use crate::{
-	callbacks::{EventCallbackInterface, PyEventCallbackInterface},
-	comm::ChannelHandler,
 	config::Config,
-	cross::{CLRepr, CLReprPython},
-	querent::{py_runtime, PyRuntime, QuerentError},
-	tokio_runtime,
+	cross::CLRepr,
+	querent::{ConfigInjection, TaskPriority},
 };
-use futures::TryFutureExt;
-use log;
-use pyo3::{prelude::*, types::PyFunction};
-use std::{collections::HashMap, sync::Mutex};
-use tokio::runtime::Runtime;
+use pyo3::prelude::*;
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime},
+};
+
+use super::{RetryPolicy, Runnable, RunnableConfig, RunnableManager, Schedule, WorkflowDependency};
 
 /// Represents a workflow.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 #[pyclass]
 pub struct Workflow {
 	/// Name of the workflow.
@@ -26,142 +26,103 @@ pub struct Workflow {
 	pub attr: String,
 	/// Optional Python code to execute instead of importing a module.
 	pub code: Option<String>,
+	/// Optional Python file or package directory to load instead of importing a module.
+	pub path: Option<PathBuf>,
 	/// Arguments to pass to the workflow's start function.
 	pub arguments: Vec<CLRepr>,
+	/// Keyword arguments to pass to the workflow's start function.
+	pub kwargs: HashMap<String, CLRepr>,
 	/// Optional configuration for the workflow.
 	pub config: Option<Config>,
+	/// How the configuration is passed to the start function.
+	pub config_injection: ConfigInjection,
+	/// Maximum time a single run may take before it is cancelled.
+	pub timeout: Option<Duration>,
+	/// Point in time after which a run is cancelled, regardless of when it started.
+	pub deadline: Option<SystemTime>,
+	/// Optional policy to start the workflow again when it fails.
+	pub retry: Option<RetryPolicy>,
+	/// Workflows that must complete before this one starts.
+	pub dependencies: Vec<WorkflowDependency>,
+	/// Optional recurring schedule, applied when the workflow is added to `Querent`.
+	pub schedule: Option<Schedule>,
+	/// Labels to select the workflow by, such as `tier=nightly`. A tag is a label without
+	/// a value.
+	pub labels: HashMap<String, String>,
+	/// Priority of the workflow's runs in the queue of the Python runtime. Bulk ingestions
+	/// can be lowered so they yield to interactive work.
+	pub priority: TaskPriority,
 }
 
-/// Manages workflows and their execution.
-pub struct WorkflowManager {
-	/// Mutex-protected map of workflows, keyed by their unique identifier.
-	pub workflows: Mutex<HashMap<String, Workflow>>,
-	/// Reference to the Python runtime.
-	pub runtime: &'static PyRuntime,
-}
+impl Runnable for Workflow {
+	fn id(&self) -> &str {
+		&self.id
+	}
 
-impl WorkflowManager {
-	/// Creates a new `WorkflowManager` instance.
-	pub fn new() -> Result<Self, String> {
-		let runtime = py_runtime().map_err(|e| e.to_string())?;
-		Ok(Self { workflows: Mutex::new(HashMap::new()), runtime })
-	}
-
-	/// Adds a workflow to the manager.
-	pub fn add_workflow(&self, workflow: Workflow) -> Result<(), String> {
-		let mut workflows =
-			self.workflows.lock().map_err(|e| format!("Mutex lock failed: {}", e))?;
-		if workflows.contains_key(&workflow.id) {
-			return Err("Workflow with the same ID already exists.".to_string());
-		} else {
-			workflows.insert(workflow.id.clone(), workflow.clone());
-		}
-		Ok(())
-	}
-
-	/// Retrieves a list of all workflows managed by this manager.
-	pub fn get_workflows(&self) -> Vec<Workflow> {
-		let workflows = self.workflows.lock().unwrap();
-		workflows.values().cloned().collect()
-	}
-
-	/// Starts workflows by executing their Python code asynchronously.
-	pub async fn start_workflows(&self) -> Result<(), QuerentError> {
-		let workflows = self.get_workflows();
-		let handles: Vec<_> = workflows
-			.iter()
-			.map(|_workflow| {
-				let args = _workflow.arguments.clone();
-				let res = match &_workflow.code {
-					None => Python::with_gil(|py| {
-						let async_mod = py.import(_workflow.import.as_str()).map_err(|e| {
-							log::error!("Failed to import module {}: {}", _workflow.import, e);
-							QuerentError::internal(e.to_string())
-						})?;
-
-						let coroutine =
-							async_mod.getattr(_workflow.attr.as_str()).map_err(|_| {
-								log::error!("Failed to find start function.");
-								QuerentError::internal("Failed to find start function.".to_string())
-							})?;
-
-						let querent_py_fun: Py<PyFunction> = coroutine.extract().map_err(|e| {
-							log::error!("Failed to extract function: {}", e);
-							QuerentError::internal(e.to_string())
-						})?;
-
-						let call_future = self.runtime.call_async(
-							querent_py_fun,
-							args,
-							_workflow.config.clone(),
-							None,
-						);
-						Ok(call_future)
-					}),
-					Some(code) => {
-						let module_file: String = _workflow.id.clone() + ".py";
-						Python::with_gil(|py| {
-							let dynamic_module = PyModule::from_code(
-								py,
-								code.as_str(),
-								module_file.as_str(),
-								_workflow.name.as_str(),
-							)
-							.map_err(|e| {
-								log::error!("Failed to import module {}: {}", _workflow.import, e);
-								QuerentError::internal(e.to_string())
-							})?;
-
-							let attr_fun =
-								dynamic_module.getattr(_workflow.attr.as_str()).map_err(|_| {
-									log::error!("Failed to find start function.");
-									QuerentError::internal(
-										"Failed to find start function.".to_string(),
-									)
-								})?;
-
-							let querent_py_fun: Py<PyFunction> =
-								attr_fun.extract().map_err(|e| {
-									log::error!("Failed to extract function: {}", e);
-									QuerentError::internal(e.to_string())
-								})?;
-
-							let call_future = self.runtime.call_async(
-								querent_py_fun,
-								args,
-								_workflow.config.clone(),
-								None,
-							);
-							Ok(call_future)
-						})
-					},
-				};
-				res
-			})
-			.collect();
-		for handle in handles {
-			match handle {
-				Ok(future) => match future.await {
-					Ok(_) => log::info!("Workflow started."),
-					Err(e) => {
-						log::error!("Failed to start workflow: {}", e);
-						return Err(QuerentError::internal(e.to_string()));
-					},
-				},
-				Err(e) => {
-					log::error!("Failed to start workflow: {}", e);
-					return Err(e);
-				},
-			}
-		}
-		Ok(())
+	fn name(&self) -> &str {
+		&self.name
+	}
+
+	fn import(&self) -> &str {
+		&self.import
+	}
+
+	fn attr(&self) -> &str {
+		&self.attr
+	}
+
+	fn code(&self) -> Option<&str> {
+		self.code.as_deref()
+	}
+
+	fn path(&self) -> Option<&Path> {
+		self.path.as_deref()
+	}
+
+	fn arguments(&self) -> &[CLRepr] {
+		&self.arguments
+	}
+
+	fn push_argument(&mut self, argument: CLRepr) {
+		self.arguments.push(argument);
 	}
-}
 
-impl Drop for WorkflowManager {
-	/// Drops the `WorkflowManager` instance, cleaning up resources.
-	fn drop(&mut self) {
-		log::info!("Dropping WorkflowManager");
-		let _ = self.runtime;
+	fn kwargs(&self) -> HashMap<String, CLRepr> {
+		self.kwargs.clone()
+	}
+
+	fn config(&self) -> RunnableConfig {
+		self.config.clone().map_or(RunnableConfig::None, RunnableConfig::Config)
+	}
+
+	fn config_injection(&self) -> ConfigInjection {
+		self.config_injection.clone()
+	}
+
+	fn timeout(&self) -> Option<Duration> {
+		self.timeout
+	}
+
+	fn deadline(&self) -> Option<SystemTime> {
+		self.deadline
+	}
+
+	fn retry(&self) -> Option<&RetryPolicy> {
+		self.retry.as_ref()
+	}
+
+	fn dependencies(&self) -> &[WorkflowDependency] {
+		&self.dependencies
+	}
+
+	fn labels(&self) -> HashMap<String, String> {
+		self.labels.clone()
+	}
+
+	fn priority(&self) -> TaskPriority {
+		self.priority
 	}
 }
+
+/// Manages workflows and their execution.
+pub type WorkflowManager = RunnableManager<Workflow>;
@@ -3,9 +3,16 @@ use crate::{
 	comm::ChannelHandler,
 	config::Config,
 	cross::{CLRepr, StringType},
+	querent::{ConfigInjection, TaskPriority},
 };
 
-use super::Workflow;
+use std::{
+	collections::HashMap,
+	path::PathBuf,
+	time::{Duration, SystemTime},
+};
+
+use super::{RetryPolicy, Schedule, Workflow, WorkflowDependency};
 
 /// Builder for constructing a `Workflow`.
 pub struct WorkflowBuilder {
@@ -14,8 +21,18 @@ pub struct WorkflowBuilder {
 	import: Option<String>,
 	attr: Option<String>,
 	code: Option<String>,
+	path: Option<PathBuf>,
 	arguments: Vec<CLRepr>,
+	kwargs: HashMap<String, CLRepr>,
 	config: Option<Config>,
+	config_injection: ConfigInjection,
+	timeout: Option<Duration>,
+	deadline: Option<SystemTime>,
+	retry: Option<RetryPolicy>,
+	dependencies: Vec<WorkflowDependency>,
+	schedule: Option<Schedule>,
+	labels: HashMap<String, String>,
+	priority: TaskPriority,
 }
 
 impl WorkflowBuilder {
@@ -27,8 +44,18 @@ impl WorkflowBuilder {
 			import: None,
 			attr: None,
 			code: None,
+			path: None,
 			arguments: Vec::new(),
+			kwargs: HashMap::new(),
 			config: None,
+			config_injection: ConfigInjection::default(),
+			timeout: None,
+			deadline: None,
+			retry: None,
+			dependencies: Vec::new(),
+			schedule: None,
+			labels: HashMap::new(),
+			priority: TaskPriority::default(),
 		}
 	}
 
@@ -40,8 +67,18 @@ impl WorkflowBuilder {
 			import: Some(workflow.import),
 			attr: Some(workflow.attr),
 			code: workflow.code,
+			path: workflow.path,
 			arguments: workflow.arguments,
+			kwargs: workflow.kwargs,
 			config: workflow.config,
+			config_injection: workflow.config_injection,
+			timeout: workflow.timeout,
+			deadline: workflow.deadline,
+			retry: workflow.retry,
+			dependencies: workflow.dependencies,
+			schedule: workflow.schedule,
+			labels: workflow.labels,
+			priority: workflow.priority,
 		}
 	}
 
@@ -68,18 +105,106 @@ impl WorkflowBuilder {
 		self.code = code;
 		self
 	}
+
+	/// Sets the Python file, or package directory containing an `__init__.py`, to load
+	/// instead of importing a module.
+	pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+		self.path = Some(path.into());
+		self
+	}
+
 	/// add arguments to the workflow
 	pub fn arguments(mut self, arguments: Vec<CLRepr>) -> Self {
 		self.arguments = arguments;
 		self
 	}
 
+	/// Sets the keyword arguments of the workflow.
+	pub fn kwargs(mut self, kwargs: HashMap<String, CLRepr>) -> Self {
+		self.kwargs = kwargs;
+		self
+	}
+
+	/// Adds a keyword argument to the workflow.
+	pub fn kwarg(mut self, name: &str, value: CLRepr) -> Self {
+		self.kwargs.insert(name.to_string(), value);
+		self
+	}
+
 	/// Sets the configuration for the workflow.
 	pub fn config(mut self, config: Config) -> Self {
 		self.config = Some(config);
 		self
 	}
 
+	/// Sets how the configuration is passed to the start function.
+	pub fn config_injection(mut self, config_injection: ConfigInjection) -> Self {
+		self.config_injection = config_injection;
+		self
+	}
+
+	/// Sets the maximum time a single run of the workflow may take.
+	pub fn timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = Some(timeout);
+		self
+	}
+
+	/// Sets the point in time after which a run of the workflow is cancelled. A failed attempt
+	/// is not retried when the retry policy would only start the next one after it.
+	pub fn deadline(mut self, deadline: SystemTime) -> Self {
+		self.deadline = Some(deadline);
+		self
+	}
+
+	/// Sets the policy used to start the workflow again when it fails.
+	pub fn retry(mut self, retry: RetryPolicy) -> Self {
+		self.retry = Some(retry);
+		self
+	}
+
+	/// Starts the workflow only after the given workflow completed.
+	pub fn depends_on(mut self, workflow_id: &str) -> Self {
+		self.dependencies.push(WorkflowDependency::after(workflow_id));
+		self
+	}
+
+	/// Starts the workflow only after the given workflow completed, appending the value it
+	/// returned to the arguments.
+	pub fn depends_on_result(mut self, workflow_id: &str) -> Self {
+		self.dependencies.push(WorkflowDependency::with_result(workflow_id));
+		self
+	}
+
+	/// Sets a label of the workflow, such as `env=prod`.
+	pub fn label(mut self, key: &str, value: &str) -> Self {
+		self.labels.insert(key.to_string(), value.to_string());
+		self
+	}
+
+	/// Sets the labels of the workflow, replacing those set so far.
+	pub fn labels(mut self, labels: HashMap<String, String>) -> Self {
+		self.labels = labels;
+		self
+	}
+
+	/// Tags the workflow, that is sets a label without a value, which the selector `<tag>`
+	/// matches.
+	pub fn tag(self, tag: &str) -> Self {
+		self.label(tag, "")
+	}
+
+	/// Sets the priority of the workflow's runs in the queue of the Python runtime.
+	pub fn priority(mut self, priority: TaskPriority) -> Self {
+		self.priority = priority;
+		self
+	}
+
+	/// Sets the recurring schedule of the workflow.
+	pub fn schedule(mut self, schedule: Schedule) -> Self {
+		self.schedule = Some(schedule);
+		self
+	}
+
 	/// Builds the `Workflow` using the configured parameters.
 	pub fn build(self) -> Workflow {
 		Workflow {
@@ -88,8 +213,18 @@ impl WorkflowBuilder {
 			import: self.import.unwrap_or_default(),
 			attr: self.attr.unwrap_or_default(),
 			code: self.code,
+			path: self.path,
 			arguments: self.arguments,
+			kwargs: self.kwargs,
 			config: self.config,
+			config_injection: self.config_injection,
+			timeout: self.timeout,
+			deadline: self.deadline,
+			retry: self.retry,
+			dependencies: self.dependencies,
+			schedule: self.schedule,
+			labels: self.labels,
+			priority: self.priority,
 		}
 	}
 }
@@ -23,6 +23,7 @@ fn test_callback_interface() {
 			event_type: EventType::Graph,
 			timestamp: 123.45,
 			payload: "TestPayload".to_string(),
+			structured_payload: None,
 			file: "TestFile".to_string(),
 			doc_source: "file://folder".to_string(),
 			image_id: Some("123456".to_string()),
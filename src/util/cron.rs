@@ -0,0 +1,246 @@
+//! Module that parses cron expressions and computes their next occurrence
+use std::{
+	fmt,
+	str::FromStr,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::time::SECS_PER_DAY;
+
+/// Number of days searched for the next occurrence, enough to reach any 29th of February.
+const SEARCH_DAYS: i64 = 366 * 8;
+
+const MONTH_NAMES: [&str; 12] =
+	["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// A standard five field cron expression: minute, hour, day of month, month and day of week.
+///
+/// Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`), steps (`*/10`, `0-30/5`) and
+/// three letter month and weekday names. `@hourly`, `@daily`, `@weekly`, `@monthly` and
+/// `@yearly` are accepted as well. Expressions are evaluated in UTC.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CronExpression {
+	source: String,
+	minutes: u64,
+	hours: u64,
+	days_of_month: u64,
+	months: u64,
+	days_of_week: u64,
+	/// Whether day of month and day of week were both restricted, in which case a day
+	/// matching either of them matches.
+	either_day: bool,
+}
+
+impl CronExpression {
+	/// Parses a cron expression.
+	pub fn parse(expression: &str) -> Result<Self, String> {
+		let source = expression.trim();
+		let expanded = match source {
+			"@hourly" => "0 * * * *",
+			"@daily" | "@midnight" => "0 0 * * *",
+			"@weekly" => "0 0 * * 0",
+			"@monthly" => "0 0 1 * *",
+			"@yearly" | "@annually" => "0 0 1 1 *",
+			other => other,
+		};
+		let fields: Vec<&str> = expanded.split_whitespace().collect();
+		if fields.len() != 5 {
+			return Err(format!(
+				"Invalid cron expression {:?}: expected 5 fields, found {}",
+				source,
+				fields.len()
+			));
+		}
+		let parse = |index: usize, name: &str, min: u32, max: u32, names: &[&str]| {
+			parse_field(fields[index], min, max, names)
+				.map_err(|e| format!("Invalid cron expression {:?}: {} field {}", source, name, e))
+		};
+		let mut days_of_week = parse(4, "day of week", 0, 7, &WEEKDAY_NAMES)?;
+		// Both 0 and 7 are Sunday.
+		if days_of_week & (1 << 7) != 0 {
+			days_of_week = (days_of_week | 1) & !(1 << 7);
+		}
+		Ok(CronExpression {
+			source: source.to_string(),
+			minutes: parse(0, "minute", 0, 59, &[])?,
+			hours: parse(1, "hour", 0, 23, &[])?,
+			days_of_month: parse(2, "day of month", 1, 31, &[])?,
+			months: parse(3, "month", 1, 12, &MONTH_NAMES)?,
+			days_of_week,
+			either_day: !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+		})
+	}
+
+	/// Returns the first occurrence strictly after `after`, truncated to the minute.
+	pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+		let secs = after.duration_since(UNIX_EPOCH).ok()?.as_secs();
+		let start_minute = secs / 60 + 1;
+		let start_day = (start_minute * 60 / SECS_PER_DAY) as i64;
+		let start_minute_of_day = (start_minute % (24 * 60)) as u32;
+
+		for day in start_day..start_day + SEARCH_DAYS {
+			if !self.matches_day(day) {
+				continue;
+			}
+			let first_minute = if day == start_day { start_minute_of_day } else { 0 };
+			for minute_of_day in first_minute..24 * 60 {
+				let (hour, minute) = (minute_of_day / 60, minute_of_day % 60);
+				if self.hours & (1 << hour) != 0 && self.minutes & (1 << minute) != 0 {
+					let secs = day as u64 * SECS_PER_DAY + minute_of_day as u64 * 60;
+					return Some(UNIX_EPOCH + Duration::from_secs(secs));
+				}
+			}
+		}
+		None
+	}
+
+	fn matches_day(&self, days_since_epoch: i64) -> bool {
+		let (_, month, day) = civil_from_days(days_since_epoch);
+		if self.months & (1 << month) == 0 {
+			return false;
+		}
+		// 1970-01-01 was a Thursday.
+		let weekday = (days_since_epoch + 4).rem_euclid(7);
+		let day_of_month = self.days_of_month & (1 << day) != 0;
+		let day_of_week = self.days_of_week & (1 << weekday) != 0;
+		if self.either_day {
+			day_of_month || day_of_week
+		} else {
+			day_of_month && day_of_week
+		}
+	}
+}
+
+impl FromStr for CronExpression {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		CronExpression::parse(s)
+	}
+}
+
+impl fmt::Debug for CronExpression {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_tuple("CronExpression").field(&self.source).finish()
+	}
+}
+
+impl fmt::Display for CronExpression {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.source)
+	}
+}
+
+/// Parses one field into a bit set of the allowed values.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+	let mut bits = 0u64;
+	for part in field.split(',') {
+		let (range, step) = match part.split_once('/') {
+			Some((range, step)) => {
+				let step: u32 =
+					step.parse().map_err(|_| format!("has an invalid step {:?}", step))?;
+				if step == 0 {
+					return Err("has a step of 0".to_string());
+				}
+				(range, step)
+			},
+			None => (part, 1),
+		};
+		let (start, end) = if range == "*" {
+			(min, max)
+		} else if let Some((start, end)) = range.split_once('-') {
+			(parse_value(start, min, max, names)?, parse_value(end, min, max, names)?)
+		} else {
+			let value = parse_value(range, min, max, names)?;
+			// `5/15` means every 15 starting at 5.
+			(value, if part.contains('/') { max } else { value })
+		};
+		if start > end {
+			return Err(format!("has an empty range {:?}", range));
+		}
+		for value in (start..=end).step_by(step as usize) {
+			bits |= 1 << value;
+		}
+	}
+	Ok(bits)
+}
+
+fn parse_value(value: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+	let upper = value.to_ascii_uppercase();
+	let parsed = match names.iter().position(|name| *name == upper) {
+		// Month names start at 1, weekday names at 0.
+		Some(index) => index as u32 + min,
+		None => value.parse().map_err(|_| format!("has an invalid value {:?}", value))?,
+	};
+	if parsed < min || parsed > max {
+		return Err(format!("value {} is outside {}-{}", parsed, min, max));
+	}
+	Ok(parsed)
+}
+
+/// Converts days since the epoch to a (year, month, day) date in the proleptic Gregorian
+/// calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let doe = z.rem_euclid(146_097);
+	let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+	let year = yoe + era * 400 + i64::from(month <= 2);
+	(year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn at(secs: u64) -> SystemTime {
+		UNIX_EPOCH + Duration::from_secs(secs)
+	}
+
+	// 2024-01-01T00:00:00Z, a Monday.
+	const NEW_YEAR_2024: u64 = 1_704_067_200;
+
+	#[test]
+	fn cron_expression_should_find_next_occurrence() {
+		let every_ten = CronExpression::parse("*/10 * * * *").unwrap();
+		assert_eq!(every_ten.next_after(at(NEW_YEAR_2024)), Some(at(NEW_YEAR_2024 + 600)));
+		assert_eq!(every_ten.next_after(at(NEW_YEAR_2024 + 1)), Some(at(NEW_YEAR_2024 + 600)));
+
+		let nightly = CronExpression::parse("30 2 * * *").unwrap();
+		assert_eq!(nightly.next_after(at(NEW_YEAR_2024)), Some(at(NEW_YEAR_2024 + 9_000)));
+
+		let fridays = CronExpression::parse("0 9 * * FRI").unwrap();
+		assert_eq!(
+			fridays.next_after(at(NEW_YEAR_2024)),
+			Some(at(NEW_YEAR_2024 + 4 * SECS_PER_DAY + 9 * 3_600))
+		);
+
+		let leap_day = CronExpression::parse("0 0 29 feb *").unwrap();
+		assert_eq!(leap_day.next_after(at(NEW_YEAR_2024)), Some(at(1_709_164_800)));
+
+		let yearly = CronExpression::parse("@yearly").unwrap();
+		assert_eq!(yearly.next_after(at(NEW_YEAR_2024)), Some(at(1_735_689_600)));
+	}
+
+	#[test]
+	fn cron_expression_should_reject_invalid_fields() {
+		assert!(CronExpression::parse("* * * *").is_err());
+		assert!(CronExpression::parse("60 * * * *").is_err());
+		assert!(CronExpression::parse("*/0 * * * *").is_err());
+		assert!(CronExpression::parse("5-1 * * * *").is_err());
+		assert!(CronExpression::parse("0 0 * FOO *").is_err());
+		assert!(CronExpression::parse("0 0 1,15 * 7").is_ok());
+	}
+
+	#[test]
+	fn civil_from_days_should_convert_dates() {
+		assert_eq!(civil_from_days(0), (1970, 1, 1));
+		assert_eq!(civil_from_days((NEW_YEAR_2024 / SECS_PER_DAY) as i64), (2024, 1, 1));
+		assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+	}
+}
@@ -1,4 +1,5 @@
 //! Implemented helpers and utilities
+pub mod cron;
 pub mod time;
 pub mod transactional_hashmap;
 pub mod transactional_vec;